@@ -0,0 +1,96 @@
+//! `agent` commands, managing the agent keeping Ids unlocked
+use crate::{opts::AgentStart, prelude::*};
+#[cfg(unix)]
+use crev_lib::Local;
+
+#[cfg(unix)]
+pub fn start(args: &AgentStart) -> Result<()> {
+    use crev_lib::agent::AgentClient;
+    use std::{
+        process::{Command, Stdio},
+        time::Duration,
+    };
+
+    let local = Local::auto_open()?;
+    if args.foreground {
+        local.serve_agent(Duration::from_secs(args.timeout_minutes * 60))?;
+        return Ok(());
+    }
+
+    let agent = AgentClient::new(local.agent_socket_path());
+    if agent.is_running() {
+        bail!("Agent already running");
+    }
+    Command::new(std::env::current_exe()?)
+        .args(&[
+            "crev",
+            "agent",
+            "start",
+            "--foreground",
+            "--timeout-minutes",
+        ])
+        .arg(args.timeout_minutes.to_string())
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    for _ in 0..50 {
+        if agent.is_running() {
+            eprintln!(
+                "Agent started, listening on {}",
+                local.agent_socket_path().display()
+            );
+            return Ok(());
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    bail!("Agent didn't start; try `cargo crev agent start --foreground` to see why")
+}
+
+#[cfg(unix)]
+pub fn lock() -> Result<()> {
+    let agent = crev_lib::agent::AgentClient::new(Local::auto_open()?.agent_socket_path());
+    if !agent.is_running() {
+        bail!("Agent not running");
+    }
+    agent.lock()?;
+    eprintln!("All Ids locked");
+    Ok(())
+}
+
+#[cfg(unix)]
+pub fn status() -> Result<()> {
+    let agent = crev_lib::agent::AgentClient::new(Local::auto_open()?.agent_socket_path());
+    if !agent.is_running() {
+        println!("Agent not running");
+        return Ok(());
+    }
+    let ids = agent.status()?;
+    if ids.is_empty() {
+        println!("Agent running, no Ids unlocked");
+    }
+    for (id, left) in ids {
+        println!(
+            "{} unlocked, locks in {}m{:02}s",
+            id,
+            left.as_secs() / 60,
+            left.as_secs() % 60
+        );
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn start(_args: &AgentStart) -> Result<()> {
+    bail!("The agent is only supported on Unix")
+}
+
+#[cfg(not(unix))]
+pub fn lock() -> Result<()> {
+    bail!("The agent is only supported on Unix")
+}
+
+#[cfg(not(unix))]
+pub fn status() -> Result<()> {
+    bail!("The agent is only supported on Unix")
+}
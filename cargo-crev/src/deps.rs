@@ -3,13 +3,22 @@ use crev_lib::*;
 use semver::Version;
 use std::path::PathBuf;
 
-use crate::{opts::*, prelude::*, shared::*, term};
+use crate::{
+    exemptions::{self, Exemption, ExemptionStatus, Exemptions},
+    opts::*,
+    policy::PolicyRule,
+    prelude::*,
+    shared::*,
+    term,
+};
 use cargo::core::PackageId;
+use serde::Serialize;
 use std::{
     collections::{HashMap, HashSet},
     ops::Add,
 };
 
+mod print_json;
 mod print_term;
 pub mod scan;
 
@@ -30,7 +39,7 @@ impl Progress {
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, Serialize)]
 /// A count of something, plus the "total" number of that thing.
 ///
 /// This is kind of context-dependent
@@ -53,7 +62,7 @@ where
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, Serialize)]
 pub struct DownloadsStats {
     pub version: u64,
     pub total: u64,
@@ -133,6 +142,8 @@ pub struct AccumulativeCrateDetails {
     pub trusted_issues: CountWithTotal,
     pub verified: bool,
     pub loc: Option<u64>,
+    /// Lines of code covered by trusted package or code reviews
+    pub reviewed_loc: Option<u64>,
     pub geiger_count: Option<u64>,
     pub has_custom_build: bool,
     pub is_unmaintained: bool,
@@ -140,6 +151,17 @@ pub struct AccumulativeCrateDetails {
     pub is_local_source_code: bool,
 }
 
+impl AccumulativeCrateDetails {
+    /// Percentage of lines of code covered by trusted reviews
+    pub fn reviewed_coverage(&self) -> Option<u64> {
+        match (self.reviewed_loc, self.loc) {
+            (Some(_), Some(0)) => Some(100),
+            (Some(reviewed_loc), Some(loc)) => Some(reviewed_loc * 100 / loc),
+            _ => None,
+        }
+    }
+}
+
 fn sum_options<T>(a: Option<T>, b: Option<T>) -> Option<T::Output>
 where
     T: Add<T>,
@@ -160,6 +182,7 @@ impl std::ops::Add<AccumulativeCrateDetails> for AccumulativeCrateDetails {
             trusted_issues: self.trusted_issues + other.trusted_issues,
             verified: self.verified && other.verified,
             loc: sum_options(self.loc, other.loc),
+            reviewed_loc: sum_options(self.reviewed_loc, other.reviewed_loc),
             geiger_count: sum_options(self.geiger_count, other.geiger_count),
             has_custom_build: self.has_custom_build || other.has_custom_build,
             is_unmaintained: self.is_unmaintained || other.is_unmaintained,
@@ -182,6 +205,12 @@ pub struct CrateDetails {
     pub dependencies: Vec<proof::PackageVersionId>,
     pub rev_dependencies: Vec<proof::PackageVersionId>,
     pub unclean_digest: bool,
+    // reviews of the same git commit, but with a different digest
+    pub revision_mismatches: u64,
+    // policy rules that determined the requirements, the most specific last
+    pub policy_rules: Vec<PolicyRule>,
+    // exemption matching the crate, if it was not verified
+    pub exemption: Option<ExemptionStatus>,
     // own accumulative stats only
     pub accumulative_own: AccumulativeCrateDetails,
     // total recursive stats
@@ -213,7 +242,7 @@ impl CrateInfo {
     pub fn download_if_needed(&self, cargo_opts: CargoOpts) -> Result<()> {
         if !self.root.exists() {
             let repo = crate::Repo::auto_open_cwd(cargo_opts)?;
-            let mut source = repo.load_source()?;
+            let mut source = repo.load_source_for(self.id.source_id(), HashSet::new())?;
             source.download(self.id)?;
         }
         Ok(())
@@ -316,14 +345,95 @@ pub fn crate_mvps(crate_: CrateSelector, common: CrateVerifyCommon) -> Result<()
     Ok(())
 }
 
+/// Regenerate the exemptions list from crates currently failing verification
+///
+/// Exemptions still in force are kept, and no longer needed ones are removed.
+/// Expired exemptions are kept as they are, and reported: they are never renewed,
+/// so the crates keep failing until reviewed, or exempted again by hand.
+pub fn update_exemptions(crate_: CrateSelector, args: CrateExempt) -> Result<()> {
+    let mut verify_args = CrateVerify::default();
+    verify_args.common = args.common;
+
+    let scanner = scan::Scanner::new(crate_, &verify_args)?;
+    let workspace_root = scanner
+        .workspace_root
+        .as_ref()
+        .ok_or_else(|| format_err!("Exemptions can only be updated in a workspace"))?;
+    let path = Exemptions::path_for_workspace(workspace_root);
+    let events = scanner.run();
+
+    let today = exemptions::today();
+    let expires = today + chrono::Duration::days(args.days);
+
+    let mut deps: Vec<_> = events.into_iter().collect();
+    deps.sort_by(|a, b| a.info.cmp(&b.info));
+
+    let mut new_exemptions = Exemptions::default();
+    let mut kept = 0;
+    let mut expired = 0;
+    for dep in &deps {
+        let details = dep.details();
+        match (details.accumulative_own.trust, &details.exemption) {
+            (VerificationStatus::Exempted, Some(ExemptionStatus::Active(exemption))) => {
+                if !new_exemptions.exemptions.contains(exemption) {
+                    new_exemptions.exemptions.push(exemption.clone());
+                    kept += 1;
+                }
+            }
+            (VerificationStatus::Insufficient, Some(ExemptionStatus::Expired(exemption))) => {
+                if !new_exemptions.exemptions.contains(exemption) {
+                    eprintln!(
+                        "Exemption of {} ({}) by {} expired on {}, not renewed: {}",
+                        exemption.name,
+                        exemption.version,
+                        exemption.owner,
+                        exemption.expires,
+                        exemption.reason
+                    );
+                    new_exemptions.exemptions.push(exemption.clone());
+                    expired += 1;
+                }
+            }
+            (VerificationStatus::Insufficient, _) => new_exemptions.exemptions.push(Exemption {
+                name: dep.info.id.name().to_string(),
+                version: semver::VersionReq::exact(&dep.info.id.version()),
+                reason: args.reason.clone(),
+                owner: args.owner.clone(),
+                expires,
+            }),
+            (VerificationStatus::Negative, _) => eprintln!(
+                "{} {} has negative reviews and can't be exempted",
+                dep.info.id.name(),
+                dep.info.id.version()
+            ),
+            _ => {}
+        }
+    }
+
+    new_exemptions.store(&path)?;
+    eprintln!(
+        "{} exemptions ({} kept, {} expired, {} new) written to {}",
+        new_exemptions.exemptions.len(),
+        kept,
+        expired,
+        new_exemptions.exemptions.len() - kept - expired,
+        path.display()
+    );
+
+    Ok(())
+}
+
 pub fn verify_deps(crate_: CrateSelector, args: CrateVerify) -> Result<CommandExitStatus> {
     let mut term = term::Term::new();
 
     let scanner = scan::Scanner::new(crate_, &args)?;
+    let policy_path = scanner.policy_path().map(ToOwned::to_owned);
     let events = scanner.run();
 
+    let human_output = args.format == VerifyOutputFormat::Human;
+
     // print header, only after `scanner` had a chance to download everything
-    if term.stderr_is_tty && term.stdout_is_tty {
+    if human_output && term.stderr_is_tty && term.stdout_is_tty {
         print_term::print_header(&mut term, &args.columns);
     }
 
@@ -341,11 +451,21 @@ pub fn verify_deps(crate_: CrateSelector, args: CrateVerify) -> Result<CommandEx
         })
         .filter(|stats| !args.skip_verified || !stats.details.accumulative.verified)
         .map(|stats| {
-            print_term::print_dep(&stats, &mut term, &args.columns, args.recursive)?;
+            match args.format {
+                VerifyOutputFormat::Human => {
+                    print_term::print_dep(&stats, &mut term, &args.columns, args.recursive)?
+                }
+                VerifyOutputFormat::JsonLines => print_json::print_dep_json_line(&stats)?,
+                VerifyOutputFormat::Json => {}
+            }
             Ok(stats)
         })
         .collect::<Result<_>>()?;
 
+    if args.format == VerifyOutputFormat::Json {
+        print_json::print_deps_json(&deps)?;
+    }
+
     let mut nb_unclean_digests = 0;
     let mut nb_unverified = 0;
     for dep in &deps {
@@ -382,7 +502,60 @@ pub fn verify_deps(crate_: CrateSelector, args: CrateVerify) -> Result<CommandEx
         }
     }
 
-    if term.stderr_is_tty && term.stdout_is_tty {
+    for dep in &deps {
+        let revision_mismatches = dep.details().revision_mismatches;
+        if revision_mismatches > 0 {
+            term.eprint(
+                format_args!(
+                    "Crate {} {} at commit {} has a different digest than {} review{} of the same commit; its checkout in {} might have been modified\n",
+                    dep.info.id.name(),
+                    dep.info.id.version(),
+                    dep.info.id.source_id().precise().unwrap_or("?"),
+                    revision_mismatches,
+                    if revision_mismatches > 1 { "s" } else { "" },
+                    dep.info.root.display(),
+                ),
+                ::term::color::RED,
+            )?;
+        }
+    }
+
+    for dep in &deps {
+        if let Some(ExemptionStatus::Expired(exemption)) = &dep.details().exemption {
+            term.eprint(
+                format_args!(
+                    "Exemption of {} {} ({}) by {} expired on {}: {}\n",
+                    dep.info.id.name(),
+                    dep.info.id.version(),
+                    exemption.version,
+                    exemption.owner,
+                    exemption.expires,
+                    exemption.reason
+                ),
+                ::term::color::RED,
+            )?;
+        }
+    }
+
+    if let Some(policy_path) = policy_path {
+        for dep in &deps {
+            let details = dep.details();
+            if details.accumulative_own.verified || details.accumulative_own.is_local_source_code {
+                continue;
+            }
+            if let Some(rule) = details.policy_rules.last() {
+                eprintln!(
+                    "{} {} does not pass requirements of {} in {}",
+                    dep.info.id.name(),
+                    dep.info.id.version(),
+                    rule,
+                    policy_path.display()
+                );
+            }
+        }
+    }
+
+    if human_output && term.stderr_is_tty && term.stdout_is_tty {
         if !args.columns.any_selected() {
             eprintln!("Some columns were hidden. Use one or more `--show-<column>` to print more details. Use `--help` for list of available columns and other options and help. Use `--show-all` to just display everything.");
         }
@@ -0,0 +1,163 @@
+// Functions related to printing dependencies as JSON
+// (for CI and other tools consuming the output of `crate verify`)
+
+use super::*;
+use std::io::{self, Write};
+
+/// Version of the JSON output schema
+///
+/// Bump it on every change that is not purely additive.
+pub const SCHEMA_VERSION: u64 = 1;
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct AccumulativeDetailsOutput {
+    pub status: VerificationStatus,
+    pub verified: bool,
+    pub trusted_issues: CountWithTotal,
+    pub loc: Option<u64>,
+    /// Lines of code covered by trusted package or code reviews
+    pub reviewed_loc: Option<u64>,
+    pub geiger_count: Option<u64>,
+    pub has_custom_build: bool,
+    pub is_unmaintained: bool,
+    pub total_owners: usize,
+    pub total_owner_groups: usize,
+    pub is_local_source_code: bool,
+}
+
+impl From<&AccumulativeCrateDetails> for AccumulativeDetailsOutput {
+    fn from(details: &AccumulativeCrateDetails) -> Self {
+        Self {
+            status: if details.is_local_source_code {
+                VerificationStatus::Local
+            } else {
+                details.trust
+            },
+            verified: details.verified,
+            trusted_issues: details.trusted_issues,
+            loc: details.loc,
+            reviewed_loc: details.reviewed_loc,
+            geiger_count: details.geiger_count,
+            has_custom_build: details.has_custom_build,
+            is_unmaintained: details.is_unmaintained,
+            total_owners: details.owner_set.to_total_owners(),
+            total_owner_groups: details.owner_set.to_total_distinct_groups(),
+            is_local_source_code: details.is_local_source_code,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct CrateVerifyOutput {
+    pub name: String,
+    pub version: String,
+    pub source: String,
+    pub digest: Option<String>,
+    pub unclean_digest: bool,
+    /// Reviews of the same git commit, but with a different digest
+    pub revision_mismatches: u64,
+    pub latest_trusted_version: Option<String>,
+    pub trusted_reviewers: Vec<PublicId>,
+    pub version_reviews: CountWithTotal,
+    pub downloads: Option<DownloadsStats>,
+    pub known_owners: Option<CountWithTotal>,
+    pub leftpad_idx: u64,
+    /// Policy rules the requirements came from, the most specific last
+    pub policy_rules: Vec<String>,
+    pub exemption: Option<ExemptionStatus>,
+    pub dependencies: Vec<proof::PackageVersionId>,
+    pub rev_dependencies: Vec<proof::PackageVersionId>,
+    /// Same as `accumulative-recursive` in recursive mode, `accumulative-own` otherwise
+    pub accumulative: AccumulativeDetailsOutput,
+    pub accumulative_own: AccumulativeDetailsOutput,
+    pub accumulative_recursive: AccumulativeDetailsOutput,
+}
+
+impl From<&CrateStats> for CrateVerifyOutput {
+    fn from(stats: &CrateStats) -> Self {
+        let details = stats.details();
+        let mut trusted_reviewers: Vec<_> = details.trusted_reviewers.iter().cloned().collect();
+        trusted_reviewers.sort_by(|a, b| a.id.to_string().cmp(&b.id.to_string()));
+
+        Self {
+            name: stats.info.id.name().to_string(),
+            version: stats.info.id.version().to_string(),
+            source: stats.info.id.source_id().url().to_string(),
+            digest: details.digest.as_ref().map(|d| d.to_string()),
+            unclean_digest: details.unclean_digest,
+            revision_mismatches: details.revision_mismatches,
+            latest_trusted_version: details
+                .latest_trusted_version
+                .as_ref()
+                .map(|v| v.to_string()),
+            trusted_reviewers,
+            version_reviews: details.version_reviews,
+            downloads: details.downloads,
+            known_owners: details.known_owners,
+            leftpad_idx: details.leftpad_idx,
+            policy_rules: details
+                .policy_rules
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+            exemption: details.exemption.clone(),
+            dependencies: details.dependencies.clone(),
+            rev_dependencies: details.rev_dependencies.clone(),
+            accumulative: (&details.accumulative).into(),
+            accumulative_own: (&details.accumulative_own).into(),
+            accumulative_recursive: (&details.accumulative_recursive).into(),
+        }
+    }
+}
+
+/// A single record of `json-lines` output
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct JsonLinesRecord<'a> {
+    schema_version: u64,
+    #[serde(flatten)]
+    crate_: &'a CrateVerifyOutput,
+}
+
+/// Whole `json` output document
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct JsonDocument<'a> {
+    schema_version: u64,
+    crates: &'a [CrateVerifyOutput],
+}
+
+/// Print one crate as a single line of JSON
+pub fn print_dep_json_line(stats: &CrateStats) -> Result<()> {
+    let output = CrateVerifyOutput::from(stats);
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    serde_json::to_writer(
+        &mut stdout,
+        &JsonLinesRecord {
+            schema_version: SCHEMA_VERSION,
+            crate_: &output,
+        },
+    )?;
+    writeln!(stdout)?;
+    stdout.flush()?;
+    Ok(())
+}
+
+/// Print all the crates as a single JSON document
+pub fn print_deps_json(deps: &[CrateStats]) -> Result<()> {
+    let crates: Vec<_> = deps.iter().map(CrateVerifyOutput::from).collect();
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    serde_json::to_writer_pretty(
+        &mut stdout,
+        &JsonDocument {
+            schema_version: SCHEMA_VERSION,
+            crates: &crates,
+        },
+    )?;
+    writeln!(stdout)?;
+    Ok(())
+}
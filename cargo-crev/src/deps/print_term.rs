@@ -42,6 +42,10 @@ pub fn print_header(_term: &mut Term, columns: &CrateVerifyColumns) {
         eprint!("{:>6} ", "loc");
     }
 
+    if columns.show_coverage() {
+        eprint!("{:>5} ", "cover");
+    }
+
     if columns.show_geiger() {
         eprint!("{:>6} ", "geiger");
     }
@@ -169,6 +173,20 @@ pub fn print_details(
         }
     }
 
+    if columns.show_coverage() {
+        match cdep.accumulative.reviewed_coverage() {
+            Some(coverage) => term.print(
+                format_args!("{:>4}% ", coverage),
+                if coverage < 100 {
+                    Some(::term::color::YELLOW)
+                } else {
+                    None
+                },
+            )?,
+            None => print!("{:>5} ", "err"),
+        }
+    }
+
     Ok(())
 }
 
@@ -178,10 +196,10 @@ fn print_stats_crate_id(stats: &CrateStats, _term: &mut Term) {
         stats.info.id.name(),
         pad_left_manually(
             stats.info.id.version().to_string()
-                + if stats.info.id.source_id().is_registry() {
-                    ""
-                } else {
+                + if stats.details.accumulative_own.is_local_source_code {
                     "*"
+                } else {
+                    ""
                 },
             15
         )
@@ -3,12 +3,15 @@ use crate::{
     deps::{
         AccumulativeCrateDetails, CountWithTotal, CrateDetails, CrateInfo, CrateStats, OwnerSetSet,
     },
-    opts::{CargoOpts, CrateSelector, CrateVerify},
+    exemptions::{self, ExemptionStatus, Exemptions},
+    lockfile::Lockfile,
+    opts::{self, CargoOpts, CrateSelector, CrateVerify},
+    policy::{self, LoadedPolicy, PolicyRule},
     prelude::*,
-    repo::Repo,
+    repo::{is_local_source, Repo},
     shared::{
-        cargo_full_ignore_list, cargo_min_ignore_list, get_geiger_count, is_digest_clean,
-        read_known_owners_list, PROJECT_SOURCE_CRATES_IO,
+        cargo_full_ignore_list, cargo_min_ignore_list, get_files_with_unsafe, get_geiger_count,
+        is_digest_clean, read_known_owners_list,
     },
 };
 use cargo::core::PackageId;
@@ -23,7 +26,7 @@ use crossbeam::{
 use std::{
     collections::{HashMap, HashSet},
     default::Default,
-    path::PathBuf,
+    path::{Path, PathBuf},
     sync::{atomic, Arc, Mutex},
 };
 
@@ -40,8 +43,17 @@ pub struct Scanner {
     full_ignore_list: fnv::FnvHashSet<PathBuf>,
     crates_io: Arc<crates_io::Client>,
     known_owners: HashSet<String>,
-    requirements: crev_lib::VerificationRequirements,
+    requirements: opts::VerificationRequirements,
+    // requirements given explicitly on the command line
+    cli_requirements: opts::VerificationRequirements,
+    policy: Option<Arc<LoadedPolicy>>,
+    // packages reachable from the roots only through dev-dependencies
+    dev_only_crates_ids: HashSet<PackageId>,
+    exemptions: Arc<Exemptions>,
+    today: chrono::NaiveDate,
     recursive: bool,
+    // count crates fully covered by code reviews of their files as verified
+    file_coverage: bool,
     crate_info_by_id: HashMap<PackageId, CrateInfo>,
     // all the packages that we might need to potentially analyse
     all_crates_ids: Vec<PackageId>,
@@ -51,16 +63,65 @@ pub struct Scanner {
     graph: Arc<crate::repo::Graph>,
     crate_details_by_id: Arc<Mutex<HashMap<PackageId, CrateDetails>>>,
     pub roots: Vec<cargo::core::PackageId>,
+    /// Where the policy and exemptions come from, if anywhere
+    pub workspace_root: Option<PathBuf>,
 }
 
 impl Scanner {
     pub fn new(root_crate: CrateSelector, args: &CrateVerify) -> Result<Scanner> {
         let local = crev_lib::Local::auto_create_or_open()?;
         let db = local.load_db()?;
+        let (repo, workspace_root) = match &args.lockfile {
+            Some(lockfile_path) => {
+                let repo = Repo::for_lockfile(lockfile_path, args.common.cargo_opts.clone())?;
+                // the project being audited doesn't get to pick its own policy and exemptions,
+                // unless asked to; they come from the current workspace, if any
+                let workspace_root = if args.lockfile_policy {
+                    Some(
+                        lockfile_path
+                            .parent()
+                            .unwrap_or_else(|| Path::new("."))
+                            .to_owned(),
+                    )
+                } else {
+                    Repo::auto_open_cwd(args.common.cargo_opts.clone())
+                        .and_then(|repo| repo.workspace_root())
+                        .ok()
+                };
+                (repo, workspace_root)
+            }
+            None => {
+                let repo = Repo::auto_open_cwd(args.common.cargo_opts.clone())?;
+                let workspace_root = repo.workspace_root()?;
+                (repo, Some(workspace_root))
+            }
+        };
+        let (policy, exemptions) = match &workspace_root {
+            Some(workspace_root) => (
+                policy::load(workspace_root)?,
+                Exemptions::load(&Exemptions::path_for_workspace(workspace_root))?,
+            ),
+            None => (None, Exemptions::default()),
+        };
+        let trust_params = match &policy {
+            Some(policy) => args
+                .common
+                .trust_params
+                .clone()
+                .or_policy(&policy.policy.trust_distance),
+            None => args.common.trust_params.clone(),
+        };
+        let cli_requirements = args.common.requirements.clone();
+        let requirements = match &policy {
+            Some(policy) => cli_requirements
+                .clone()
+                .or_policy(&policy.policy.requirements),
+            None => cli_requirements.clone(),
+        };
         let trust_set = if let Some(for_id) =
             local.get_for_id_from_str_opt(OptionDeref::as_deref(&args.common.for_id))?
         {
-            db.calculate_trust_set(&for_id, &args.common.trust_params.clone().into())
+            db.calculate_trust_set(&for_id, &trust_params.into())
         } else {
             // when running without an id (explicit, or current), just use an empty trust set
             crev_wot::TrustSet::default()
@@ -69,48 +130,68 @@ impl Scanner {
         let full_ignore_list = cargo_full_ignore_list(false);
         let crates_io = crates_io::Client::new(&local)?;
         let known_owners = read_known_owners_list().unwrap_or_else(|_| HashSet::new());
-        let requirements =
-            crev_lib::VerificationRequirements::from(args.common.requirements.clone());
-        let repo = Repo::auto_open_cwd(args.common.cargo_opts.clone())?;
 
         if root_crate.unrelated {
             // we would have to create a ephemeral workspace, etc.
             bail!("Unrealated crates are currently not supported");
         }
 
-        let roots = repo.find_roots_by_crate_selector(&root_crate)?;
-        let roots_set: HashSet<_> = roots.iter().cloned().collect();
+        let (roots, graph, crate_info_by_id, dev_only_crates_ids, selected_crates_ids) =
+            if let Some(lockfile_path) = &args.lockfile {
+                if root_crate.name.is_some() {
+                    bail!("All the packages of a lockfile are verified, a crate can't be selected");
+                }
+                Self::locked_packages(&repo, lockfile_path, args.skip_indirect)?
+            } else {
+                let roots = repo.find_roots_by_crate_selector(&root_crate)?;
+                let roots_set: HashSet<_> = roots.iter().cloned().collect();
 
-        let (all_pkgs_set, _resolve) = repo.get_package_set()?;
+                let (all_pkgs_set, _resolve) = repo.get_package_set()?;
 
-        let graph = repo.get_dependency_graph(roots.clone())?;
+                let graph = repo.get_dependency_graph(roots.clone())?;
 
-        let all_pkgs_ids = graph.get_all_pkg_ids();
+                let all_pkgs_ids = graph.get_all_pkg_ids();
 
-        let crate_info_by_id: HashMap<PackageId, CrateInfo> = all_pkgs_set
-            .get_many(all_pkgs_ids)?
-            .into_iter()
-            .map(|pkg| (pkg.package_id(), CrateInfo::from_pkg(pkg)))
-            .collect();
+                let crate_info_by_id: HashMap<PackageId, CrateInfo> = all_pkgs_set
+                    .get_many(all_pkgs_ids)?
+                    .into_iter()
+                    .map(|pkg| (pkg.package_id(), CrateInfo::from_pkg(pkg)))
+                    .collect();
 
-        let all_crates_ids = crate_info_by_id.keys().cloned().collect();
+                let non_dev_crates_ids = graph.get_non_dev_reachable_from(&roots);
+                let dev_only_crates_ids = crate_info_by_id
+                    .keys()
+                    .filter(|id| !non_dev_crates_ids.contains(id))
+                    .cloned()
+                    .collect();
 
-        let selected_crates_ids = crate_info_by_id
-            .iter()
-            .filter_map(|(id, _crate_info)| {
-                if !args.skip_indirect
-                    || roots_set.contains(id)
-                    || graph
-                        .get_reverse_dependencies_of(*id)
-                        .any(|r_dep| roots.contains(&r_dep))
-                {
-                    Some(id)
-                } else {
-                    None
-                }
-            })
-            .cloned()
-            .collect();
+                let selected_crates_ids = crate_info_by_id
+                    .iter()
+                    .filter_map(|(id, _crate_info)| {
+                        if !args.skip_indirect
+                            || roots_set.contains(id)
+                            || graph
+                                .get_reverse_dependencies_of(*id)
+                                .any(|r_dep| roots.contains(&r_dep))
+                        {
+                            Some(id)
+                        } else {
+                            None
+                        }
+                    })
+                    .cloned()
+                    .collect();
+
+                (
+                    roots,
+                    graph,
+                    crate_info_by_id,
+                    dev_only_crates_ids,
+                    selected_crates_ids,
+                )
+            };
+
+        let all_crates_ids = crate_info_by_id.keys().cloned().collect();
 
         Ok(Scanner {
             db: Arc::new(db),
@@ -120,7 +201,13 @@ impl Scanner {
             crates_io: Arc::new(crates_io),
             known_owners,
             requirements,
+            cli_requirements,
+            policy: policy.map(Arc::new),
+            dev_only_crates_ids,
+            exemptions: Arc::new(exemptions),
+            today: exemptions::today(),
             recursive: args.recursive,
+            file_coverage: args.file_coverage,
             crate_info_by_id,
             all_crates_ids,
             selected_crates_ids,
@@ -128,13 +215,138 @@ impl Scanner {
             graph: Arc::new(graph),
             crate_details_by_id: Default::default(),
             roots,
+            workspace_root,
         })
     }
 
+    /// Packages locked in a standalone `Cargo.lock`, downloaded if needed
+    ///
+    /// Local packages (e.g. workspace members) can't be verified, and are skipped.
+    #[allow(clippy::type_complexity)]
+    fn locked_packages(
+        repo: &Repo,
+        lockfile_path: &Path,
+        skip_indirect: bool,
+    ) -> Result<(
+        Vec<PackageId>,
+        crate::repo::Graph,
+        HashMap<PackageId, CrateInfo>,
+        HashSet<PackageId>,
+        HashSet<PackageId>,
+    )> {
+        let lockfile = Lockfile::read(lockfile_path)?;
+        let pkg_ids: Vec<_> = lockfile.packages.iter().map(|locked| locked.id).collect();
+        let pkgs = repo.get_packages(&pkg_ids)?;
+
+        // the downloads are checked against the registry index, which must agree with the lockfile
+        let locked_checksums: HashMap<_, _> = lockfile
+            .packages
+            .iter()
+            .filter_map(|locked| Some((locked.id, locked.checksum.as_ref()?)))
+            .collect();
+        for pkg in &pkgs {
+            if let (Some(locked_checksum), Some(checksum)) = (
+                locked_checksums.get(&pkg.package_id()),
+                pkg.summary().checksum(),
+            ) {
+                if locked_checksum.as_str() != checksum {
+                    bail!(
+                        "Checksum of {} in {} does not match the registry: {} != {}",
+                        pkg.package_id(),
+                        lockfile_path.display(),
+                        locked_checksum,
+                        checksum
+                    );
+                }
+            }
+        }
+
+        let graph = crate::repo::Graph::from_dependencies(&pkgs, &lockfile.dependencies);
+        let crate_info_by_id: HashMap<PackageId, CrateInfo> = pkgs
+            .iter()
+            .map(|pkg| (pkg.package_id(), CrateInfo::from_pkg(pkg)))
+            .collect();
+        let selected_crates_ids = if skip_indirect {
+            lockfile.direct_dependencies
+        } else {
+            crate_info_by_id.keys().cloned().collect()
+        };
+
+        // the roots are the local packages, and a lockfile doesn't tell which dependencies are dev ones
+        Ok((
+            vec![],
+            graph,
+            crate_info_by_id,
+            HashSet::new(),
+            selected_crates_ids,
+        ))
+    }
+
     pub fn selected_crate_count(&self) -> usize {
         self.selected_crates_ids.len()
     }
 
+    /// Path of the policy file in use, if any
+    pub fn policy_path(&self) -> Option<&std::path::Path> {
+        self.policy.as_ref().map(|policy| policy.path.as_path())
+    }
+
+    /// Requirements to use for a given crate, and the policy rules they come from
+    fn requirements_for(
+        &self,
+        info: &CrateInfo,
+    ) -> (opts::VerificationRequirements, Vec<PolicyRule>) {
+        match &self.policy {
+            Some(policy) => policy.requirements_for(
+                &self.cli_requirements,
+                policy::CrateKind {
+                    name: info.id.name().as_str(),
+                    has_custom_build: info.has_custom_build,
+                    is_dev_dependency_only: self.dev_only_crates_ids.contains(&info.id),
+                },
+            ),
+            None => (self.requirements.clone(), vec![]),
+        }
+    }
+
+    /// Verification of a crate without enough package reviews, by the code reviews of its files
+    ///
+    /// Only with `--file-coverage`, or `--min-coverage`.
+    fn verify_files_coverage(
+        &self,
+        info: &CrateInfo,
+        files_coverage: &crev_lib::FilesCoverage,
+        loc_by_file: Option<&HashMap<PathBuf, u64>>,
+        requirements: &opts::VerificationRequirements,
+    ) -> Result<VerificationStatus> {
+        if !self.file_coverage && !requirements.requires_coverage() {
+            return Ok(VerificationStatus::Insufficient);
+        }
+        match (files_coverage.status(), loc_by_file) {
+            (VerificationStatus::Insufficient, Some(loc_by_file))
+                if requirements.requires_coverage() =>
+            {
+                let unsafe_files = if requirements.min_unsafe_coverage.is_some() {
+                    get_files_with_unsafe(&info.root)?
+                } else {
+                    HashSet::new()
+                };
+                let is_covered = files_coverage.meets_coverage(
+                    loc_by_file,
+                    &unsafe_files,
+                    requirements.min_coverage,
+                    requirements.min_unsafe_coverage,
+                );
+                Ok(if is_covered {
+                    VerificationStatus::Verified
+                } else {
+                    VerificationStatus::Insufficient
+                })
+            }
+            (status, _) => Ok(status),
+        }
+    }
+
     /// start computations on a new thread
     pub fn run(self) -> Receiver<CrateStats> {
         let (ready_tx, ready_rx) = unbounded();
@@ -225,15 +437,26 @@ impl Scanner {
 
     fn get_crate_details(&mut self, info: &CrateInfo) -> Result<CrateDetails> {
         let pkg_name = info.id.name();
+        let source = crate::cargo_registry_to_crev_source_id(&info.id.source_id());
         let proof_pkg_id = proof::PackageId {
-            source: "https://crates.io".into(),
+            source: source.clone(),
             name: pkg_name.to_string(),
         };
+        // downloads and owners are only known for crates from crates.io
+        let is_from_crates_io = info.id.source_id().is_default_registry();
+        // git dependencies are reviewed by commit, as many commits share a version
+        let git_revision = if info.id.source_id().is_git() {
+            info.id.source_id().precise()
+        } else {
+            None
+        };
 
         let pkg_version = info.id.version();
+        let (effective_requirements, policy_rules) = self.requirements_for(info);
+        let requirements = crev_lib::VerificationRequirements::from(effective_requirements.clone());
         info.download_if_needed(self.cargo_opts.clone())?;
         let geiger_count = get_geiger_count(&info.root).ok();
-        let is_local_source_code = !info.id.source_id().is_registry();
+        let is_local_source_code = is_local_source(info.id.source_id());
         let ignore_list = if is_local_source_code {
             &self.min_ignore_list
         } else {
@@ -244,43 +467,113 @@ impl Scanner {
         } else {
             None
         };
-        let unclean_digest = digest
-            .as_ref()
-            .map(|digest| !is_digest_clean(&self.db, &pkg_name, &pkg_version, &digest))
-            .unwrap_or(false);
+        let unclean_digest = git_revision.is_none()
+            && digest
+                .as_ref()
+                .map(|digest| !is_digest_clean(&self.db, &source, &pkg_name, &pkg_version, &digest))
+                .unwrap_or(false);
+        let revision_mismatches = match (git_revision, digest.as_ref()) {
+            (Some(revision), Some(digest)) => crev_lib::find_revision_digest_mismatches(
+                &source,
+                &pkg_name,
+                revision,
+                digest,
+                &self.trust_set,
+                &self.db,
+            )
+            .len() as u64,
+            _ => 0,
+        };
         let verification_result = if let Some(digest) = digest.as_ref() {
-            crev_lib::verify_package_digest(&digest, &self.trust_set, &self.requirements, &self.db)
+            crev_lib::verify_package_digest(&digest, &self.trust_set, &requirements, &self.db)
         } else {
             VerificationStatus::Local
         };
-        let verified = verification_result.is_verified();
+        let loc_by_file = crate::tokei::get_rust_line_counts_by_file(&info.root).ok();
+        let loc = loc_by_file
+            .as_ref()
+            .map(|loc_by_file| loc_by_file.values().sum::<u64>());
+        // code reviews of individual files, for crates not verified as a whole
+        let files_coverage = if digest.is_some() && !verification_result.is_verified() {
+            Some(crev_lib::get_dir_files_coverage(
+                &source,
+                &pkg_name,
+                &info.root,
+                ignore_list,
+                &self.trust_set,
+                &requirements,
+                &self.db,
+            )?)
+        } else {
+            None
+        };
+        let verification_result = match &files_coverage {
+            Some(files_coverage) if verification_result == VerificationStatus::Insufficient => self
+                .verify_files_coverage(
+                    info,
+                    files_coverage,
+                    loc_by_file.as_ref(),
+                    &effective_requirements,
+                )?,
+            _ => verification_result,
+        };
+        let reviewed_loc = if verification_result.is_verified() {
+            loc
+        } else {
+            match (&files_coverage, &loc_by_file) {
+                (Some(files_coverage), Some(loc_by_file)) => {
+                    Some(files_coverage.covered_loc(loc_by_file, |_| true).0)
+                }
+                _ => loc.map(|_| 0),
+            }
+        };
+        let exemption = if verification_result == VerificationStatus::Insufficient {
+            self.exemptions.find(&pkg_name, &pkg_version, self.today)
+        } else {
+            None
+        };
+        let verification_result = match exemption {
+            Some(ExemptionStatus::Active(_)) => VerificationStatus::Exempted,
+            _ => verification_result,
+        };
+        let verified = verification_result.is_verified()
+            || verification_result == VerificationStatus::Exempted;
 
         let pkg_name = info.id.name().to_string();
 
-        let version_reviews: Vec<_> = self
-            .db
-            .get_package_reviews_for_package(
-                PROJECT_SOURCE_CRATES_IO,
-                Some(&pkg_name),
-                Some(&info.id.version()),
-            )
-            .collect();
+        let version_reviews: Vec<_> = match git_revision {
+            Some(revision) => self
+                .db
+                .get_pkg_reviews_for_revision(&source, &pkg_name, revision)
+                .collect(),
+            None => self
+                .db
+                .get_package_reviews_for_package(&source, Some(&pkg_name), Some(&info.id.version()))
+                .collect(),
+        };
 
         let version_reviews_count = version_reviews.len();
-        let total_reviews_count =
-            self.db
-                .get_package_review_count(PROJECT_SOURCE_CRATES_IO, Some(&pkg_name), None);
+        let total_reviews_count = self
+            .db
+            .get_package_review_count(&source, Some(&pkg_name), None);
         let version_review_count = CountWithTotal {
             count: version_reviews_count as u64,
             total: total_reviews_count as u64,
         };
 
-        let downloads = self
-            .crates_io
-            .get_downloads_count(&pkg_name, &pkg_version)
-            .ok();
+        let downloads = if is_from_crates_io {
+            self.crates_io
+                .get_downloads_count(&pkg_name, &pkg_version)
+                .ok()
+        } else {
+            None
+        };
 
-        let owner_list = self.crates_io.get_owners(&pkg_name).ok();
+        let owner_list = if is_from_crates_io {
+            self.crates_io.get_owners(&pkg_name).ok()
+        } else {
+            None
+        };
         let known_owners = match &owner_list {
             Some(owner_list) => {
                 let total_owners_count = owner_list.len();
@@ -297,15 +590,15 @@ impl Scanner {
         };
 
         let issues_from_trusted = self.db.get_open_issues_for_version(
-            PROJECT_SOURCE_CRATES_IO,
+            &source,
             &pkg_name,
             &pkg_version,
             &self.trust_set,
-            self.requirements.trust_level.into(),
+            requirements.trust_level.into(),
         );
 
         let issues_from_all = self.db.get_open_issues_for_version(
-            PROJECT_SOURCE_CRATES_IO,
+            &source,
             &pkg_name,
             &pkg_version,
             &self.trust_set,
@@ -317,13 +610,11 @@ impl Scanner {
             total: issues_from_all.len() as u64,
         };
 
-        let loc = crate::tokei::get_rust_line_count(&info.root).ok();
-
         let latest_trusted_version = crev_lib::find_latest_trusted_version(
             &self.trust_set,
-            PROJECT_SOURCE_CRATES_IO,
+            &source,
             &pkg_name,
-            &self.requirements,
+            &requirements,
             &self.db,
         );
 
@@ -338,7 +629,8 @@ impl Scanner {
             trust: verification_result,
             trusted_issues: issues,
             geiger_count,
-            loc: loc.map(|l| l as u64),
+            loc,
+            reviewed_loc,
             verified,
             has_custom_build: info.has_custom_build,
             is_unmaintained,
@@ -372,7 +664,7 @@ impl Scanner {
                 .map(|pkg_review| pkg_review.from().to_owned())
                 .filter(|id| {
                     self.trust_set.get_effective_trust_level(&id.id)
-                        >= self.requirements.trust_level.into()
+                        >= requirements.trust_level.into()
                 })
                 .collect(),
             latest_trusted_version,
@@ -380,6 +672,9 @@ impl Scanner {
             downloads,
             known_owners,
             unclean_digest,
+            revision_mismatches,
+            policy_rules,
+            exemption,
             leftpad_idx: downloads
                 .and_then(|d| d.recent.checked_div(accumulative_own.loc.unwrap_or(0)))
                 .unwrap_or(0),
@@ -1,7 +1,7 @@
 use anyhow::{bail, Result};
 use crev_data::{
     proof::{self, CommonOps, ContentExt},
-    PublicId, UnlockedId,
+    PublicId, Signer,
 };
 
 pub fn parse_dyn_content(proof: &proof::Proof) -> Result<Box<dyn DynContent>> {
@@ -16,7 +16,7 @@ pub fn parse_dyn_content(proof: &proof::Proof) -> Result<Box<dyn DynContent>> {
 pub trait DynContent {
     fn set_date(&mut self, date: &proof::Date);
     fn set_author(&mut self, id: &PublicId);
-    fn sign_by(&self, id: &UnlockedId) -> Result<proof::Proof>;
+    fn sign_by(&self, signer: &dyn Signer) -> Result<proof::Proof>;
 }
 
 impl DynContent for proof::review::Code {
@@ -26,8 +26,8 @@ impl DynContent for proof::review::Code {
     fn set_author(&mut self, id: &PublicId) {
         self.common.from = id.clone();
     }
-    fn sign_by(&self, id: &UnlockedId) -> Result<proof::Proof> {
-        Ok(ContentExt::sign_by(self, id)?)
+    fn sign_by(&self, signer: &dyn Signer) -> Result<proof::Proof> {
+        Ok(ContentExt::sign_by(self, signer)?)
     }
 }
 impl DynContent for proof::review::Package {
@@ -37,8 +37,8 @@ impl DynContent for proof::review::Package {
     fn set_author(&mut self, id: &PublicId) {
         self.common.from = id.clone();
     }
-    fn sign_by(&self, id: &UnlockedId) -> Result<proof::Proof> {
-        Ok(ContentExt::sign_by(self, id)?)
+    fn sign_by(&self, signer: &dyn Signer) -> Result<proof::Proof> {
+        Ok(ContentExt::sign_by(self, signer)?)
     }
 }
 impl DynContent for proof::trust::Trust {
@@ -48,7 +48,7 @@ impl DynContent for proof::trust::Trust {
     fn set_author(&mut self, id: &PublicId) {
         self.common.from = id.clone();
     }
-    fn sign_by(&self, id: &UnlockedId) -> Result<proof::Proof> {
-        Ok(ContentExt::sign_by(self, id)?)
+    fn sign_by(&self, signer: &dyn Signer) -> Result<proof::Proof> {
+        Ok(ContentExt::sign_by(self, signer)?)
     }
 }
@@ -0,0 +1,102 @@
+//! Project-level exemptions from verification
+//!
+//! Lets a project adopt crev incrementally: crates that lack reviews
+//! can be listed in `crev-exemptions.toml` in the workspace root,
+//! each with a reason, owner and an expiry date.
+//!
+//! ```toml
+//! [[exemption]]
+//! name = "foo"
+//! version = "^1.2"
+//! reason = "Waiting for a review"
+//! owner = "jane@example.com"
+//! expires = "2020-12-31"
+//! ```
+//!
+//! Exemptions turn crates lacking reviews into `exmt` (exempted) crates.
+//! Crates with negative reviews are never exempted. Expired exemptions
+//! are reported, and the crates fail the verification.
+use crate::prelude::*;
+use chrono::NaiveDate;
+use semver::VersionReq;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+pub const EXEMPTIONS_FILE_NAME: &str = "crev-exemptions.toml";
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Exemption {
+    pub name: String,
+    pub version: VersionReq,
+    pub reason: String,
+    pub owner: String,
+    pub expires: NaiveDate,
+}
+
+impl Exemption {
+    pub fn matches(&self, name: &str, version: &Version) -> bool {
+        self.name == name && self.version.matches(version)
+    }
+
+    pub fn is_expired(&self, today: NaiveDate) -> bool {
+        self.expires < today
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Exemptions {
+    #[serde(default, rename = "exemption")]
+    pub exemptions: Vec<Exemption>,
+}
+
+/// Result of looking up exemptions of a crate
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "kebab-case", tag = "status")]
+pub enum ExemptionStatus {
+    /// Crate is exempted from verification
+    Active(Exemption),
+    /// Crate was exempted, but the exemption has expired
+    Expired(Exemption),
+}
+
+impl Exemptions {
+    pub fn path_for_workspace(workspace_root: &Path) -> PathBuf {
+        workspace_root.join(EXEMPTIONS_FILE_NAME)
+    }
+
+    /// Load exemptions of a workspace (empty if there's no exemptions file)
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(path)?;
+        Ok(toml::from_str(&content).with_context(|| format!("Parsing {}", path.display()))?)
+    }
+
+    pub fn store(&self, path: &Path) -> Result<()> {
+        let content = toml::to_string(self)?;
+        crev_common::store_str_to_file(path, &content)?;
+        Ok(())
+    }
+
+    /// Look up exemption for a given crate
+    ///
+    /// Active exemptions take precedence over expired ones.
+    pub fn find(&self, name: &str, version: &Version, today: NaiveDate) -> Option<ExemptionStatus> {
+        let mut expired = None;
+        for exemption in self.exemptions.iter().filter(|e| e.matches(name, version)) {
+            if exemption.is_expired(today) {
+                expired = Some(exemption);
+            } else {
+                return Some(ExemptionStatus::Active(exemption.clone()));
+            }
+        }
+        expired.map(|e| ExemptionStatus::Expired(e.clone()))
+    }
+}
+
+pub fn today() -> NaiveDate {
+    chrono::Local::today().naive_local()
+}
@@ -0,0 +1,272 @@
+//! `crate explain` and `id explain` - why a crate passes or fails
+//! the verification, and how an Id is trusted
+use crate::{
+    exemptions::{self, ExemptionStatus, Exemptions},
+    opts::{self, CrateSelector, CrateVerifyCommon, IdExplain},
+    policy::{self, PolicyRule},
+    prelude::*,
+    repo::{is_local_source, Repo},
+    shared::{cargo_full_ignore_list, cargo_min_ignore_list, id_from_string},
+};
+use crev_common::convert::OptionDeref;
+use crev_data::proof::CommonOps;
+use crev_data::Id;
+use crev_lib::{ReviewVerdict, VerificationStatus};
+use crev_wot::{ProofDB, TrustPathHop};
+
+pub fn explain_crate(root_crate: CrateSelector, args: CrateVerifyCommon) -> Result<()> {
+    root_crate.ensure_name_given()?;
+
+    let local = crev_lib::Local::auto_create_or_open()?;
+    let db = local.load_db()?;
+    let repo = Repo::auto_open_cwd(args.cargo_opts.clone())?;
+    let workspace_root = repo.workspace_root()?;
+    let policy = policy::load(&workspace_root)?;
+
+    let trust_params = match &policy {
+        Some(policy) => args
+            .trust_params
+            .clone()
+            .or_policy(&policy.policy.trust_distance),
+        None => args.trust_params.clone(),
+    };
+    let trust_set =
+        if let Some(for_id) = local.get_for_id_from_str_opt(OptionDeref::as_deref(&args.for_id))? {
+            db.calculate_trust_set(&for_id, &trust_params.into())
+        } else {
+            crev_wot::TrustSet::default()
+        };
+
+    let pkg_id = repo.find_pkgid_by_crate_selector(&root_crate)?;
+    let crate_ = repo.get_crate(&pkg_id)?;
+    println!("{} {}", pkg_id.name(), pkg_id.version());
+
+    if is_local_source(pkg_id.source_id()) {
+        let digest = crev_lib::get_dir_digest(crate_.root(), &cargo_min_ignore_list())?;
+        println!("digest: {}", digest);
+        println!("status: {}", VerificationStatus::Local);
+        println!("Local source code is not verified.");
+        return Ok(());
+    }
+
+    let digest = crev_lib::get_dir_digest(crate_.root(), &cargo_full_ignore_list(false))?;
+    println!("digest: {}", digest);
+
+    let (requirements, policy_rules): (opts::VerificationRequirements, Vec<PolicyRule>) =
+        match &policy {
+            Some(policy) => policy.requirements_for(
+                &args.requirements,
+                policy::CrateKind {
+                    name: pkg_id.name().as_str(),
+                    has_custom_build: crate_.has_custom_build(),
+                    // can't tell without scanning the whole dependency graph
+                    is_dev_dependency_only: false,
+                },
+            ),
+            None => (args.requirements.clone(), vec![]),
+        };
+    let requirements = crev_lib::VerificationRequirements::from(requirements);
+
+    let trace =
+        crev_lib::explain_package_digest_verification(&digest, &trust_set, &requirements, &db);
+
+    let mut status = trace.status;
+    let mut exemption = None;
+    if status == VerificationStatus::Insufficient {
+        let exemptions = Exemptions::load(&Exemptions::path_for_workspace(&workspace_root))?;
+        exemption = exemptions.find(&pkg_id.name(), pkg_id.version(), exemptions::today());
+        if let Some(ExemptionStatus::Active(_)) = exemption {
+            status = VerificationStatus::Exempted;
+        }
+    }
+
+    println!(
+        "status: {} ({} of {} required reviews)",
+        status, trace.counted, trace.required
+    );
+    print!(
+        "requirements: trust >= {}, thoroughness >= {}, understanding >= {}, redundancy {}",
+        requirements.trust_level,
+        requirements.thoroughness,
+        requirements.understanding,
+        requirements.redundancy
+    );
+    if let Some(max_age) = requirements.review_max_age {
+        print!(
+            ", reviews older than {} days less trusted",
+            max_age.num_days()
+        );
+    }
+    if let Some(policy) = &policy {
+        if let Some(rule) = policy_rules.last() {
+            print!(" (policy {} in {})", rule, policy.path.display());
+        }
+    }
+    println!();
+
+    match exemption {
+        Some(ExemptionStatus::Active(e)) => println!(
+            "exempted: {} by {} until {}: {}",
+            e.version, e.owner, e.expires, e.reason
+        ),
+        Some(ExemptionStatus::Expired(e)) => println!(
+            "exemption expired: {} by {} on {}: {}",
+            e.version, e.owner, e.expires, e.reason
+        ),
+        None => {}
+    }
+
+    if !trace.teams.is_empty() {
+        println!("teams:");
+        for team_trace in &trace.teams {
+            println!(
+                "  {:<21} {} trust: {:<6} distance: {:<3} members: {} of {} required{} {}",
+                team_trace.verdict.to_string(),
+                team_trace.team,
+                team_trace.effective_trust,
+                team_trace
+                    .distance
+                    .map(|d| d.to_string())
+                    .unwrap_or_else(|| "-".into()),
+                team_trace.counted_members.len(),
+                team_trace.threshold,
+                if team_trace.negative_members.is_empty() {
+                    "".into()
+                } else {
+                    format!(", {} negative", team_trace.negative_members.len())
+                },
+                id_url_display(&db, &team_trace.team),
+            );
+        }
+    }
+
+    if trace.reviews.is_empty() {
+        println!("No reviews of this exact crate content.");
+        return Ok(());
+    }
+
+    println!("reviews:");
+    for review_trace in &trace.reviews {
+        let review = &review_trace.review;
+        println!(
+            "  {:<21} {} trust: {:<6} distance: {:<3} thoroughness: {:<6} understanding: {:<6} rating: {} {}{}",
+            review_trace.verdict.to_string(),
+            review.from().id,
+            review_trace.effective_trust,
+            review_trace
+                .distance
+                .map(|d| d.to_string())
+                .unwrap_or_else(|| "-".into()),
+            review.review.thoroughness,
+            review.review.understanding,
+            review.review.rating,
+            review.from().url_display(),
+            if review_trace.stale {
+                format!(" (stale, from {})", review.date().format("%Y-%m-%d"))
+            } else {
+                "".into()
+            },
+        );
+    }
+
+    if trace
+        .reviews
+        .iter()
+        .any(|r| r.verdict == ReviewVerdict::ReviewerNotTrusted)
+    {
+        println!("Use `cargo crev id trust <id>` to trust more reviewers.");
+    }
+
+    Ok(())
+}
+
+fn id_url_display(db: &ProofDB, id: &Id) -> String {
+    db.lookup_url(id)
+        .any_unverified()
+        .map(|url| url.url.clone())
+        .unwrap_or_else(|| "(no url)".into())
+}
+
+fn print_trust_path(db: &ProofDB, path: &[TrustPathHop]) {
+    for hop in path {
+        println!(
+            "  {:<6} distance: {:<3} {} {}",
+            hop.effective_trust,
+            hop.distance,
+            hop.id,
+            id_url_display(db, &hop.id)
+        );
+    }
+}
+
+pub fn explain_id(args: IdExplain) -> Result<()> {
+    let local = crev_lib::Local::auto_create_or_open()?;
+    let db = local.load_db()?;
+    let id = id_from_string(&db, &args.public_id)?;
+    let for_id = local.get_for_id_from_str(OptionDeref::as_deref(&args.for_id))?;
+    let trust_set = db.calculate_trust_set(&for_id, &args.trust_params.into());
+
+    println!("{} {}", id, id_url_display(&db, &id));
+
+    match db.get_revoked_since(&id) {
+        Some(Some(since)) => println!("revoked: proofs created since {} are invalid", since),
+        Some(None) => println!("revoked: all proofs are invalid"),
+        None => {}
+    }
+
+    if let Some(distrusted_by) = trust_set.get_distrusted_by(&id) {
+        let mut distrusted_by: Vec<_> = distrusted_by.iter().collect();
+        distrusted_by.sort_by_key(|id| id.to_string());
+        println!("distrusted by:");
+        for distrusting_id in distrusted_by {
+            println!(
+                "  {} {}",
+                distrusting_id,
+                id_url_display(&db, distrusting_id)
+            );
+        }
+    }
+
+    let distance = match trust_set.get_distance(&id) {
+        Some(distance) => distance,
+        None => {
+            println!("Not trusted by {}.", for_id);
+            return Ok(());
+        }
+    };
+    println!(
+        "trust: {} distance: {}",
+        trust_set.get_effective_trust_level(&id),
+        distance
+    );
+
+    let shortest = trust_set.get_shortest_trust_path(&id);
+    let strongest = trust_set.get_strongest_trust_path(&id);
+    if let Some(shortest) = &shortest {
+        println!("shortest path:");
+        print_trust_path(&db, shortest);
+    }
+    if let Some(strongest) = &strongest {
+        if Some(strongest) != shortest.as_ref() {
+            println!("strongest path:");
+            print_trust_path(&db, strongest);
+        }
+    }
+
+    let mut referers: Vec<_> = trust_set.get_referers(&id).collect();
+    referers.sort_by(|a, b| b.1.cmp(&a.1).then(a.2.cmp(&b.2)));
+    if !referers.is_empty() {
+        println!("trusted directly by:");
+        for (referer, effective_trust, distance) in referers {
+            println!(
+                "  {:<6} distance: {:<3} {} {}",
+                effective_trust,
+                distance,
+                referer,
+                id_url_display(&db, referer)
+            );
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,136 @@
+//! Packages locked in a standalone `Cargo.lock`
+//!
+//! Used to verify a project without a buildable workspace (e.g. an old
+//! release tag): the lockfile is only parsed, never resolved by cargo,
+//! so exactly the locked packages are verified.
+use crate::prelude::*;
+use cargo::core::{PackageId, SourceId};
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::Path,
+};
+
+#[derive(Deserialize)]
+struct LockfileToml {
+    #[serde(default)]
+    package: Vec<LockedPackageToml>,
+    // checksums, in the older format
+    #[serde(default)]
+    metadata: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize)]
+struct LockedPackageToml {
+    name: String,
+    version: String,
+    source: Option<String>,
+    checksum: Option<String>,
+    #[serde(default)]
+    dependencies: Vec<String>,
+}
+
+impl LockedPackageToml {
+    /// Whether a `name [version [(source)]]` dependency entry refers to this package
+    fn matches(&self, dependency: &str) -> bool {
+        let mut parts = dependency.splitn(3, ' ');
+        parts.next() == Some(self.name.as_str())
+            && parts.next().map_or(true, |version| version == self.version)
+            && parts.next().map_or(true, |source| {
+                Some(source.trim_start_matches('(').trim_end_matches(')')) == self.source.as_deref()
+            })
+    }
+}
+
+/// A package from a registry or a git repository
+pub struct LockedPackage {
+    pub id: PackageId,
+    /// Checksum of the `.crate` file (registry packages only)
+    pub checksum: Option<String>,
+}
+
+pub struct Lockfile {
+    pub packages: Vec<LockedPackage>,
+    pub dependencies: HashMap<PackageId, Vec<PackageId>>,
+    /// Packages the local ones (e.g. workspace members) depend on directly
+    pub direct_dependencies: HashSet<PackageId>,
+}
+
+impl Lockfile {
+    pub fn read(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Can't read {}", path.display()))?;
+        let lockfile: LockfileToml =
+            toml::from_str(&content).with_context(|| format!("Can't parse {}", path.display()))?;
+
+        // `None` for local packages, which have no source
+        let ids = lockfile
+            .package
+            .iter()
+            .map(|pkg| {
+                pkg.source
+                    .as_ref()
+                    .map(|source| {
+                        let source_id = SourceId::from_url(source)?;
+                        // registry packages are identified by their version alone
+                        let source_id = if source_id.is_registry() {
+                            source_id.with_precise(None)
+                        } else {
+                            source_id
+                        };
+                        PackageId::new(pkg.name.as_str(), &pkg.version, source_id)
+                    })
+                    .transpose()
+            })
+            .collect::<Result<Vec<Option<PackageId>>>>()?;
+
+        let mut packages = vec![];
+        let mut dependencies = HashMap::new();
+        let mut direct_dependencies = HashSet::new();
+        for (pkg, id) in lockfile.package.iter().zip(&ids) {
+            let mut pkg_dependencies = vec![];
+            for dependency in &pkg.dependencies {
+                let dep_id = lockfile
+                    .package
+                    .iter()
+                    .position(|dep| dep.matches(dependency))
+                    .ok_or_else(|| {
+                        format_err!(
+                            "{}: dependency `{}` of {} {} is not locked",
+                            path.display(),
+                            dependency,
+                            pkg.name,
+                            pkg.version
+                        )
+                    })?;
+                pkg_dependencies.extend(ids[dep_id]);
+            }
+
+            match id {
+                Some(id) => {
+                    let checksum = pkg.checksum.clone().or_else(|| {
+                        lockfile
+                            .metadata
+                            .get(&format!(
+                                "checksum {} {} ({})",
+                                pkg.name,
+                                pkg.version,
+                                pkg.source.as_deref().unwrap_or("")
+                            ))
+                            .filter(|checksum| *checksum != "<none>")
+                            .cloned()
+                    });
+                    packages.push(LockedPackage { id: *id, checksum });
+                    dependencies.insert(*id, pkg_dependencies);
+                }
+                None => direct_dependencies.extend(pkg_dependencies),
+            }
+        }
+
+        Ok(Lockfile {
+            packages,
+            dependencies,
+            direct_dependencies,
+        })
+    }
+}
@@ -21,12 +21,18 @@ use structopt::StructOpt;
 /// Documentation
 pub mod doc;
 
+mod agent;
 mod crates_io;
 mod deps;
 mod dyn_proof;
 mod edit;
+mod exemptions;
+mod explain;
 mod info;
+mod lockfile;
+mod notes;
 mod opts;
+mod policy;
 mod prelude;
 mod repo;
 mod review;
@@ -78,11 +84,14 @@ fn repo_update(args: opts::Update) -> Result<()> {
 pub fn proof_find(args: opts::ProofFind) -> Result<()> {
     let local = crev_lib::Local::auto_open()?;
     let db = local.load_db()?;
-    let mut iter = Box::new(db.get_pkg_reviews_for_source(PROJECT_SOURCE_CRATES_IO))
-        as Box<dyn Iterator<Item = &proof::review::Package>>;
+    let mut iter = match args.source.as_ref() {
+        Some(source) => Box::new(db.get_pkg_reviews_for_source(source))
+            as Box<dyn Iterator<Item = &proof::review::Package>>,
+        None => Box::new(db.get_pkg_reviews()),
+    };
 
     if let Some(author) = args.author.as_ref() {
-        let id = crev_data::id::Id::crevid_from_str(author)?;
+        let id = id_from_string(&db, author)?;
         iter = Box::new(iter.filter(move |r| r.common.from.id == id));
     }
 
@@ -103,6 +112,23 @@ fn crate_review(args: opts::CrateReview) -> Result<()> {
     handle_goto_mode_command(&args.common, |sel| {
         let is_advisory =
             args.advisory || args.affected.is_some() || (!args.issue && args.severity.is_some());
+        if !args.files.is_empty() {
+            if is_advisory {
+                bail!("Advisories are for whole crates, and can't be limited to `--files`");
+            }
+            return create_code_review_proof(
+                sel,
+                &args.files,
+                if args.issue {
+                    TrustProofType::Distrust
+                } else {
+                    TrustProofType::Trust
+                },
+                &args.common_proof_create,
+                args.skip_activity_check || args.issue,
+                args.cargo_opts.clone(),
+            );
+        }
         create_review_proof(
             sel,
             if args.issue {
@@ -135,12 +161,19 @@ fn crate_review(args: opts::CrateReview) -> Result<()> {
     Ok(())
 }
 
+/// The `source` of a package in crev proofs
+///
+/// `https://crates.io` for crates.io, and the URL in Cargo's format otherwise
+/// (`registry+<index URL>` for alternative registries, `git+<repository URL>`
+/// for git dependencies; the commit is recorded as the revision).
 pub fn cargo_registry_to_crev_source_id(source_id: &cargo::core::SourceId) -> String {
-    let s = source_id.into_url().to_string();
-    if &s == "registry+https://github.com/rust-lang/crates.io-index" {
+    if source_id.is_default_registry() {
         crate::PROJECT_SOURCE_CRATES_IO.into()
+    } else if source_id.is_git() {
+        // without the branch/tag and commit, which aren't part of the source
+        format!("git+{}", source_id.url())
     } else {
-        s
+        source_id.into_url().to_string()
     }
 }
 
@@ -179,6 +212,11 @@ fn print_ids<'a>(
 
 fn run_command(command: opts::Command) -> Result<CommandExitStatus> {
     match command {
+        opts::Command::Agent(args) => match args {
+            opts::Agent::Start(args) => agent::start(&args)?,
+            opts::Agent::Lock => agent::lock()?,
+            opts::Agent::Status => agent::status()?,
+        },
         opts::Command::Id(args) => match args {
             opts::Id::New(args) => {
                 let url = match (args.url, args.github_username) {
@@ -200,19 +238,41 @@ fn run_command(command: opts::Command) -> Result<CommandExitStatus> {
                     crev_common::read_new_passphrase()
                 }
                 let local = Local::auto_create_or_open()?;
-                let res = local
-                    .generate_id(&url, args.use_https_push, read_new_passphrase)
-                    .map_err(|e| {
-                        eprintln!("To create your proof repository, fork the template:");
-                        eprintln!("https://github.com/crev-dev/crev-proofs/fork");
-                        eprintln!(
-                            "For help visit: https://github.com/crev-dev/crev/wiki/Proof-Repository"
-                        );
-                        eprintln!();
-                        e
-                    })?;
-                println!("Your CrevID was created and will be printed below in an encrypted form.");
-                println!("Make sure to back it up on another device, to prevent losing it.");
+                let res = match (&args.openpgp, &args.ssh) {
+                    (Some(path), None) => local.import_openpgp_id(
+                        &url,
+                        args.use_https_push,
+                        &std::fs::read(path)?,
+                        &|| {
+                            crev_common::read_passphrase_with_prompt(
+                                "Enter passphrase of the OpenPGP key: ",
+                            )
+                        },
+                        read_new_passphrase,
+                    ),
+                    (None, Some(path)) => local.add_ssh_id(&url, args.use_https_push, path),
+                    (None, None) => {
+                        local.generate_id(&url, args.use_https_push, read_new_passphrase)
+                    }
+                    (Some(_), Some(_)) => bail!("Can't use both --openpgp and --ssh"),
+                }
+                .map_err(|e| {
+                    eprintln!("To create your proof repository, fork the template:");
+                    eprintln!("https://github.com/crev-dev/crev-proofs/fork");
+                    eprintln!(
+                        "For help visit: https://github.com/crev-dev/crev/wiki/Proof-Repository"
+                    );
+                    eprintln!();
+                    e
+                })?;
+                if args.ssh.is_some() {
+                    println!("Your CrevID was created, and uses the SSH key in its key file:");
+                } else {
+                    println!(
+                        "Your CrevID was created and will be printed below in an encrypted form."
+                    );
+                    println!("Make sure to back it up on another device, to prevent losing it.");
+                }
                 println!("{}", res);
 
                 let local = crev_lib::Local::auto_open()?;
@@ -279,6 +339,32 @@ fn run_command(command: opts::Command) -> Result<CommandExitStatus> {
                     &args.common_proof_create,
                 )?;
             }
+            opts::Id::Explain(args) => {
+                explain::explain_id(args)?;
+            }
+            opts::Id::Revoke(args) => {
+                revoke_id(&args)?;
+            }
+            opts::Id::Rotate(args) => {
+                rotate_id(&args)?;
+            }
+            opts::Id::Team(args) => {
+                create_team_proof(ids_from_string(&args.members)?, &args)?;
+            }
+            opts::Id::Graph(args) => {
+                let local = crev_lib::Local::auto_create_or_open()?;
+                let db = local.load_db()?;
+                let for_id = local.get_for_id_from_str(OptionDeref::as_deref(&args.for_id))?;
+                let trust_set = db.calculate_trust_set(&for_id, &args.trust_params.into());
+                let graph = db.get_trust_graph(&for_id, &trust_set, args.max_depth);
+
+                match args.format {
+                    opts::IdGraphFormat::Dot => print!("{}", graph.to_dot()),
+                    opts::IdGraphFormat::Json => {
+                        println!("{}", serde_json::to_string_pretty(&graph)?)
+                    }
+                }
+            }
             opts::Id::Query(cmd) => match cmd {
                 opts::IdQuery::Current { trust_params } => {
                     let local = Local::auto_open()?;
@@ -404,9 +490,18 @@ fn run_command(command: opts::Command) -> Result<CommandExitStatus> {
             opts::Crate::Mvp { crate_, opts } => {
                 deps::crate_mvps(crate_, opts)?;
             }
+            opts::Crate::Exempt { crate_, opts } => {
+                deps::update_exemptions(crate_, opts)?;
+            }
+            opts::Crate::Explain { crate_, opts } => {
+                explain::explain_crate(crate_, opts)?;
+            }
             opts::Crate::Info { crate_, opts } => {
                 info::print_crate_info(crate_, opts)?;
             }
+            opts::Crate::Notes { crate_, opts } => {
+                notes::print_crate_notes(crate_, opts)?;
+            }
             opts::Crate::Goto(args) => {
                 goto_crate_src(&args.crate_)?;
             }
@@ -430,6 +525,25 @@ fn run_command(command: opts::Command) -> Result<CommandExitStatus> {
                     let is_advisory = args.advisory
                         || args.affected.is_some()
                         || (!args.issue && args.severity.is_some());
+                    if !args.files.is_empty() {
+                        if is_advisory {
+                            bail!(
+                                "Advisories are for whole crates, and can't be limited to `--files`"
+                            );
+                        }
+                        return create_code_review_proof(
+                            sel,
+                            &args.files,
+                            if args.issue {
+                                TrustProofType::Distrust
+                            } else {
+                                TrustProofType::Untrust
+                            },
+                            &args.common_proof_create,
+                            args.skip_activity_check || args.issue,
+                            args.cargo_opts.clone(),
+                        );
+                    }
                     create_review_proof(
                         sel,
                         if args.issue {
@@ -517,6 +631,7 @@ fn run_command(command: opts::Command) -> Result<CommandExitStatus> {
                 opts::RepoQuery::Issue(args) => list_issues(&args)?,
             },
             opts::Repo::Publish => repo_publish()?,
+            opts::Repo::Export(args) => export_proofs(&args)?,
             opts::Repo::Fetch(cmd) => match cmd {
                 opts::RepoFetch::Trusted {
                     distance_params,
@@ -533,6 +648,12 @@ fn run_command(command: opts::Command) -> Result<CommandExitStatus> {
                     let local = Local::auto_create_or_open()?;
                     local.fetch_all()?;
                 }
+                opts::RepoFetch::RustSec(params) => {
+                    let local = Local::auto_create_or_open()?;
+                    let count =
+                        local.fetch_advisory_db(&params.location, params.trust_level.into())?;
+                    eprintln!("Found {} advisories in {}", count, params.location);
+                }
             },
             opts::Repo::Update(args) => repo_update(args)?,
             opts::Repo::Edit(cmd) => match cmd {
@@ -547,7 +668,7 @@ fn run_command(command: opts::Command) -> Result<CommandExitStatus> {
 
             opts::Repo::Import(args) => {
                 let local = Local::auto_create_or_open()?;
-                let id = local.read_current_unlocked_id(&crev_common::read_passphrase)?;
+                let signer = local.read_current_signer(&crev_common::read_passphrase)?;
 
                 let s = load_stdin_with_prompt()?;
                 let proofs = crev_data::proof::Proof::parse_from(s.as_slice())?;
@@ -560,8 +681,8 @@ fn run_command(command: opts::Command) -> Result<CommandExitStatus> {
                             if args.reset_date {
                                 content.set_date(&now);
                             }
-                            content.set_author(&id.as_public_id());
-                            let proof = content.sign_by(&id)?;
+                            content.set_author(signer.public_id());
+                            let proof = content.sign_by(signer.as_ref())?;
                             maybe_store(&local, &proof, &commit_msg, &args.common)?;
                         }
                         Err(e) => {
@@ -601,11 +722,18 @@ fn run_command(command: opts::Command) -> Result<CommandExitStatus> {
 }
 
 fn ids_from_string(id_strings: &[String]) -> Result<Vec<Id>> {
+    // only needed to look up OpenPGP Ids
+    let mut db = None;
     id_strings
         .iter()
         .map(|s| match Id::crevid_from_str(&s) {
             Ok(s) => Ok(s),
-            Err(e) => bail!("'{}' is not a valid crev Id: {}", s, e),
+            Err(_) => {
+                if db.is_none() {
+                    db = Some(crev_lib::Local::auto_open()?.load_db()?);
+                }
+                id_from_string(db.as_ref().expect("loaded above"), s)
+            }
         })
         .collect()
 }
@@ -0,0 +1,140 @@
+//! `crate notes` - notes of trusted reviewers about lines of the crate
+//! files, shown together with the source they refer to
+use crate::{
+    opts::{CrateSelector, CrateVerifyCommon},
+    policy,
+    prelude::*,
+    repo::Repo,
+};
+use crev_common::convert::OptionDeref;
+use crev_data::proof::{review, trust::TrustLevel, CommonOps};
+use std::path::Path;
+
+/// Lines of the source to show before and after the lines of a note
+const CONTEXT_LINES: u64 = 2;
+
+pub fn print_crate_notes(crate_sel: CrateSelector, args: CrateVerifyCommon) -> Result<()> {
+    crate_sel.ensure_name_given()?;
+
+    let local = crev_lib::Local::auto_create_or_open()?;
+    let db = local.load_db()?;
+    let repo = Repo::auto_open_cwd(args.cargo_opts.clone())?;
+    let policy = policy::load(&repo.workspace_root()?)?;
+
+    let (trust_params, requirements) = match &policy {
+        Some(policy) => (
+            args.trust_params
+                .clone()
+                .or_policy(&policy.policy.trust_distance),
+            args.requirements
+                .clone()
+                .or_policy(&policy.policy.requirements),
+        ),
+        None => (args.trust_params.clone(), args.requirements.clone()),
+    };
+    let requirements = crev_lib::VerificationRequirements::from(requirements);
+    let trust_set =
+        if let Some(for_id) = local.get_for_id_from_str_opt(OptionDeref::as_deref(&args.for_id))? {
+            db.calculate_trust_set(&for_id, &trust_params.into())
+        } else {
+            crev_wot::TrustSet::default()
+        };
+
+    let pkg_id = repo.find_pkgid_by_crate_selector(&crate_sel)?;
+    let crate_ = repo.get_crate(&pkg_id)?;
+    let crate_root = crate_.root();
+    let source = crate::cargo_registry_to_crev_source_id(&pkg_id.source_id());
+
+    let name = pkg_id.name();
+    let mut notes = vec![];
+    for review in db.get_pkg_reviews_for_name(&source, &name) {
+        let reviewer = &review.from().id;
+        if db.is_proof_revoked(reviewer, review.date_utc())
+            || trust_set.get_effective_trust_level(reviewer)
+                < TrustLevel::from(requirements.trust_level)
+        {
+            continue;
+        }
+        let is_same_version = &review.package.id.version == pkg_id.version();
+        // a note about a path outside of the crate can't be shown
+        for note in review
+            .notes
+            .iter()
+            .filter(|note| review::is_path_inside_package(&note.path))
+        {
+            let digest = crev_common::blake2b256sum_file(&crate_root.join(&note.path)).ok();
+            let is_same_content = digest.as_deref() == Some(note.digest.as_slice());
+            // notes made for other versions apply only to files with the same content
+            if is_same_version || is_same_content {
+                notes.push((review, note, is_same_content));
+            }
+        }
+    }
+    notes.sort_by(|a, b| {
+        (&a.1.path, a.1.lines.start, a.1.lines.end).cmp(&(
+            &b.1.path,
+            b.1.lines.start,
+            b.1.lines.end,
+        ))
+    });
+
+    if notes.is_empty() {
+        eprintln!(
+            "No notes of trusted reviewers for {} {}",
+            pkg_id.name(),
+            pkg_id.version()
+        );
+    }
+    for (review, note, is_same_content) in notes {
+        print_note(crate_root, review, note, is_same_content)?;
+    }
+
+    Ok(())
+}
+
+fn print_note(
+    crate_root: &Path,
+    review: &review::Package,
+    note: &review::Note,
+    is_same_content: bool,
+) -> Result<()> {
+    println!(
+        "{}:{} [{}] by {} {} (v{})",
+        note.path.display(),
+        note.lines,
+        note.severity,
+        review.from().id,
+        review.from().url_display(),
+        review.package.id.version
+    );
+    for line in note.text.lines() {
+        println!("  {}", line);
+    }
+
+    let path = crate_root.join(&note.path);
+    if !path.is_file() {
+        println!("  (file not found)");
+        println!();
+        return Ok(());
+    }
+    if !is_same_content {
+        println!("  (the file is different from the one the note was made for)");
+    }
+    let content = std::fs::read(&path)?;
+    let first = note.lines.start.saturating_sub(CONTEXT_LINES).max(1);
+    let last = note.lines.end + CONTEXT_LINES;
+    for (i, line) in String::from_utf8_lossy(&content).lines().enumerate() {
+        let line_number = i as u64 + 1;
+        if first <= line_number && line_number <= last {
+            let marker = if note.lines.contains(line_number) {
+                '>'
+            } else {
+                '|'
+            };
+            println!("{:>6} {} {}", line_number, marker, line);
+        }
+    }
+    println!();
+
+    Ok(())
+}
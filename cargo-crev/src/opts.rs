@@ -95,6 +95,12 @@ pub struct IdNew {
     #[structopt(long = "https-push")]
     /// Setup `https` instead of recommended `ssh`-based push url
     pub use_https_push: bool,
+    #[structopt(long = "openpgp", parse(from_os_str))]
+    /// Instead of generating a new key, import an OpenPGP Ed25519 secret key from a file (`gpg --export-secret-keys --armor <key-id>`)
+    pub openpgp: Option<PathBuf>,
+    #[structopt(long = "ssh", parse(from_os_str))]
+    /// Instead of generating a new key, sign with an Ed25519 SSH key from a private key file (e.g. `~/.ssh/id_ed25519`), protected by its own passphrase
+    pub ssh: Option<PathBuf>,
 }
 
 #[derive(Debug, StructOpt, Clone)]
@@ -104,30 +110,53 @@ pub struct IdSwitch {
 }
 
 /// Parameters describing trust graph traversal
+///
+/// Values not given explicitly are taken from the project policy (if any),
+/// and then from the built-in defaults.
 #[derive(Debug, StructOpt, Clone, Default)]
 pub struct TrustDistanceParams {
-    #[structopt(long = "depth", default_value = "10")]
-    /// [trust-graph-traversal] Maximum allowed distance from the root identity when traversing trust graph
-    pub depth: u64,
+    #[structopt(long = "depth")]
+    /// [trust-graph-traversal] Maximum allowed distance from the root identity when traversing trust graph [default: 10]
+    pub depth: Option<u64>,
+
+    /// [trust-graph-traversal] Cost of traversing trust graph edge of high trust level [default: 0]
+    #[structopt(long = "high-cost")]
+    pub high_cost: Option<u64>,
+    /// [trust-graph-traversal] Cost of traversing trust graph edge of medium trust level [default: 1]
+    #[structopt(long = "medium-cost")]
+    pub medium_cost: Option<u64>,
+    /// [trust-graph-traversal] Cost of traversing trust graph edge of low trust level [default: 5]
+    #[structopt(long = "low-cost")]
+    pub low_cost: Option<u64>,
+    /// [trust-graph-traversal] Ignore trust proofs older than this many days, unless re-signed [default: no limit]
+    #[structopt(long = "trust-max-age-days")]
+    pub trust_max_age_days: Option<u64>,
+}
 
-    /// [trust-graph-traversal] Cost of traversing trust graph edge of high trust level
-    #[structopt(long = "high-cost", default_value = "0")]
-    pub high_cost: u64,
-    /// [trust-graph-traversal] Cost of traversing trust graph edge of medium trust level
-    #[structopt(long = "medium-cost", default_value = "1")]
-    pub medium_cost: u64,
-    /// [trust-graph-traversal] Cost of traversing trust graph edge of low trust level
-    #[structopt(long = "low-cost", default_value = "5")]
-    pub low_cost: u64,
+impl TrustDistanceParams {
+    /// Fill values not given on the command line from `policy`
+    pub fn or_policy(self, policy: &crate::policy::TrustDistancePolicy) -> Self {
+        Self {
+            depth: self.depth.or(policy.depth),
+            high_cost: self.high_cost.or(policy.high_cost),
+            medium_cost: self.medium_cost.or(policy.medium_cost),
+            low_cost: self.low_cost.or(policy.low_cost),
+            trust_max_age_days: self.trust_max_age_days.or(policy.trust_max_age_days),
+        }
+    }
 }
 
 impl From<TrustDistanceParams> for crev_lib::TrustDistanceParams {
     fn from(params: TrustDistanceParams) -> Self {
+        let default = crev_lib::TrustDistanceParams::default();
         crev_lib::TrustDistanceParams {
-            max_distance: params.depth,
-            high_trust_distance: params.high_cost,
-            medium_trust_distance: params.medium_cost,
-            low_trust_distance: params.low_cost,
+            max_distance: params.depth.unwrap_or(default.max_distance),
+            high_trust_distance: params.high_cost.unwrap_or(default.high_trust_distance),
+            medium_trust_distance: params.medium_cost.unwrap_or(default.medium_trust_distance),
+            low_trust_distance: params.low_cost.unwrap_or(default.low_trust_distance),
+            trust_max_age: params
+                .trust_max_age_days
+                .map(|days| chrono::Duration::days(days as i64)),
         }
     }
 }
@@ -164,29 +193,65 @@ pub struct TrustLevelRequirements {
 }
 
 /// Verification Requirements
+///
+/// Values not given explicitly are taken from the project policy (if any),
+/// and then from the built-in defaults.
 #[derive(Debug, StructOpt, Clone, Default)]
 pub struct VerificationRequirements {
-    #[structopt(flatten)]
-    pub trust_level: TrustLevelRequirements,
+    /// Minimum trust level required [default: low]
+    #[structopt(long = "trust")]
+    pub trust_level: Option<crev_data::Level>,
+
+    /// Number of reviews required [default: 1]
+    #[structopt(long = "redundancy")]
+    pub redundancy: Option<u64>,
+    /// Required understanding [default: none]
+    #[structopt(long = "understanding")]
+    pub understanding_level: Option<Level>,
+    /// Required thoroughness [default: none]
+    #[structopt(long = "thoroughness")]
+    pub thoroughness_level: Option<Level>,
+    /// Count reviews older than this many days as if their authors were trusted one level less [default: no limit]
+    #[structopt(long = "review-max-age-days")]
+    pub review_max_age_days: Option<u64>,
+    /// Count crates with at least this percentage of lines of code covered by trusted reviews as verified [default: no]
+    #[structopt(long = "min-coverage")]
+    pub min_coverage: Option<u64>,
+    /// With `--min-coverage`, also require this percentage of lines of code in files with `unsafe` to be covered by trusted reviews [default: no]
+    #[structopt(long = "min-unsafe-coverage")]
+    pub min_unsafe_coverage: Option<u64>,
+}
 
-    /// Number of reviews required
-    #[structopt(long = "redundancy", default_value = "1")]
-    pub redundancy: u64,
-    /// Required understanding
-    #[structopt(long = "understanding", default_value = "none")]
-    pub understanding_level: Level,
-    /// Required thoroughness
-    #[structopt(long = "thoroughness", default_value = "none")]
-    pub thoroughness_level: Level,
+impl VerificationRequirements {
+    /// Fill values not given on the command line from `policy`
+    pub fn or_policy(self, policy: &crate::policy::RequirementsPolicy) -> Self {
+        Self {
+            trust_level: self.trust_level.or(policy.trust),
+            redundancy: self.redundancy.or(policy.redundancy),
+            understanding_level: self.understanding_level.or(policy.understanding),
+            thoroughness_level: self.thoroughness_level.or(policy.thoroughness),
+            review_max_age_days: self.review_max_age_days.or(policy.review_max_age_days),
+            min_coverage: self.min_coverage.or(policy.min_coverage),
+            min_unsafe_coverage: self.min_unsafe_coverage.or(policy.min_unsafe_coverage),
+        }
+    }
+
+    /// Whether crates can be verified by their reviewed coverage
+    pub fn requires_coverage(&self) -> bool {
+        self.min_coverage.is_some()
+    }
 }
 
 impl From<VerificationRequirements> for crev_lib::VerificationRequirements {
     fn from(req: VerificationRequirements) -> Self {
         crev_lib::VerificationRequirements {
-            trust_level: req.trust_level.trust_level,
-            redundancy: req.redundancy,
-            understanding: req.understanding_level,
-            thoroughness: req.thoroughness_level,
+            trust_level: req.trust_level.unwrap_or(Level::Low),
+            redundancy: req.redundancy.unwrap_or(1),
+            understanding: req.understanding_level.unwrap_or(Level::None),
+            thoroughness: req.thoroughness_level.unwrap_or(Level::None),
+            review_max_age: req
+                .review_max_age_days
+                .map(|days| chrono::Duration::days(days as i64)),
         }
     }
 }
@@ -243,6 +308,10 @@ pub struct CrateVerifyColumns {
     /// Show Lines of Code
     pub show_loc: Option<Option<bool>>,
 
+    #[structopt(long = "show-coverage")]
+    /// Show percentage of Lines of Code covered by trusted reviews
+    pub show_coverage: Option<Option<bool>>,
+
     #[structopt(long = "show-issues")]
     /// Show count of issues reported
     pub show_issues: Option<Option<bool>>,
@@ -281,6 +350,7 @@ impl CrateVerifyColumns {
             || self.show_flags.is_some()
             || self.show_issues.is_some()
             || self.show_loc.is_some()
+            || self.show_coverage.is_some()
             || self.show_geiger.is_some()
             || self.show_all
     }
@@ -294,16 +364,47 @@ impl CrateVerifyColumns {
     show_x!(show_owners, false);
     show_x!(show_issues, true);
     show_x!(show_loc, false);
+    show_x!(show_coverage, false);
     show_x!(show_geiger, false);
 }
 
+/// Output format of `crate verify`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerifyOutputFormat {
+    /// Colored columns, for humans
+    Human,
+    /// A single JSON document with all the crates
+    Json,
+    /// One JSON object per crate, printed as soon as it is available
+    JsonLines,
+}
+
+impl Default for VerifyOutputFormat {
+    fn default() -> Self {
+        VerifyOutputFormat::Human
+    }
+}
+
+impl std::str::FromStr for VerifyOutputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "human" => VerifyOutputFormat::Human,
+            "json" => VerifyOutputFormat::Json,
+            "json-lines" => VerifyOutputFormat::JsonLines,
+            _ => bail!("Unknown output format: {}", s),
+        })
+    }
+}
+
 #[derive(Debug, StructOpt, Clone, Default)]
 #[structopt(
     after_help = r"Recursive mode will calculate most metrics for the crate together with all its transitive dependencies.
 
 Column description:
 
-- trust      - Trust check result: `pass` for trusted, `none` for lacking reviews, `flagged` or `dangerous` for crates with problem reports.
+- trust      - Trust check result: `pass` for trusted, `none` for lacking reviews, `exmt` for exempted (see `crate exempt`), `flagged` or `dangerous` for crates with problem reports.
 - reviews    - Number of reviews for the specific version and for all available versions (total)
 - downloads  - Download counts from crates.io for the specific version and all versions
 - owner
@@ -313,6 +414,7 @@ Column description:
     - Total number of owner groups ignoring subsets
 - issues     - Number of issues repored (from trusted sources/all)
 - lines      - Lines of Rust code
+- cover      - Percentage of lines of Rust code covered by trusted package or code reviews
 - geiger     - Geiger score: number of `unsafe` lines
 - flgs       - Flags for specific types of packages
   - CB         - Custom Build
@@ -346,6 +448,26 @@ pub struct CrateVerify {
     #[structopt(long = "recursive")]
     /// Calculate recursive metrics for your packages
     pub recursive: bool,
+
+    #[structopt(long = "file-coverage")]
+    /// Count crates with every file reviewed by trusted code reviews as verified
+    pub file_coverage: bool,
+
+    #[structopt(long = "lockfile", parse(from_os_str))]
+    /// Verify exactly the packages locked in this `Cargo.lock`, without opening its workspace
+    pub lockfile: Option<PathBuf>,
+
+    #[structopt(long = "lockfile-policy", requires = "lockfile")]
+    /// Use the policy and exemptions found next to the `--lockfile` (instead of the ones of the current workspace)
+    pub lockfile_policy: bool,
+
+    #[structopt(
+        long = "format",
+        default_value = "human",
+        possible_values = &["human", "json", "json-lines"]
+    )]
+    /// Output format (`json` and `json-lines` print all the columns)
+    pub format: VerifyOutputFormat,
 }
 
 #[derive(Debug, StructOpt, Clone)]
@@ -357,6 +479,57 @@ pub struct IdTrust {
     pub common_proof_create: CommonProofCreate,
 }
 
+#[derive(Debug, StructOpt, Clone)]
+pub struct IdExplain {
+    /// Public Id to explain
+    pub public_id: String,
+
+    #[structopt(flatten)]
+    pub trust_params: TrustDistanceParams,
+
+    #[structopt(long = "for-id")]
+    pub for_id: Option<String>,
+}
+
+/// Output format of `id graph`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdGraphFormat {
+    /// Graphviz DOT
+    Dot,
+    /// A single JSON document with all the nodes and edges
+    Json,
+}
+
+impl std::str::FromStr for IdGraphFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "dot" => IdGraphFormat::Dot,
+            "json" => IdGraphFormat::Json,
+            _ => bail!("Unknown output format: {}", s),
+        })
+    }
+}
+
+#[derive(Debug, StructOpt, Clone)]
+pub struct IdGraph {
+    #[structopt(flatten)]
+    pub trust_params: TrustDistanceParams,
+
+    /// Root of the graph (current Id by default)
+    #[structopt(long = "for-id")]
+    pub for_id: Option<String>,
+
+    /// Include only Ids at most this many trust edges away from the root
+    #[structopt(long = "max-depth")]
+    pub max_depth: Option<u64>,
+
+    #[structopt(long = "format", default_value = "dot", possible_values = &["dot", "json"])]
+    /// Output format
+    pub format: IdGraphFormat,
+}
+
 #[derive(Debug, StructOpt, Clone)]
 pub struct TrustUrls {
     /// Public IDs or proof repo URLs to create Trust Proof for
@@ -372,6 +545,17 @@ pub struct RepoFetchUrl {
     pub url: String,
 }
 
+#[derive(Debug, StructOpt, Clone)]
+pub struct RepoFetchRustSec {
+    /// Local directory or git URL of a RustSec advisory database
+    #[structopt(default_value = "https://github.com/rustsec/advisory-db")]
+    pub location: String,
+
+    /// Trust level of the advisories from the database
+    #[structopt(long = "trust", default_value = "medium")]
+    pub trust_level: crev_data::Level,
+}
+
 #[derive(Debug, StructOpt, Clone)]
 pub enum RepoFetch {
     /// Fetch updates from trusted Ids
@@ -391,6 +575,10 @@ pub enum RepoFetch {
     #[structopt(name = "all")]
     /// Fetch all previously retrieved public proof repositories
     All,
+
+    #[structopt(name = "rustsec")]
+    /// Fetch a RustSec advisory database, and import its advisories from now on
+    RustSec(RepoFetchRustSec),
 }
 
 #[derive(Debug, StructOpt, Clone)]
@@ -567,6 +755,10 @@ pub struct CrateReview {
     #[allow(clippy::option_option)]
     pub diff: Option<Option<semver::Version>>,
 
+    /// Review only these files (paths relative to the crate root)
+    #[structopt(long = "files", parse(from_os_str))]
+    pub files: Vec<PathBuf>,
+
     #[structopt(flatten)]
     pub cargo_opts: CargoOpts,
 }
@@ -578,6 +770,24 @@ pub struct AdviseCommon {
     pub severity: Level,
 }
 
+#[derive(Debug, StructOpt, Clone)]
+pub struct CrateExempt {
+    #[structopt(flatten)]
+    pub common: CrateVerifyCommon,
+
+    /// Person responsible for the new exemptions
+    #[structopt(long = "owner")]
+    pub owner: String,
+
+    /// Reason for the new exemptions
+    #[structopt(long = "reason", default_value = "Not reviewed yet")]
+    pub reason: String,
+
+    /// Number of days after which the new exemptions expire
+    #[structopt(long = "days", default_value = "90")]
+    pub days: i64,
+}
+
 #[derive(Debug, StructOpt, Clone)]
 pub struct CrateSearch {
     /// Number of results
@@ -592,6 +802,77 @@ pub struct IdExport {
     pub id: Option<String>,
 }
 
+#[derive(Debug, StructOpt, Clone)]
+pub struct IdRevoke {
+    /// Id to revoke (current Id by default)
+    pub id: Option<String>,
+
+    /// Invalidate only proofs created since this date (`YYYY-MM-DD` or RFC 3339), instead of all of them
+    #[structopt(long = "since")]
+    pub since: Option<String>,
+
+    /// Reason for the revocation
+    #[structopt(long = "comment", default_value = "")]
+    pub comment: String,
+
+    #[structopt(flatten)]
+    pub common_proof_create: CommonProofCreate,
+}
+
+#[derive(Debug, StructOpt, Clone)]
+pub struct IdTeam {
+    /// Ids of the members of the team
+    #[structopt(required = true)]
+    pub members: Vec<String>,
+
+    /// Number of members that have to review a crate for it to count as reviewed by the team
+    #[structopt(long = "threshold")]
+    pub threshold: u64,
+
+    /// Description of the team
+    #[structopt(long = "comment", default_value = "")]
+    pub comment: String,
+
+    #[structopt(flatten)]
+    pub common_proof_create: CommonProofCreate,
+}
+
+#[derive(Debug, StructOpt, Clone)]
+pub struct IdRotate {
+    /// Reason for the rotation
+    #[structopt(long = "comment", default_value = "")]
+    pub comment: String,
+
+    #[structopt(flatten)]
+    pub common_proof_create: CommonProofCreate,
+}
+
+#[derive(Debug, StructOpt, Clone)]
+pub enum Agent {
+    /// Start the agent in the background
+    #[structopt(name = "start")]
+    Start(AgentStart),
+
+    /// Lock all the Ids held by the agent
+    #[structopt(name = "lock")]
+    Lock,
+
+    /// Show whether the agent is running, and the Ids it holds unlocked
+    #[structopt(name = "status")]
+    Status,
+}
+
+#[derive(Debug, StructOpt, Clone)]
+pub struct AgentStart {
+    /// Lock Ids not used for this many minutes
+    #[structopt(long = "timeout-minutes", default_value = "15")]
+    pub timeout_minutes: u64,
+
+    /// Run in the foreground
+    #[structopt(long = "foreground")]
+    pub foreground: bool,
+}
+
 #[derive(Debug, StructOpt, Clone)]
 pub struct RepoImport {
     /// Reset proof date to current date
@@ -602,6 +883,44 @@ pub struct RepoImport {
     pub common: CommonProofCreate,
 }
 
+/// Output format of `repo export`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// One OSV record per advisory and issue
+    Osv,
+}
+
+impl std::str::FromStr for ExportFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "osv" => ExportFormat::Osv,
+            _ => bail!("Unknown export format: {}", s),
+        })
+    }
+}
+
+#[derive(Debug, StructOpt, Clone)]
+pub struct RepoExport {
+    #[structopt(long = "format", default_value = "osv", possible_values = &["osv"])]
+    /// Export format
+    pub format: ExportFormat,
+
+    #[structopt(flatten)]
+    pub trust_params: TrustDistanceParams,
+
+    #[structopt(flatten)]
+    pub trust_level: TrustLevelRequirements,
+
+    #[structopt(long = "for-id")]
+    pub for_id: Option<String>,
+
+    /// Write every record to `<id>.json` in this directory, instead of a JSON array to stdout
+    #[structopt(long = "output-dir", parse(from_os_str))]
+    pub output_dir: Option<PathBuf>,
+}
+
 #[derive(Debug, StructOpt, Clone)]
 pub enum Id {
     /// Create a new Id
@@ -639,6 +958,26 @@ pub enum Id {
     /// Query Ids
     #[structopt(name = "query")]
     Query(IdQuery),
+
+    /// Explain how an Id is trusted (or not) in the web of trust
+    #[structopt(name = "explain")]
+    Explain(IdExplain),
+
+    /// Revoke an Id (e.g. after its secret key leaked)
+    #[structopt(name = "revoke")]
+    Revoke(IdRevoke),
+
+    /// Replace the current Id with a new one, carrying over the trust in it
+    #[structopt(name = "rotate")]
+    Rotate(IdRotate),
+
+    /// Declare the current Id a team of other Ids, reviewing together
+    #[structopt(name = "team")]
+    Team(IdTeam),
+
+    /// Export the web of trust as a graph (e.g. `cargo crev id graph | dot -Tsvg > wot.svg`)
+    #[structopt(name = "graph")]
+    Graph(IdGraph),
 }
 
 #[derive(Debug, StructOpt, Clone)]
@@ -684,6 +1023,15 @@ pub enum Crate {
         crate_: CrateSelector,
     },
 
+    /// Regenerate the exemptions list from crates currently failing verification
+    #[structopt(name = "exempt")]
+    Exempt {
+        #[structopt(flatten)]
+        opts: CrateExempt,
+        #[structopt(flatten)]
+        crate_: CrateSelector,
+    },
+
     /// Review a crate (code review, security advisory, flag issues)
     #[structopt(name = "review")]
     Review(CrateReview),
@@ -696,6 +1044,15 @@ pub enum Crate {
     #[structopt(name = "search")]
     Search(CrateSearch),
 
+    /// Explain why the given crate passes or fails verification
+    #[structopt(name = "explain")]
+    Explain {
+        #[structopt(flatten)]
+        opts: CrateVerifyCommon,
+        #[structopt(flatten)]
+        crate_: CrateSelector,
+    },
+
     /// Display rich info about the given crate
     #[structopt(name = "info")]
     Info {
@@ -704,6 +1061,15 @@ pub enum Crate {
         #[structopt(flatten)]
         crate_: CrateSelector,
     },
+
+    /// Show notes of trusted reviewers about lines of the crate source
+    #[structopt(name = "notes")]
+    Notes {
+        #[structopt(flatten)]
+        opts: CrateVerifyCommon,
+        #[structopt(flatten)]
+        crate_: CrateSelector,
+    },
 }
 
 #[derive(Debug, StructOpt, Clone)]
@@ -735,6 +1101,10 @@ pub struct ProofFind {
     /// Find a proof by a crev Id
     #[structopt(name = "author", long = "author")]
     pub author: Option<String>,
+
+    /// Find proofs of packages from this source (e.g. `https://crates.io`, `git+<URL>`)
+    #[structopt(name = "source", long = "source")]
+    pub source: Option<String>,
 }
 
 #[derive(Debug, StructOpt, Clone)]
@@ -762,11 +1132,10 @@ pub enum Repo {
     #[structopt(name = "import")]
     Import(RepoImport),
 
-    /*
-    /// Export proofs
+    /// Export advisories and issues of trusted reviewers
     #[structopt(name = "export")]
-    Export,
-    */
+    Export(RepoExport),
+
     /// Query proofs
     #[structopt(name = "query")]
     Query(RepoQuery),
@@ -792,6 +1161,10 @@ pub enum Proof {
 #[structopt(setting = structopt::clap::AppSettings::DeriveDisplayOrder)]
 #[structopt(setting = structopt::clap::AppSettings::DisableHelpSubcommand)]
 pub enum Command {
+    /// Agent keeping your Id unlocked, so the passphrase isn't asked for every proof
+    #[structopt(name = "agent")]
+    Agent(Agent),
+
     /// Local configuration
     #[structopt(name = "config")]
     Config(Config),
@@ -0,0 +1,230 @@
+//! Project-level verification policy
+//!
+//! A policy can be checked in together with the project, so that everyone
+//! verifying it uses the same requirements. It is read from `crev.toml`
+//! in the workspace root, or from `[workspace.metadata.crev]`
+//! or `[package.metadata.crev]` of the root `Cargo.toml`.
+//!
+//! ```toml
+//! [requirements]
+//! trust = "medium"
+//! redundancy = 1
+//! # reviews older than two years count as less trusted
+//! review-max-age-days = 730
+//! # crates without package reviews pass if most of their code is reviewed,
+//! # including every file with `unsafe`
+//! min-coverage = 80
+//! min-unsafe-coverage = 100
+//!
+//! [trust-distance]
+//! depth = 5
+//! # trust proofs expire unless re-signed within a year
+//! trust-max-age-days = 365
+//!
+//! # crates with a `build.rs`
+//! [custom-build]
+//! redundancy = 2
+//!
+//! # crates used only as dev-dependencies
+//! [dev-dependencies]
+//! trust = "low"
+//!
+//! [crates.openssl-sys]
+//! thoroughness = "high"
+//! ```
+//!
+//! Values given explicitly on the command line always take precedence.
+use crate::{opts, prelude::*};
+use crev_data::Level;
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+pub const POLICY_FILE_NAME: &str = "crev.toml";
+
+/// Verification requirements that can be set by a policy
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct RequirementsPolicy {
+    pub trust: Option<Level>,
+    pub redundancy: Option<u64>,
+    pub understanding: Option<Level>,
+    pub thoroughness: Option<Level>,
+    pub review_max_age_days: Option<u64>,
+    /// Percentage of lines of code covered by reviews that verifies a crate
+    pub min_coverage: Option<u64>,
+    /// Same as `min_coverage`, but for files with `unsafe` code only,
+    /// required in addition to `min_coverage`
+    pub min_unsafe_coverage: Option<u64>,
+}
+
+impl RequirementsPolicy {
+    /// Values set in `self`, with the missing ones taken from `other`
+    fn or(&self, other: &RequirementsPolicy) -> Self {
+        Self {
+            trust: self.trust.or(other.trust),
+            redundancy: self.redundancy.or(other.redundancy),
+            understanding: self.understanding.or(other.understanding),
+            thoroughness: self.thoroughness.or(other.thoroughness),
+            review_max_age_days: self.review_max_age_days.or(other.review_max_age_days),
+            min_coverage: self.min_coverage.or(other.min_coverage),
+            min_unsafe_coverage: self.min_unsafe_coverage.or(other.min_unsafe_coverage),
+        }
+    }
+}
+
+/// Trust graph traversal parameters that can be set by a policy
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct TrustDistancePolicy {
+    pub depth: Option<u64>,
+    pub high_cost: Option<u64>,
+    pub medium_cost: Option<u64>,
+    pub low_cost: Option<u64>,
+    pub trust_max_age_days: Option<u64>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Policy {
+    /// Requirements for all the crates
+    #[serde(default)]
+    pub requirements: RequirementsPolicy,
+    #[serde(default)]
+    pub trust_distance: TrustDistancePolicy,
+    /// Overrides for crates with a custom build script
+    pub custom_build: Option<RequirementsPolicy>,
+    /// Overrides for crates used only as dev-dependencies
+    pub dev_dependencies: Option<RequirementsPolicy>,
+    /// Overrides for specific crates, by name
+    #[serde(default)]
+    pub crates: HashMap<String, RequirementsPolicy>,
+}
+
+/// Rule of a policy that determined requirements for a crate
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PolicyRule {
+    Requirements,
+    CustomBuild,
+    DevDependencies,
+    Crate(String),
+}
+
+impl fmt::Display for PolicyRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PolicyRule::Requirements => f.write_str("[requirements]"),
+            PolicyRule::CustomBuild => f.write_str("[custom-build]"),
+            PolicyRule::DevDependencies => f.write_str("[dev-dependencies]"),
+            PolicyRule::Crate(name) => write!(f, "[crates.{}]", name),
+        }
+    }
+}
+
+/// What kind of crate are we looking for requirements for
+#[derive(Copy, Clone, Debug)]
+pub struct CrateKind<'a> {
+    pub name: &'a str,
+    pub has_custom_build: bool,
+    pub is_dev_dependency_only: bool,
+}
+
+/// A policy, together with where it was loaded from
+#[derive(Clone, Debug)]
+pub struct LoadedPolicy {
+    pub path: PathBuf,
+    pub policy: Policy,
+}
+
+impl Policy {
+    /// Requirements for a given crate, and the rules that contributed to them
+    ///
+    /// The most specific rule is last.
+    pub fn requirements_for(&self, kind: CrateKind<'_>) -> (RequirementsPolicy, Vec<PolicyRule>) {
+        let mut requirements = self.requirements.clone();
+        let mut rules = vec![PolicyRule::Requirements];
+
+        if kind.is_dev_dependency_only {
+            if let Some(dev) = &self.dev_dependencies {
+                requirements = dev.or(&requirements);
+                rules.push(PolicyRule::DevDependencies);
+            }
+        }
+
+        if kind.has_custom_build {
+            if let Some(custom_build) = &self.custom_build {
+                requirements = custom_build.or(&requirements);
+                rules.push(PolicyRule::CustomBuild);
+            }
+        }
+
+        if let Some(crate_) = self.crates.get(kind.name) {
+            requirements = crate_.or(&requirements);
+            rules.push(PolicyRule::Crate(kind.name.to_owned()));
+        }
+
+        (requirements, rules)
+    }
+}
+
+fn parse_crev_toml(path: &Path) -> Result<Policy> {
+    let content = std::fs::read_to_string(path)?;
+    Ok(toml::from_str(&content).with_context(|| format!("Parsing {}", path.display()))?)
+}
+
+fn parse_cargo_toml_metadata(path: &Path) -> Result<Option<Policy>> {
+    // e.g. a standalone lockfile, without its manifest
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content = std::fs::read_to_string(path)?;
+    let manifest: toml::Value =
+        toml::from_str(&content).with_context(|| format!("Parsing {}", path.display()))?;
+
+    for section in &["workspace", "package"] {
+        if let Some(policy) = manifest
+            .get(section)
+            .and_then(|s| s.get("metadata"))
+            .and_then(|m| m.get("crev"))
+        {
+            return Ok(Some(policy.clone().try_into().with_context(|| {
+                format!(
+                    "Parsing `[{}.metadata.crev]` in {}",
+                    section,
+                    path.display()
+                )
+            })?));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Load the policy of a workspace, if there's any
+pub fn load(workspace_root: &Path) -> Result<Option<LoadedPolicy>> {
+    let path = workspace_root.join(POLICY_FILE_NAME);
+    if path.exists() {
+        return Ok(Some(LoadedPolicy {
+            policy: parse_crev_toml(&path)?,
+            path,
+        }));
+    }
+
+    let path = workspace_root.join("Cargo.toml");
+    Ok(parse_cargo_toml_metadata(&path)?.map(|policy| LoadedPolicy { path, policy }))
+}
+
+impl LoadedPolicy {
+    /// Effective requirements for a crate: command line, then policy, then built-in defaults
+    pub fn requirements_for(
+        &self,
+        cli: &opts::VerificationRequirements,
+        kind: CrateKind<'_>,
+    ) -> (opts::VerificationRequirements, Vec<PolicyRule>) {
+        let (policy_requirements, rules) = self.policy.requirements_for(kind);
+        (cli.clone().or_policy(&policy_requirements), rules)
+    }
+}
@@ -15,17 +15,24 @@ use cargo::{
 };
 use cargo_platform::Cfg;
 use crev_common::convert::OptionDeref;
-use petgraph::graph::NodeIndex;
+use petgraph::{graph::NodeIndex, visit::EdgeRef};
 use std::{
     collections::{hash_map::Entry, BTreeSet, HashMap, HashSet},
     env,
-    path::PathBuf,
+    path::{Path, PathBuf},
     rc::Rc,
     str::{self, FromStr},
 };
 
 use crate::{crates_io, prelude::*};
 
+/// Whether packages from this source are local code (path dependencies, workspace members)
+///
+/// Packages from registries and git repositories are the ones that can be reviewed.
+pub fn is_local_source(source_id: SourceId) -> bool {
+    !(source_id.is_registry() || source_id.is_git())
+}
+
 #[derive(Debug)]
 struct Node {
     id: PackageId,
@@ -40,6 +47,40 @@ pub struct Graph {
 }
 
 impl Graph {
+    /// Graph of packages with the given dependencies (e.g. locked in a `Cargo.lock`)
+    ///
+    /// The kinds of the dependencies are not known, so they are all considered normal.
+    pub fn from_dependencies(
+        packages: &[Package],
+        dependencies: &HashMap<PackageId, Vec<PackageId>>,
+    ) -> Graph {
+        let mut graph = Graph {
+            graph: petgraph::Graph::new(),
+            nodes: HashMap::new(),
+        };
+
+        for pkg in packages {
+            let node = Node {
+                id: pkg.package_id(),
+                metadata: pkg.manifest().metadata().clone(),
+            };
+            graph
+                .nodes
+                .insert(pkg.package_id(), graph.graph.add_node(node));
+        }
+        for (pkg_id, dep_ids) in dependencies {
+            for dep_id in dep_ids {
+                if let (Some(idx), Some(dep_idx)) =
+                    (graph.nodes.get(pkg_id), graph.nodes.get(dep_id))
+                {
+                    graph.graph.add_edge(*idx, *dep_idx, DepKind::Normal);
+                }
+            }
+        }
+
+        graph
+    }
+
     pub fn get_all_pkg_ids<'s>(&'s self) -> impl Iterator<Item = PackageId> + 's {
         self.nodes.keys().cloned()
     }
@@ -106,6 +147,32 @@ impl Graph {
 
         processed
     }
+
+    /// All the packages reachable from `roots` without going through dev-dependency edges
+    pub fn get_non_dev_reachable_from(&self, roots: &[PackageId]) -> HashSet<PackageId> {
+        let mut pending: Vec<_> = roots
+            .iter()
+            .filter_map(|pkg_id| self.nodes.get(pkg_id).cloned())
+            .collect();
+        let mut processed = HashSet::new();
+
+        while let Some(node_idx) = pending.pop() {
+            if !processed.insert(self.graph.node_weight(node_idx).unwrap().id) {
+                continue;
+            }
+
+            for edge in self
+                .graph
+                .edges_directed(node_idx, petgraph::Direction::Outgoing)
+            {
+                if *edge.weight() != DepKind::Development {
+                    pending.push(edge.target());
+                }
+            }
+        }
+
+        processed
+    }
 }
 
 fn get_cfgs(rustc: &Rustc, target: Option<&str>) -> Result<Vec<Cfg>> {
@@ -278,13 +345,29 @@ impl Repo {
     }
 
     pub fn auto_open_cwd(cargo_opts: opts::CargoOpts) -> Result<Self> {
-        cargo::core::enable_nightly_features();
         let manifest_path = if let Some(ref path) = cargo_opts.manifest_path {
             path.to_owned()
         } else {
             let cwd = env::current_dir()?;
             find_root_manifest_for_wd(&cwd)?
         };
+        Self::open(manifest_path, cargo_opts)
+    }
+
+    /// Open the project a standalone `Cargo.lock` is for
+    ///
+    /// Its workspace doesn't have to exist or build: only the cargo
+    /// configuration and package sources are used (see `get_packages`).
+    pub fn for_lockfile(lockfile_path: &Path, cargo_opts: opts::CargoOpts) -> Result<Self> {
+        let manifest_path = lockfile_path
+            .parent()
+            .unwrap_or_else(|| Path::new("."))
+            .join("Cargo.toml");
+        Self::open(manifest_path, cargo_opts)
+    }
+
+    fn open(manifest_path: PathBuf, cargo_opts: opts::CargoOpts) -> Result<Self> {
+        cargo::core::enable_nightly_features();
         let mut config = cargo::util::config::Config::default()?;
         config.configure(
             0,
@@ -327,6 +410,11 @@ impl Repo {
             .to_string_lossy()
     }
 
+    /// Directory of the workspace root `Cargo.toml`
+    pub fn workspace_root(&self) -> Result<PathBuf> {
+        Ok(self.workspace()?.root().to_owned())
+    }
+
     fn workspace(&self) -> CargoResult<Workspace<'_>> {
         Workspace::new(&self.manifest_path, &self.config)
     }
@@ -390,7 +478,9 @@ impl Repo {
         let crates_io = crates_io::Client::new(&local)?;
 
         self.for_every_non_local_dep_crate(|crate_| {
-            let _ = crates_io.get_downloads_count(&crate_.name(), &crate_.version());
+            if crate_.package_id().source_id().is_default_registry() {
+                let _ = crates_io.get_downloads_count(&crate_.name(), &crate_.version());
+            }
             Ok(())
         })?;
 
@@ -405,13 +495,19 @@ impl Repo {
         Ok(source)
     }
 
-    pub fn load_source_with_whitelist<'a>(
+    /// Load any source: a registry, or a git repository
+    pub fn load_source_for<'a>(
         &'a self,
+        source_id: SourceId,
         yanked_whitelist: HashSet<PackageId>,
     ) -> Result<Box<dyn cargo::core::source::Source + 'a>> {
-        let source_id = SourceId::crates_io(&self.config)?;
         let map = cargo::sources::SourceConfigMap::new(&self.config)?;
-        let source = map.load(source_id, &yanked_whitelist)?;
+        let mut source = map.load(source_id, &yanked_whitelist)?;
+        if source_id.is_git() {
+            // git sources have to be checked out before they can give out packages
+            let _lock = self.config.acquire_package_cache_lock()?;
+            source.update()?;
+        }
         Ok(source)
     }
 
@@ -441,15 +537,23 @@ impl Repo {
             self.cargo_opts.no_default_features,
             self.cargo_opts.no_dev_dependencies,
         )?;
-        let mut source = self.load_source()?;
 
         let pkgs = package_set.get_many(package_set.package_ids())?;
 
+        // loaded once per source, when a package from it is missing
+        let mut sources = HashMap::new();
         for pkg in pkgs {
-            if !pkg.summary().source_id().is_registry() {
+            let source_id = pkg.summary().source_id();
+            if is_local_source(source_id) {
                 continue;
             }
             if !pkg.root().exists() {
+                let source = match sources.entry(source_id) {
+                    Entry::Occupied(entry) => entry.into_mut(),
+                    Entry::Vacant(entry) => {
+                        entry.insert(self.load_source_for(source_id, HashSet::new())?)
+                    }
+                };
                 source.download(pkg.package_id())?;
             }
 
@@ -484,7 +588,7 @@ impl Repo {
         )?;
 
         for pkg_id in package_set.package_ids() {
-            if !pkg_id.source_id().is_registry() {
+            if is_local_source(pkg_id.source_id()) {
                 continue;
             }
 
@@ -558,7 +662,7 @@ impl Repo {
         // We need to whitelist the crate, in case it was yanked
         let mut yanked_whitelist = HashSet::default();
         yanked_whitelist.insert(pkg_id.to_owned());
-        let source = self.load_source_with_whitelist(yanked_whitelist)?;
+        let source = self.load_source_for(pkg_id.source_id(), yanked_whitelist)?;
 
         let mut source_map = SourceMap::new();
         source_map.insert(source);
@@ -567,20 +671,46 @@ impl Repo {
         Ok(package_set.get_one(pkg_id.to_owned())?.to_owned())
     }
 
+    /// Get the given packages, downloading them if needed, without resolving any workspace
+    pub fn get_packages(&self, pkg_ids: &[PackageId]) -> Result<Vec<Package>> {
+        // locked packages might have been yanked since
+        let yanked_whitelist: HashSet<_> = pkg_ids.iter().cloned().collect();
+        let source_ids: HashSet<_> = pkg_ids.iter().map(|pkg_id| pkg_id.source_id()).collect();
+
+        let mut source_map = SourceMap::new();
+        for source_id in source_ids {
+            source_map.insert(self.load_source_for(source_id, yanked_whitelist.clone())?);
+        }
+        let package_set = PackageSet::new(pkg_ids, source_map, &self.config)?;
+        let pkgs = package_set
+            .get_many(pkg_ids.iter().cloned())?
+            .into_iter()
+            .cloned()
+            .collect();
+        Ok(pkgs)
+    }
+
     pub fn find_independent_pkg_id_by_selector(
         &self,
         name: &str,
         version: Option<&Version>,
     ) -> Result<Option<PackageId>> {
-        let mut source = if let Some(version) = version {
+        self.find_pkgid_in_source(name, version, SourceId::crates_io(&self.config)?)
+    }
+
+    /// Find a crate in a given source, regardless of the dependencies
+    pub fn find_pkgid_in_source(
+        &self,
+        name: &str,
+        version: Option<&Version>,
+        source_id: SourceId,
+    ) -> Result<Option<PackageId>> {
+        let mut yanked_whitelist = HashSet::default();
+        if let Some(version) = version {
             // special case - we need to whitelist the crate, in case it was yanked
-            let mut yanked_whitelist = HashSet::default();
-            let source_id = SourceId::crates_io(&self.config)?;
             yanked_whitelist.insert(PackageId::new(name, version, source_id)?);
-            self.load_source_with_whitelist(yanked_whitelist)?
-        } else {
-            self.load_source()?
-        };
+        }
+        let mut source = self.load_source_for(source_id, yanked_whitelist)?;
         let mut summaries = vec![];
         let version_str = version.map(ToString::to_string);
         let dependency_request = Dependency::parse_no_deprecated(
@@ -9,7 +9,10 @@ use crev_data::{
     Rating,
 };
 use crev_lib::{self, local::Local, TrustProofType};
-use std::default::Default;
+use std::{
+    default::Default,
+    path::{Path, PathBuf},
+};
 
 use crate::{repo::*, shared::*};
 
@@ -36,55 +39,60 @@ pub fn create_review_proof(
 
     assert!(!crate_root.starts_with(std::env::current_dir()?));
     let local = Local::auto_open()?;
+    let source = crate::cargo_registry_to_crev_source_id(&pkg_id.source_id());
 
     let diff_base_version = crate_review_activity_check(
         &local,
+        &source,
         &pkg_id.name(),
         &effective_crate_version,
         &diff_version,
         skip_activity_check,
     )?;
 
-    let (digest_clean, vcs) =
-        check_package_clean_state(&repo, &crate_root, &crate_.name(), &effective_crate_version)?;
+    let (digest_clean, vcs) = check_package_clean_state(&repo, pkg_id, &crate_root)?;
 
     let diff_base = if let Some(ref diff_base_version) = diff_base_version {
-        let crate_id = repo.find_pkgid(&crate_.name(), Some(diff_base_version), true)?;
+        let crate_id = repo
+            .find_pkgid_in_source(&crate_.name(), Some(diff_base_version), pkg_id.source_id())?
+            .ok_or_else(|| {
+                format_err!("Could not find {} v{}", crate_.name(), diff_base_version)
+            })?;
         let crate_ = repo.get_crate(&crate_id)?;
         let crate_root = crate_.root();
 
-        let (digest, vcs) =
-            check_package_clean_state(&repo, &crate_root, &crate_.name(), &diff_base_version)?;
+        let (digest, vcs) = check_package_clean_state(&repo, crate_id, &crate_root)?;
 
         Some(proof::PackageInfo {
             id: proof::PackageVersionId::new(
-                PROJECT_SOURCE_CRATES_IO.to_owned(),
+                source.clone(),
                 crate_.name().to_string(),
                 diff_base_version.to_owned(),
             ),
             digest: digest.into_vec(),
             digest_type: proof::default_digest_type(),
-            revision: vcs_info_to_revision_string(vcs),
+            revision: pkg_revision_string(crate_id, vcs),
             revision_type: proof::default_revision_type(),
         })
     } else {
         None
     };
 
-    let id = local.read_current_unlocked_id(&crev_common::read_passphrase)?;
+    let signer = local.read_current_signer(&crev_common::read_passphrase)?;
+    let id = signer.public_id();
 
     let db = local.load_db()?;
     let mut review = proof::review::PackageBuilder::default()
-        .from(id.id.to_owned())
+        .from(id.to_owned())
         .package(proof::PackageInfo {
             id: proof::PackageVersionId::new(
-                PROJECT_SOURCE_CRATES_IO.to_owned(),
+                source.clone(),
                 crate_.name().to_string(),
                 effective_crate_version.to_owned(),
             ),
             digest: digest_clean.into_vec(),
             digest_type: proof::default_digest_type(),
-            revision: vcs_info_to_revision_string(vcs),
+            revision: pkg_revision_string(pkg_id, vcs),
             revision_type: proof::default_revision_type(),
         })
         .review(if advise_common.is_some() || report_severity.is_some() {
@@ -96,26 +104,32 @@ pub fn create_review_proof(
         .build()
         .map_err(|e| format_err!("{}", e))?;
 
-    let previous_date =
-        if let Some((prev_date, prev_review, prev_advisories, prev_issues, prev_comment)) =
-            find_previous_review_data(
-                &db,
-                &id.id,
-                &crate_.name(),
-                effective_crate_version,
-                &diff_base_version,
-            )
-        {
-            if trust != TrustProofType::Untrust {
-                review.review = prev_review;
-            }
-            review.comment = prev_comment;
-            review.advisories = prev_advisories;
-            review.issues = prev_issues;
-            prev_date
-        } else {
-            None
-        };
+    let previous_date = if let Some((
+        prev_date,
+        prev_review,
+        prev_advisories,
+        prev_issues,
+        prev_notes,
+        prev_comment,
+    )) = find_previous_review_data(
+        &db,
+        id,
+        &source,
+        &crate_.name(),
+        effective_crate_version,
+        &diff_base_version,
+    ) {
+        if trust != TrustProofType::Untrust {
+            review.review = prev_review;
+        }
+        review.comment = prev_comment;
+        review.advisories = prev_advisories;
+        review.issues = prev_issues;
+        review.notes = prev_notes;
+        prev_date
+    } else {
+        None
+    };
 
     if let Some(advise_common) = advise_common {
         let mut advisory: proof::review::package::Advisory = advise_common.affected.into();
@@ -130,19 +144,20 @@ pub fn create_review_proof(
     }
 
     review.flags = db
-        .get_pkg_flags_by_author(&id.id.id, &review.package.id.id)
+        .get_pkg_flags_by_author(&id.id, &review.package.id.id)
         .cloned()
         .unwrap_or_default();
 
-    review.alternatives = db.get_pkg_alternatives_by_author(&id.id.id, &review.package.id.id);
+    review.alternatives = db.get_pkg_alternatives_by_author(&id.id, &review.package.id.id);
 
-    let review = edit::edit_proof_content_iteractively(
+    let mut review = edit::edit_proof_content_iteractively(
         &review,
         previous_date.as_ref(),
         diff_base_version.as_ref(),
     )?;
+    fill_note_digests(&crate_root, &mut review.notes)?;
 
-    let proof = review.sign_by(&id)?;
+    let proof = review.sign_by(signer.as_ref())?;
 
     let commit_msg = format!(
         "Add review for {crate} v{version}",
@@ -152,9 +167,134 @@ pub fn create_review_proof(
     maybe_store(&local, &proof, &commit_msg, proof_create_opt)
 }
 
+/// Review individual files of a crate
+///
+/// * `files` - paths relative to the crate root
+pub fn create_code_review_proof(
+    crate_sel: &CrateSelector,
+    files: &[PathBuf],
+    trust: TrustProofType,
+    proof_create_opt: &opts::CommonProofCreate,
+    skip_activity_check: bool,
+    cargo_opts: CargoOpts,
+) -> Result<()> {
+    let repo = Repo::auto_open_cwd(cargo_opts)?;
+
+    let pkg_id = repo.find_pkgid_by_crate_selector(crate_sel)?;
+    let crate_ = repo.get_crate(&pkg_id)?;
+    let crate_root = crate_.root();
+    let effective_crate_version = crate_.version();
+
+    let local = Local::auto_open()?;
+    let source = crate::cargo_registry_to_crev_source_id(&pkg_id.source_id());
+
+    crate_review_activity_check(
+        &local,
+        &source,
+        &pkg_id.name(),
+        &effective_crate_version,
+        &None,
+        skip_activity_check,
+    )?;
+
+    let (digest_clean, vcs) = check_package_clean_state(&repo, pkg_id, &crate_root)?;
+
+    // digests of the clean copy, for files the package digest is calculated from
+    let ignore_list = cargo_full_ignore_list(false);
+    let files = files
+        .iter()
+        .map(|path| {
+            let path = path.strip_prefix(crate_root).unwrap_or(path);
+            let full_path = crate_root.join(path);
+            if !proof::review::is_path_inside_package(path)
+                || path
+                    .ancestors()
+                    .any(|ancestor| ignore_list.contains(ancestor))
+                || !full_path.is_file()
+            {
+                bail!(
+                    "{} is not a file of {} v{}",
+                    path.display(),
+                    crate_.name(),
+                    effective_crate_version
+                );
+            }
+            Ok(proof::review::File {
+                path: path.to_owned(),
+                digest: crev_common::blake2b256sum_file(&full_path)?,
+                digest_type: proof::default_digest_type(),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let signer = local.read_current_signer(&crev_common::read_passphrase)?;
+    let id = signer.public_id();
+
+    let review = id.create_code_review_proof(
+        proof::PackageInfo {
+            id: proof::PackageVersionId::new(
+                source,
+                crate_.name().to_string(),
+                effective_crate_version.to_owned(),
+            ),
+            digest: digest_clean.into_vec(),
+            digest_type: proof::default_digest_type(),
+            revision: pkg_revision_string(pkg_id, vcs),
+            revision_type: proof::default_revision_type(),
+        },
+        files,
+        trust.to_review(),
+        "".into(),
+    )?;
+
+    let review = edit::edit_proof_content_iteractively(&review, None, None)?;
+
+    let proof = review.sign_by(signer.as_ref())?;
+
+    let commit_msg = format!(
+        "Add code review for {count} files of {crate} v{version}",
+        count = review.files.len(),
+        crate = &crate_.name(),
+        version = effective_crate_version
+    );
+    maybe_store(&local, &proof, &commit_msg, proof_create_opt)
+}
+
+/// Record digests of the crate files `notes` are about
+///
+/// Notes about lines that don't exist are kept, but without a digest,
+/// so the review being edited is not lost.
+fn fill_note_digests(crate_root: &Path, notes: &mut [proof::review::Note]) -> Result<()> {
+    for note in notes {
+        let path = crate_root.join(&note.path);
+        if !proof::review::is_path_inside_package(&note.path) || !path.is_file() {
+            eprintln!(
+                "Warning: note about {}: not a file of the crate",
+                note.path.display()
+            );
+            continue;
+        }
+        let line_count = String::from_utf8_lossy(&std::fs::read(&path)?)
+            .lines()
+            .count() as u64;
+        if line_count < note.lines.end {
+            eprintln!(
+                "Warning: note about {}:{}: the file has only {} lines",
+                note.path.display(),
+                note.lines,
+                line_count
+            );
+            continue;
+        }
+        note.digest = crev_common::blake2b256sum_file(&path)?;
+    }
+    Ok(())
+}
+
 pub fn find_previous_review_data(
     db: &crev_wot::ProofDB,
     id: &crev_data::PublicId,
+    source: &str,
     name: &str,
     crate_version: &Version,
     diff_base_version: &Option<Version>,
@@ -163,27 +303,26 @@ pub fn find_previous_review_data(
     crev_data::proof::review::Review,
     Vec<crev_data::proof::review::package::Advisory>,
     Vec<crev_data::proof::review::package::Issue>,
+    Vec<crev_data::proof::review::Note>,
     String,
 )> {
-    if let Some(previous_review) =
-        db.get_pkg_review(PROJECT_SOURCE_CRATES_IO, name, crate_version, &id.id)
-    {
+    if let Some(previous_review) = db.get_pkg_review(source, name, crate_version, &id.id) {
         return Some((
             Some(previous_review.common.date),
             previous_review.review.to_owned(),
             previous_review.advisories.to_owned(),
             previous_review.issues.to_owned(),
+            previous_review.notes.to_owned(),
             previous_review.comment.to_owned(),
         ));
     } else if let Some(diff_base_version) = diff_base_version {
-        if let Some(base_review) =
-            db.get_pkg_review(PROJECT_SOURCE_CRATES_IO, name, &diff_base_version, &id.id)
-        {
+        if let Some(base_review) = db.get_pkg_review(source, name, &diff_base_version, &id.id) {
             return Some((
                 None,
                 base_review.review.to_owned(),
                 vec![],
                 vec![],
+                vec![],
                 base_review.comment.to_owned(),
             ));
         }
@@ -2,6 +2,8 @@
 //
 use crate::{deps::scan, edit, opts, opts::CrateSelector, prelude::*, repo::*};
 use anyhow::{format_err, Context, Result};
+use cargo::core::PackageId;
+use crev_common::convert::OptionDeref;
 use crev_data::{
     proof::{self, ContentExt},
     Id,
@@ -47,6 +49,18 @@ pub fn vcs_info_to_revision_string(vcs: Option<VcsInfoJson>) -> String {
         .unwrap_or_else(|| "".into())
 }
 
+/// Revision to record in the review of a package
+///
+/// The commit a git dependency is locked to, or the one from
+/// `.cargo_vcs_info.json` of a crate from a registry.
+pub fn pkg_revision_string(pkg_id: PackageId, vcs: Option<VcsInfoJson>) -> String {
+    let source_id = pkg_id.source_id();
+    match source_id.precise() {
+        Some(commit) if source_id.is_git() => commit.to_owned(),
+        _ => vcs_info_to_revision_string(vcs),
+    }
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub enum VcsInfoJsonGit {
     #[serde(rename = "sha1")]
@@ -73,8 +87,7 @@ impl VcsInfoJson {
 
 /// Ignore things that are commonly added during the review (eg. by RLS)
 pub fn cargo_full_ignore_list(ignore_cargo_lock: bool) -> fnv::FnvHashSet<PathBuf> {
-    let mut ignore_list = HashSet::default();
-    ignore_list.insert(PathBuf::from(".cargo-ok"));
+    let mut ignore_list = cargo_min_ignore_list();
     ignore_list.insert(PathBuf::from("target"));
     if ignore_cargo_lock {
         ignore_list.insert(PathBuf::from("Cargo.lock"));
@@ -84,9 +97,12 @@ pub fn cargo_full_ignore_list(ignore_cargo_lock: bool) -> fnv::FnvHashSet<PathBu
 }
 
 /// Ignore only the marker added by `cargo` after fully downloading and extracting crate
+///
+/// and the `.git` dir, present in the checkouts of git dependencies
 pub fn cargo_min_ignore_list() -> fnv::FnvHashSet<PathBuf> {
     let mut ignore_list = HashSet::default();
     ignore_list.insert(PathBuf::from(".cargo-ok"));
+    ignore_list.insert(PathBuf::from(".git"));
     ignore_list
 }
 
@@ -125,7 +141,7 @@ pub fn goto_crate_src(selector: &opts::CrateSelector) -> Result<()> {
     let crate_version = crate_.version();
     let local = crev_lib::Local::auto_create_or_open()?;
     local.record_review_activity(
-        PROJECT_SOURCE_CRATES_IO,
+        &crate::cargo_registry_to_crev_source_id(&crate_id.source_id()),
         &crate_.name().to_string(),
         crate_version,
         &crev_lib::ReviewActivity::new_full(),
@@ -187,7 +203,7 @@ pub fn clean_all_unclean_crates() -> Result<()> {
     let events = scanner.run();
 
     for stats in events.into_iter() {
-        if stats.details.accumulative_own.is_local_source_code {
+        if !stats.info.id.source_id().is_registry() {
             continue;
         }
         if stats.is_digest_unclean() {
@@ -206,8 +222,10 @@ pub fn clean_all_unclean_crates() -> Result<()> {
 pub fn clean_crate(selector: &CrateSelector) -> Result<()> {
     let repo = Repo::auto_open_cwd_default()?;
     let crate_id = repo.find_pkgid_by_crate_selector(selector)?;
+    if !crate_id.source_id().is_registry() {
+        bail!("Only crates from registries can be cleaned");
+    }
     let crate_ = repo.get_crate(&crate_id)?;
-    assert!(crate_.package_id().source_id().is_registry());
     let crate_root = crate_.root();
 
     assert!(crate_root.is_absolute());
@@ -264,7 +282,7 @@ pub fn crate_open(crate_sel: &CrateSelector, cmd: Option<String>, cmd_save: bool
         get_open_cmd(&local)?
     };
     local.record_review_activity(
-        PROJECT_SOURCE_CRATES_IO,
+        &crate::cargo_registry_to_crev_source_id(&crate_id.source_id()),
         &crate_.name().to_string(),
         &crate_.version(),
         &crev_lib::ReviewActivity::new_full(),
@@ -306,12 +324,13 @@ impl UnrelatedOrDependency {
 /// Return `Option<Version>` indicating final ReviewMode settings to use.
 pub fn crate_review_activity_check(
     local: &Local,
+    source: &str,
     name: &str,
     version: &Version,
     diff: &Option<Option<Version>>,
     skip_activity_check: bool,
 ) -> Result<Option<Version>> {
-    let activity = local.read_review_activity(PROJECT_SOURCE_CRATES_IO, name, version)?;
+    let activity = local.read_review_activity(source, name, version)?;
 
     let diff = match diff {
         None => None,
@@ -384,12 +403,64 @@ pub fn crate_review_activity_check(
     Ok(diff)
 }
 
+/// Check that the checkout of a git dependency wasn't modified
+///
+/// Unlike crates from registries, these can't be just downloaded again to compare.
+fn check_git_checkout_clean(crate_root: &Path) -> Result<()> {
+    let git_repo = git2::Repository::discover(crate_root)?;
+    let workdir = git_repo
+        .workdir()
+        .ok_or_else(|| format_err!("{} is not a git checkout", crate_root.display()))?
+        .canonicalize()?;
+    let crate_rel_path = crate_root
+        .canonicalize()?
+        .strip_prefix(&workdir)?
+        .to_owned();
+
+    let mut status_opts = git2::StatusOptions::new();
+    status_opts.include_untracked(true);
+    if !crate_rel_path.as_os_str().is_empty() {
+        status_opts.pathspec(&crate_rel_path);
+    }
+    let ignore_list = cargo_full_ignore_list(false);
+    let modified: Vec<_> = git_repo
+        .statuses(Some(&mut status_opts))?
+        .iter()
+        .filter_map(|entry| entry.path().map(PathBuf::from))
+        .filter(|path| {
+            let path = path.strip_prefix(&crate_rel_path).unwrap_or(path);
+            !path
+                .components()
+                .next()
+                .map_or(false, |first| ignore_list.contains(Path::new(&first)))
+        })
+        .collect();
+    if !modified.is_empty() {
+        bail!(
+            "The checkout in {} was modified: {}. Restore it with `git checkout` and review again.",
+            workdir.display(),
+            modified
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+    Ok(())
+}
+
 pub fn check_package_clean_state(
     repo: &Repo,
+    pkg_id: PackageId,
     crate_root: &Path,
-    name: &str,
-    version: &Version,
 ) -> Result<(crev_data::Digest, Option<VcsInfoJson>)> {
+    if pkg_id.source_id().is_git() {
+        check_git_checkout_clean(crate_root)?;
+        let digest =
+            crev_lib::get_recursive_digest_for_dir(&crate_root, &cargo_full_ignore_list(false))?;
+        return Ok((digest, None));
+    }
+
     // to protect from creating a digest from a crate in unclean state
     // we move the old directory, download a fresh one and double
     // check if the digest was the same
@@ -405,13 +476,10 @@ pub fn check_package_clean_state(
     // shells, we move all the entries in a dir, instead of the whole
     // dir. this is not a perfect solution, but better than nothing.
     crev_common::fs::move_dir_content(&crate_root, &reviewed_pkg_dir)?;
-    let crate_second_id = repo.find_pkgid(name, Some(version), true)?;
-    let crate_second = repo.get_crate(&crate_second_id)?;
+    let crate_second = repo.get_crate(&pkg_id)?;
     let crate_root_second = crate_second.root();
-    let crate_version_second = crate_second.version();
 
     assert_eq!(crate_root, crate_root_second);
-    assert_eq!(version, crate_version_second);
 
     let digest_clean =
         crev_lib::get_recursive_digest_for_dir(&crate_root, &cargo_min_ignore_list())?;
@@ -463,6 +531,7 @@ pub fn run_diff(args: &opts::Diff) -> Result<std::process::ExitStatus> {
     let dst_version = &args.dst;
     let dst_crate_id = repo.find_pkgid(name, dst_version.to_owned().as_ref(), false)?;
     let dst_crate = repo.get_crate(&dst_crate_id)?;
+    let source = crate::cargo_registry_to_crev_source_id(&dst_crate_id.source_id());
 
     let requirements = crev_lib::VerificationRequirements::from(args.requirements.clone());
     let trust_distance_params = &args.trust_params.clone().into();
@@ -475,20 +544,16 @@ pub fn run_diff(args: &opts::Diff) -> Result<std::process::ExitStatus> {
         .src
         .clone()
         .or_else(|| {
-            crev_lib::find_latest_trusted_version(
-                &trust_set,
-                PROJECT_SOURCE_CRATES_IO,
-                &name,
-                &requirements,
-                &db,
-            )
+            crev_lib::find_latest_trusted_version(&trust_set, &source, &name, &requirements, &db)
         })
         .ok_or_else(|| format_err!("No previously reviewed version found"))?;
-    let src_crate_id = repo.find_pkgid(name, Some(&src_version), true)?;
+    let src_crate_id = repo
+        .find_pkgid_in_source(name, Some(&src_version), dst_crate_id.source_id())?
+        .ok_or_else(|| format_err!("Could not find {} v{}", name, src_version))?;
     let src_crate = repo.get_crate(&src_crate_id)?;
 
     local.record_review_activity(
-        PROJECT_SOURCE_CRATES_IO,
+        &source,
         &name,
         &dst_crate.version(),
         &crev_lib::ReviewActivity::new_diff(&src_version),
@@ -570,6 +635,84 @@ pub fn list_issues(args: &opts::RepoQueryIssue) -> Result<()> {
     Ok(())
 }
 
+/// Export advisories and issues of trusted reviewers
+pub fn export_proofs(args: &opts::RepoExport) -> Result<()> {
+    let local = crev_lib::Local::auto_open()?;
+    let db = local.load_db()?;
+    let for_id = local.get_for_id_from_str(OptionDeref::as_deref(&args.for_id))?;
+    let trust_set = db.calculate_trust_set(&for_id, &args.trust_params.clone().into());
+    let now = crev_common::now().with_timezone(&chrono::Utc);
+
+    let records = match args.format {
+        opts::ExportFormat::Osv => {
+            let reviews: Vec<_> = db
+                .get_pkg_reviews_with_issues_for(
+                    PROJECT_SOURCE_CRATES_IO,
+                    None,
+                    None,
+                    &trust_set,
+                    args.trust_level.trust_level.into(),
+                )
+                // advisory databases publish their advisories themselves
+                .filter(|review| !db.is_unsigned_author(&review.from().id))
+                .filter(|review| {
+                    !db.is_proof_revoked(&review.from().id, review.date_utc())
+                        && !review.is_expired_at(now)
+                })
+                .collect();
+            let mut records = vec![];
+            for review in &reviews {
+                let package = &review.package.id;
+                let skipped = |kind: &str, ids: &[String]| {
+                    eprintln!(
+                        "Warning: {} {} of {} {} by {}: no affected versions that can be exported, skipped",
+                        kind,
+                        ids.join(", "),
+                        package.id.name,
+                        package.version,
+                        review.from().id
+                    )
+                };
+                for advisory in &review.advisories {
+                    match crev_lib::osv::advisory_to_osv(review, advisory) {
+                        Some(record) => records.push(record),
+                        None => skipped("advisory", &advisory.ids),
+                    }
+                }
+                for issue in &review.issues {
+                    let same_package = reviews
+                        .iter()
+                        .filter(|other| other.package.id.id == package.id)
+                        .copied();
+                    match crev_lib::osv::issue_to_osv(review, issue, same_package) {
+                        Some(record) => records.push(record),
+                        None => skipped("issue", std::slice::from_ref(&issue.id)),
+                    }
+                }
+            }
+            records
+        }
+    };
+
+    if let Some(ref output_dir) = args.output_dir {
+        for record in &records {
+            let path = output_dir.join(format!("{}.json", record.id));
+            crev_common::store_to_file_with(&path, |file| {
+                serde_json::to_writer_pretty(file, record)
+            })??;
+        }
+        eprintln!(
+            "Exported {} records to {}",
+            records.len(),
+            output_dir.display()
+        );
+    } else {
+        println!("{}", serde_json::to_string_pretty(&records)?);
+    }
+
+    Ok(())
+}
+
 /// Are we executing from a shell started by `cargo crev goto`?
 ///
 /// If yes - return the path the original directory where the
@@ -619,21 +762,17 @@ pub fn create_trust_proof(
 ) -> Result<()> {
     let local = Local::auto_open()?;
 
-    let unlocked_id = local.read_current_unlocked_id(&crev_common::read_passphrase)?;
+    let signer = local.read_current_signer(&crev_common::read_passphrase)?;
 
     let string_ids = ids
         .iter()
         .map(|id| id.to_string())
         .collect::<Vec<_>>()
         .join(", ");
-    let trust = edit::build_trust_proof_interactively(
-        &local,
-        unlocked_id.as_public_id(),
-        ids,
-        trust_or_distrust,
-    )?;
+    let trust =
+        edit::build_trust_proof_interactively(&local, signer.public_id(), ids, trust_or_distrust)?;
 
-    let proof = trust.sign_by(&unlocked_id)?;
+    let proof = trust.sign_by(signer.as_ref())?;
     let commit_msg = format!(
         "Add {t_or_d} for {ids}",
         t_or_d = trust_or_distrust,
@@ -645,6 +784,140 @@ pub fn create_trust_proof(
     Ok(())
 }
 
+/// Creates a team proof, declaring the current Id a team of `members`
+pub fn create_team_proof(members: Vec<Id>, args: &opts::IdTeam) -> Result<()> {
+    let local = Local::auto_open()?;
+
+    let signer = local.read_current_signer(&crev_common::read_passphrase)?;
+    let team = local.build_team_proof(
+        signer.public_id(),
+        members,
+        args.threshold,
+        args.comment.clone(),
+    )?;
+    let proof = team.sign_by(signer.as_ref())?;
+
+    maybe_store(
+        &local,
+        &proof,
+        &format!(
+            "Add team of {} with threshold {}",
+            team.members.len(),
+            team.threshold
+        ),
+        &args.common_proof_create,
+    )?;
+
+    Ok(())
+}
+
+fn parse_revocation_date(s: &str) -> Result<proof::Date> {
+    use chrono::TimeZone as _;
+
+    if let Ok(date) = chrono::DateTime::parse_from_rfc3339(s) {
+        return Ok(date);
+    }
+    let date = chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .with_context(|| format!("Invalid date: {}", s))?;
+    let offset = *crev_common::now().offset();
+    offset
+        .from_local_datetime(&date.and_hms(0, 0, 0))
+        .single()
+        .ok_or_else(|| format_err!("Invalid date: {}", s))
+}
+
+/// Parse an Id given on the command line
+///
+/// OpenPGP Ids are given by their fingerprints, and have to be found among
+/// the Ids known from the proofs in `db`.
+pub fn id_from_string(db: &crev_wot::ProofDB, id_str: &str) -> Result<Id> {
+    match Id::crevid_from_str(id_str) {
+        Ok(id) => Ok(id),
+        Err(e) => match db.find_id(id_str) {
+            Some(id) => Ok(id),
+            None => bail!("'{}' is not a valid crev Id: {}", id_str, e),
+        },
+    }
+}
+
+pub fn revoke_id(args: &opts::IdRevoke) -> Result<()> {
+    let local = Local::auto_open()?;
+    let id = match &args.id {
+        Some(id) => local.id_from_str(id)?,
+        None => local.get_current_userid()?,
+    };
+    let revoked_since = args
+        .since
+        .as_deref()
+        .map(parse_revocation_date)
+        .transpose()?;
+
+    let msg = match revoked_since {
+        Some(since) => format!(
+            "Revoke all proofs of {} created since {}? This can't be undone. (y/N)",
+            id, since
+        ),
+        None => format!(
+            "Revoke {} and all its proofs? This can't be undone. (y/N)",
+            id
+        ),
+    };
+    if crev_common::yes_or_no_was_y(&msg)? != Some(true) {
+        bail!("Revocation cancelled");
+    }
+
+    let signer = local.read_signer(&id, &crev_common::read_passphrase)?;
+    let revocation = signer
+        .public_id()
+        .create_revocation_proof(revoked_since, args.comment.clone())?;
+    let proof = revocation.sign_by(signer.as_ref())?;
+
+    maybe_store(
+        &local,
+        &proof,
+        &format!("Add revocation of {}", id),
+        &args.common_proof_create,
+    )?;
+
+    Ok(())
+}
+
+pub fn rotate_id(args: &opts::IdRotate) -> Result<()> {
+    let local = Local::auto_open()?;
+    let old_id = local.get_current_userid()?;
+
+    fn read_new_passphrase() -> io::Result<String> {
+        println!("New CrevID will be protected by a passphrase.");
+        println!("There's no way to recover your CrevID if you forget your passphrase.");
+        crev_common::read_new_passphrase()
+    }
+    let (locked_id, proof) = local.rotate_id(
+        &crev_common::read_passphrase,
+        read_new_passphrase,
+        args.comment.clone(),
+    )?;
+    println!("Your new CrevID was created and will be printed below in an encrypted form.");
+    println!("Make sure to back it up on another device, to prevent losing it.");
+    println!("{}", locked_id);
+
+    maybe_store(
+        &local,
+        &proof,
+        &format!(
+            "Add key link from {} to {}",
+            old_id,
+            locked_id.to_public_id().id
+        ),
+        &args.common_proof_create,
+    )?;
+    eprintln!(
+        "Consider revoking the old Id with `cargo crev id revoke {}`",
+        old_id
+    );
+
+    Ok(())
+}
+
 pub fn is_file_with_ext(entry: &walkdir::DirEntry, file_ext: &str) -> bool {
     if !entry.file_type().is_file() {
         return false;
@@ -687,6 +960,26 @@ pub fn get_geiger_count(path: &Path) -> Result<u64> {
     Ok(count)
 }
 
+/// Rust files with any `unsafe` code, by path relative to `path`
+// Note: this function is very slow
+pub fn get_files_with_unsafe(path: &Path) -> Result<HashSet<PathBuf>> {
+    let root = path.canonicalize()?;
+    let mut files = HashSet::new();
+    for file in iter_rs_files_in_dir(&root) {
+        let file = file?;
+        if geiger::find_unsafe_in_file(&file, geiger::IncludeTests::No)?
+            .counters
+            .has_unsafe()
+        {
+            if let Ok(rel_path) = file.strip_prefix(&root) {
+                files.insert(rel_path.to_owned());
+            }
+        }
+    }
+
+    Ok(files)
+}
+
 /// Result of `run_command`
 ///
 /// This is to distinguish expected non-success results,
@@ -700,12 +993,13 @@ pub enum CommandExitStatus {
 
 pub fn is_digest_clean(
     db: &crev_wot::ProofDB,
+    source: &str,
     name: &str,
     version: &Version,
     digest: &crev_data::Digest,
 ) -> bool {
     let mut at_least_one = false;
-    !db.get_package_reviews_for_package(PROJECT_SOURCE_CRATES_IO, Some(name), Some(version))
+    !db.get_package_reviews_for_package(source, Some(name), Some(version))
         .map(|review| {
             at_least_one = true;
             review
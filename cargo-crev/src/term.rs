@@ -14,6 +14,7 @@ pub fn verification_status_color(s: VerificationStatus) -> Option<color::Color>
     match s {
         Verified | Local => Some(term::color::GREEN),
         Insufficient => None,
+        Exempted => Some(term::color::CYAN),
         Negative => Some(term::color::YELLOW),
     }
 }
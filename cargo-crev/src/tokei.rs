@@ -1,8 +1,11 @@
 use crate::prelude::*;
-use std::path::Path;
-use tokei::{Config, LanguageType, Languages};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+use tokei::{Config, Language, LanguageType, Languages};
 
-pub fn get_rust_line_count(path: &Path) -> Result<usize> {
+fn get_rust_stats(path: &Path) -> Result<Language> {
     let excluded = &["tests/", "examples/"];
     let mut config = Config::default();
     config.treat_doc_strings_as_comments = Some(true);
@@ -10,8 +13,23 @@ pub fn get_rust_line_count(path: &Path) -> Result<usize> {
     config.hidden = Some(true);
     let mut languages = Languages::new();
     languages.get_statistics(&[path], excluded, &config);
-    let rust = languages
-        .get(&LanguageType::Rust)
-        .ok_or_else(|| format_err!("Rust should work"))?;
-    Ok(rust.code)
+    languages
+        .remove(&LanguageType::Rust)
+        .ok_or_else(|| format_err!("Rust should work"))
+}
+
+pub fn get_rust_line_count(path: &Path) -> Result<usize> {
+    Ok(get_rust_stats(path)?.code)
+}
+
+/// Lines of Rust code of every file, by path relative to `path`
+pub fn get_rust_line_counts_by_file(path: &Path) -> Result<HashMap<PathBuf, u64>> {
+    Ok(get_rust_stats(path)?
+        .stats
+        .into_iter()
+        .map(|stats| {
+            let rel_path = stats.name.strip_prefix(path).unwrap_or(&stats.name);
+            (rel_path.to_owned(), stats.code as u64)
+        })
+        .collect())
 }
@@ -113,6 +113,7 @@ impl<'t> VerifyScreen<'t> {
                 Box::new(|dep: &CrateStats| match dep.details.accumulative.trust {
                     VerificationStatus::Local => ListViewCell::new("locl".to_owned(), &TS.good),
                     VerificationStatus::Verified => ListViewCell::new("pass".to_owned(), &TS.good),
+                    VerificationStatus::Exempted => ListViewCell::new("exmt".to_owned(), &TS.std),
                     VerificationStatus::Insufficient => {
                         ListViewCell::new("none".to_owned(), &TS.none)
                     }
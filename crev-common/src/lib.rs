@@ -291,6 +291,12 @@ pub fn read_passphrase() -> io::Result<String> {
     rpassword::read_password()
 }
 
+/// Read a passphrase of something other than a CrevID, e.g. a key being imported
+pub fn read_passphrase_with_prompt(prompt: &str) -> io::Result<String> {
+    eprint!("{}", prompt);
+    rpassword::read_password()
+}
+
 pub fn read_new_passphrase() -> io::Result<String> {
     if let Ok(pass) = env::var("CREV_PASSPHRASE") {
         eprint!("Using passphrase set in CREV_PASSPHRASE\n");
@@ -37,6 +37,33 @@ where
     serializer.serialize_str(&crate::base64_encode(key.as_ref()))
 }
 
+pub fn from_base64_opt<'d, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: serde::Deserializer<'d>,
+    T: MyTryFromBytes,
+{
+    use self::serde::de::Error;
+    Option::<String>::deserialize(deserializer)?
+        .map(|string| {
+            let bytes =
+                crate::base64_decode(&string).map_err(|err| Error::custom(err.to_string()))?;
+            T::try_from(&bytes)
+                .map_err(|err| Error::custom(format!("{}", &err as &dyn ::std::error::Error)))
+        })
+        .transpose()
+}
+
+pub fn as_base64_opt<T, S>(key: &Option<T>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: AsRef<[u8]>,
+    S: serde::Serializer,
+{
+    match key {
+        Some(key) => serializer.serialize_some(&crate::base64_encode(key.as_ref())),
+        None => serializer.serialize_none(),
+    }
+}
+
 pub fn from_hex<'d, T, D>(deserializer: D) -> Result<T, D::Error>
 where
     D: serde::Deserializer<'d>,
@@ -85,6 +112,34 @@ where
     serializer.serialize_str(&key.to_rfc3339())
 }
 
+pub fn from_rfc3339_fixed_opt<'d, D>(
+    deserializer: D,
+) -> Result<Option<chrono::DateTime<FixedOffset>>, D::Error>
+where
+    D: serde::Deserializer<'d>,
+{
+    use self::serde::de::Error;
+    Option::<String>::deserialize(deserializer)?
+        .map(|string| {
+            DateTime::<FixedOffset>::parse_from_rfc3339(&string)
+                .map_err(|err| Error::custom(err.to_string()))
+        })
+        .transpose()
+}
+
+pub fn as_rfc3339_fixed_opt<S>(
+    key: &Option<chrono::DateTime<FixedOffset>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    match key {
+        Some(key) => serializer.serialize_some(&key.to_rfc3339()),
+        None => serializer.serialize_none(),
+    }
+}
+
 impl MyTryFromBytes for Vec<u8> {
     type Err = io::Error;
     fn try_from(slice: &[u8]) -> Result<Self, Self::Err> {
@@ -1,4 +1,9 @@
-use crate::{proof, proof::ContentExt, Url};
+use crate::{
+    openpgp, proof,
+    proof::ContentExt,
+    signer::{Signer, SignerError},
+    ssh, Url,
+};
 use crev_common::{
     self,
     serde::{as_base64, from_base64},
@@ -13,6 +18,10 @@ use std::fmt;
 pub enum IdType {
     #[serde(rename = "crev")]
     Crev,
+    #[serde(rename = "openpgp")]
+    OpenPgp,
+    #[serde(rename = "ssh")]
+    Ssh,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -27,6 +36,10 @@ pub enum IdError {
     InvalidPublicKey(Box<str>),
     #[error("Invalid secret key: {}", _0)]
     InvalidSecretKey(Box<str>),
+    #[error(transparent)]
+    OpenPgp(#[from] openpgp::OpenPgpError),
+    #[error(transparent)]
+    Ssh(#[from] ssh::SshError),
 }
 
 impl fmt::Display for IdType {
@@ -34,14 +47,15 @@ impl fmt::Display for IdType {
         use IdType::*;
         f.write_str(match self {
             Crev => "crev",
+            OpenPgp => "openpgp",
+            Ssh => "ssh",
         })
     }
 }
 
 /// An Id supported by `crev` system
 ///
-/// Right now it's only native CrevID, but in future at least GPG
-/// should be supported.
+/// Either a native CrevID, or an (Ed25519) OpenPGP or SSH key.
 #[derive(Clone, Serialize, Deserialize, Hash, PartialEq, Eq, PartialOrd, Ord)]
 #[serde(tag = "id-type")]
 pub enum Id {
@@ -50,13 +64,25 @@ pub enum Id {
         #[serde(serialize_with = "as_base64", deserialize_with = "from_base64")]
         id: Vec<u8>,
     },
+    /// Displayed as the key's fingerprint
+    #[serde(rename = "openpgp")]
+    OpenPgp {
+        /// Body of the public key packet
+        #[serde(serialize_with = "as_base64", deserialize_with = "from_base64")]
+        key: Vec<u8>,
+    },
+    /// Displayed as `ssh:` followed by the key, encoded like CrevIDs
+    #[serde(rename = "ssh")]
+    Ssh {
+        /// Ed25519 public key
+        #[serde(serialize_with = "as_base64", deserialize_with = "from_base64")]
+        key: Vec<u8>,
+    },
 }
 
 impl fmt::Debug for Id {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Id::Crev { id } => f.write_str(&crev_common::base64_encode(id)),
-        }
+        fmt::Display::fmt(self, f)
     }
 }
 
@@ -64,10 +90,20 @@ impl fmt::Display for Id {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Id::Crev { id } => f.write_str(&crev_common::base64_encode(id)),
+            Id::OpenPgp { key } => {
+                for b in &openpgp::fingerprint(key) {
+                    write!(f, "{:02X}", b)?;
+                }
+                Ok(())
+            }
+            Id::Ssh { key } => write!(f, "{}{}", SSH_ID_PREFIX, crev_common::base64_encode(key)),
         }
     }
 }
 
+/// Not part of the URL-safe base64 alphabet, so no CrevID starts with it
+const SSH_ID_PREFIX: &str = "ssh:";
+
 impl Id {
     pub fn new_crev(bytes: Vec<u8>) -> Result<Self, IdError> {
         if bytes.len() != 32 {
@@ -76,7 +112,48 @@ impl Id {
         Ok(Id::Crev { id: bytes })
     }
 
+    /// Id of an OpenPGP key, given the body of its public key packet
+    pub fn new_openpgp(key: Vec<u8>) -> Result<Self, IdError> {
+        openpgp::public_key(&key)?;
+        Ok(Id::OpenPgp { key })
+    }
+
+    /// Id of an SSH key, given the Ed25519 public key
+    pub fn new_ssh(key: Vec<u8>) -> Result<Self, IdError> {
+        ed25519_dalek::PublicKey::from_bytes(&key)
+            .map_err(|e| IdError::InvalidPublicKey(e.to_string().into()))?;
+        Ok(Id::Ssh { key })
+    }
+
+    pub fn id_type(&self) -> IdType {
+        match self {
+            Id::Crev { .. } => IdType::Crev,
+            Id::OpenPgp { .. } => IdType::OpenPgp,
+            Id::Ssh { .. } => IdType::Ssh,
+        }
+    }
+
+    /// String form of the Id, usable as a file name
+    ///
+    /// Same as `to_string`, except for SSH Ids, as `:` is not allowed in file
+    /// names on Windows.
+    pub fn to_file_name(&self) -> String {
+        match self {
+            Id::Ssh { key } => format!("ssh-{}", crev_common::base64_encode(key)),
+            _ => self.to_string(),
+        }
+    }
+
+    /// Parse an Id from its string form
+    ///
+    /// Works for CrevIDs and SSH Ids, but not OpenPGP Ids, which are
+    /// displayed as fingerprints.
     pub fn crevid_from_str(s: &str) -> Result<Self, IdError> {
+        if let Some(key) = s.strip_prefix(SSH_ID_PREFIX) {
+            let bytes = crev_common::base64_decode(key)
+                .map_err(|e| IdError::InvalidCrevId(e.to_string().into()))?;
+            return Self::new_ssh(bytes);
+        }
         let bytes = crev_common::base64_decode(s)
             .map_err(|e| IdError::InvalidCrevId(e.to_string().into()))?;
         Self::new_crev(bytes)
@@ -96,14 +173,37 @@ impl Id {
                     .verify(&content, &signature)
                     .map_err(|e| IdError::InvalidSignature(e.to_string().into()))?;
             }
+            Id::OpenPgp { key } => {
+                let sig_bytes = crev_common::base64_decode(sig_str)
+                    .map_err(|e| IdError::InvalidSignature(e.to_string().into()))?;
+                openpgp::verify(key, content, &sig_bytes)
+                    .map_err(|e| IdError::InvalidSignature(e.to_string().into()))?;
+            }
+            Id::Ssh { key } => {
+                let sig_bytes = crev_common::base64_decode(sig_str)
+                    .map_err(|e| IdError::InvalidSignature(e.to_string().into()))?;
+                ssh::verify(key, content, &sig_bytes)
+                    .map_err(|e| IdError::InvalidSignature(e.to_string().into()))?;
+            }
         }
 
         Ok(())
     }
 
+    /// The Ed25519 public key behind this Id
+    pub fn ed25519_public_key(&self) -> Result<ed25519_dalek::PublicKey, IdError> {
+        match self {
+            Id::Crev { id: key } | Id::Ssh { key } => ed25519_dalek::PublicKey::from_bytes(key)
+                .map_err(|e| IdError::InvalidPublicKey(e.to_string().into())),
+            Id::OpenPgp { key } => Ok(openpgp::public_key(key)?),
+        }
+    }
+
     pub fn to_bytes(&self) -> Vec<u8> {
         match self {
             Id::Crev { id } => id.clone(),
+            Id::OpenPgp { key } => key.clone(),
+            Id::Ssh { key } => key.clone(),
         }
     }
 }
@@ -155,6 +255,37 @@ impl PublicId {
             .map_err(|e| crate::Error::BuildingProof(e.into()))?)
     }
 
+    /// Revoke this Id, either entirely or for proofs created since a given date
+    pub fn create_revocation_proof(
+        &self,
+        revoked_since: Option<proof::Date>,
+        comment: String,
+    ) -> crate::Result<proof::Revocation> {
+        Ok(proof::RevocationBuilder::default()
+            .from(self.clone())
+            .revoked_since(revoked_since)
+            .comment(comment)
+            .build()
+            .map_err(|e| crate::Error::BuildingProof(e.into()))?)
+    }
+
+    /// Declare this Id a team of `members`, reviewing as one
+    /// once `threshold` of them reviewed a package
+    pub fn create_team_proof(
+        &self,
+        members: Vec<PublicId>,
+        threshold: u64,
+        comment: String,
+    ) -> crate::Result<proof::Team> {
+        Ok(proof::TeamBuilder::default()
+            .from(self.clone())
+            .members(members)
+            .threshold(threshold)
+            .comment(comment)
+            .build()
+            .map_err(|e| crate::Error::BuildingProof(e.into()))?)
+    }
+
     pub fn create_package_review_proof(
         &self,
         package: proof::PackageInfo,
@@ -170,6 +301,24 @@ impl PublicId {
             .map_err(|e| crate::Error::BuildingProof(e.into()))?)
     }
 
+    /// Review individual `files` of the `package`
+    pub fn create_code_review_proof(
+        &self,
+        package: proof::PackageInfo,
+        files: Vec<proof::review::File>,
+        review: proof::review::Review,
+        comment: String,
+    ) -> crate::Result<proof::review::Code> {
+        Ok(proof::review::CodeBuilder::default()
+            .from(self.clone())
+            .package(package)
+            .files(files)
+            .review(review)
+            .comment(comment)
+            .build()
+            .map_err(|e| crate::Error::BuildingProof(e.into()))?)
+    }
+
     pub fn url_display(&self) -> &str {
         match &self.url {
             Some(url) => &url.url,
@@ -213,12 +362,44 @@ impl UnlockedId {
         })
     }
 
-    pub fn sign(&self, msg: &[u8]) -> Vec<u8> {
-        self.keypair.sign(msg).to_bytes().to_vec()
+    /// Unlock an OpenPGP Id, given the body of its public key packet
+    /// and the Ed25519 secret key
+    pub fn new_openpgp(url: Url, key: Vec<u8>, sec_key: Vec<u8>) -> Result<Self, IdError> {
+        let sec_key = SecretKey::from_bytes(&sec_key)
+            .map_err(|e| IdError::InvalidSecretKey(e.to_string().into()))?;
+        let calculated_pub_key: PublicKey = PublicKey::from(&sec_key);
+        if calculated_pub_key != openpgp::public_key(&key)? {
+            return Err(IdError::InvalidSecretKey(
+                "doesn't match the OpenPGP public key".into(),
+            ));
+        }
+
+        Ok(Self {
+            id: crate::PublicId::new(Id::new_openpgp(key)?, url),
+            keypair: ed25519_dalek::Keypair {
+                secret: sec_key,
+                public: calculated_pub_key,
+            },
+        })
+    }
+
+    /// Unlock an SSH Id, given the Ed25519 secret key
+    pub fn new_ssh(url: Url, sec_key: Vec<u8>) -> Result<Self, IdError> {
+        let sec_key = SecretKey::from_bytes(&sec_key)
+            .map_err(|e| IdError::InvalidSecretKey(e.to_string().into()))?;
+        let calculated_pub_key: PublicKey = PublicKey::from(&sec_key);
+
+        Ok(Self {
+            id: crate::PublicId::new(Id::new_ssh(calculated_pub_key.as_bytes().to_vec())?, url),
+            keypair: ed25519_dalek::Keypair {
+                secret: sec_key,
+                public: calculated_pub_key,
+            },
+        })
     }
 
     pub fn type_as_string(&self) -> String {
-        "crev".into()
+        self.id.id.id_type().to_string()
     }
 
     pub fn as_public_id(&self) -> &PublicId {
@@ -247,6 +428,31 @@ impl UnlockedId {
         ids: impl IntoIterator<Item = &'a PublicId>,
         trust_level: proof::trust::TrustLevel,
     ) -> crate::Result<proof::Proof> {
-        self.id.create_trust_proof(ids, trust_level)?.sign_by(&self)
+        self.id.create_trust_proof(ids, trust_level)?.sign_by(self)
+    }
+
+    /// Link `old_id` to this (new) Id, so the trust in it applies to this one
+    pub fn create_key_link_proof(
+        &self,
+        old_id: &dyn Signer,
+        comment: String,
+    ) -> crate::Result<proof::KeyLink> {
+        Ok(proof::KeyLinkBuilder::default()
+            .from(self.id.clone())
+            .old_id(old_id.public_id().clone())
+            .old_id_signature(proof::KeyLink::sign_statement(old_id, self.as_ref())?)
+            .comment(comment)
+            .build()
+            .map_err(|e| crate::Error::BuildingProof(e.into()))?)
+    }
+}
+
+impl Signer for UnlockedId {
+    fn public_id(&self) -> &PublicId {
+        &self.id
+    }
+
+    fn sign_ed25519(&self, data: &[u8]) -> Result<[u8; 64], SignerError> {
+        Ok(self.keypair.sign(data).to_bytes())
     }
 }
@@ -5,12 +5,15 @@
 pub mod digest;
 pub mod id;
 pub mod level;
+pub mod openpgp;
 mod prelude;
 pub mod proof;
+pub mod signer;
+pub mod ssh;
 pub mod url;
 #[macro_use]
 pub mod util;
-use crate::{id::IdError, proof::content::ValidationError};
+use crate::{id::IdError, proof::content::ValidationError, signer::SignerError};
 pub use semver::Version;
 
 pub use crate::{
@@ -22,6 +25,7 @@ pub use crate::{
         review::{Rating, Review},
         trust::TrustLevel,
     },
+    signer::Signer,
     url::Url,
 };
 
@@ -64,6 +68,9 @@ pub enum Error {
     #[error(transparent)]
     Parse(#[from] ParseError),
 
+    #[error(transparent)]
+    Signer(#[from] SignerError),
+
     #[error("Unknown level: {}", _0)]
     UnknownLevel(Box<str>),
 
@@ -0,0 +1,575 @@
+//! Minimal OpenPGP (RFC 4880) support, for using existing OpenPGP keys as Ids
+//!
+//! Only v4 Ed25519 (EdDSA) keys are supported: they are what `gpg` generates
+//! by default, and they map directly onto the keys of native CrevIDs.
+//! Proofs are signed with regular detached OpenPGP signatures
+//! (binary, base64 encoded), so they can be verified with `gpg` too.
+//! Only signatures made over SHA-256 or stronger digests are accepted.
+use aes::{
+    block_cipher_trait::{generic_array::GenericArray, BlockCipher},
+    Aes128, Aes192, Aes256,
+};
+use sha2::Digest;
+use std::{convert::TryInto, iter};
+
+const TAG_SIGNATURE: u8 = 2;
+const TAG_SECRET_KEY: u8 = 5;
+
+const ALGO_EDDSA: u8 = 22;
+const SIGNATURE_TYPE_BINARY: u8 = 0x00;
+
+pub(crate) const HASH_SHA1: u8 = 2;
+pub(crate) const HASH_SHA256: u8 = 8;
+const HASH_SHA384: u8 = 9;
+const HASH_SHA512: u8 = 10;
+pub(crate) const HASH_SHA224: u8 = 11;
+
+const CIPHER_AES128: u8 = 7;
+const CIPHER_AES192: u8 = 8;
+const CIPHER_AES256: u8 = 9;
+
+pub(crate) const SUBPACKET_CREATION_TIME: u8 = 2;
+pub(crate) const SUBPACKET_SIGNATURE_EXPIRATION_TIME: u8 = 3;
+const SUBPACKET_ISSUER: u8 = 16;
+const SUBPACKET_ISSUER_FINGERPRINT: u8 = 33;
+
+/// OID of the Ed25519 curve
+const ED25519_OID: &[u8] = &[0x2b, 0x06, 0x01, 0x04, 0x01, 0xda, 0x47, 0x0f, 0x01];
+/// Prefix of Ed25519 points in their native encoding
+const ED25519_POINT_PREFIX: u8 = 0x40;
+
+#[derive(Debug, thiserror::Error)]
+pub enum OpenPgpError {
+    #[error("Invalid ASCII armor")]
+    InvalidArmor,
+    #[error("Truncated or malformed OpenPGP packet")]
+    Malformed,
+    #[error("No OpenPGP {} packet found", _0)]
+    PacketNotFound(&'static str),
+    #[error("Unsupported OpenPGP key: only v4 Ed25519 keys are supported")]
+    UnsupportedKey,
+    #[error("Unsupported OpenPGP signature: {}", _0)]
+    UnsupportedSignature(Box<str>),
+    #[error("Unsupported OpenPGP secret key protection: {}", _0)]
+    UnsupportedProtection(Box<str>),
+    #[error("Incorrect OpenPGP passphrase")]
+    IncorrectPassphrase,
+    #[error("Invalid OpenPGP signature")]
+    InvalidSignature,
+    #[error("OpenPGP signature expired")]
+    SignatureExpired,
+}
+
+type Result<T> = std::result::Result<T, OpenPgpError>;
+
+/// Reads the fields of a packet
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.0.len() < len {
+            return Err(OpenPgpError::Malformed);
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_be_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn mpi(&mut self) -> Result<&'a [u8]> {
+        let bits = self.u16()? as usize;
+        self.take((bits + 7) / 8)
+    }
+}
+
+struct Packet<'a> {
+    tag: u8,
+    body: &'a [u8],
+}
+
+fn read_packets(data: &[u8]) -> Result<Vec<Packet<'_>>> {
+    let mut reader = Reader(data);
+    let mut packets = vec![];
+    while !reader.0.is_empty() {
+        let ctb = reader.u8()?;
+        if ctb & 0x80 == 0 {
+            return Err(OpenPgpError::Malformed);
+        }
+        let (tag, len) = if ctb & 0x40 != 0 {
+            let len = match reader.u8()? {
+                len @ 0..=191 => len as usize,
+                len @ 192..=223 => ((len as usize - 192) << 8) + reader.u8()? as usize + 192,
+                255 => reader.u32()? as usize,
+                // partial lengths are not used by keys or signatures
+                _ => return Err(OpenPgpError::Malformed),
+            };
+            (ctb & 0x3f, len)
+        } else {
+            let len = match ctb & 0x03 {
+                0 => reader.u8()? as usize,
+                1 => reader.u16()? as usize,
+                2 => reader.u32()? as usize,
+                _ => return Err(OpenPgpError::Malformed),
+            };
+            ((ctb >> 2) & 0x0f, len)
+        };
+        packets.push(Packet {
+            tag,
+            body: reader.take(len)?,
+        });
+    }
+    Ok(packets)
+}
+
+fn write_packet(tag: u8, body: &[u8]) -> Vec<u8> {
+    let mut packet = vec![0xc0 | tag];
+    match body.len() {
+        len @ 0..=191 => packet.push(len as u8),
+        len @ 192..=8383 => {
+            packet.push((((len - 192) >> 8) + 192) as u8);
+            packet.push((len - 192) as u8);
+        }
+        len => {
+            packet.push(255);
+            packet.extend_from_slice(&(len as u32).to_be_bytes());
+        }
+    }
+    packet.extend_from_slice(body);
+    packet
+}
+
+fn write_mpi(out: &mut Vec<u8>, bytes: &[u8]) {
+    let start = bytes.iter().position(|&b| b != 0).unwrap_or(bytes.len());
+    let bytes = &bytes[start..];
+    let bits = bytes
+        .first()
+        .map_or(0, |first| bytes.len() * 8 - first.leading_zeros() as usize);
+    out.extend_from_slice(&(bits as u16).to_be_bytes());
+    out.extend_from_slice(bytes);
+}
+
+/// Left-pad a 32 bytes long number, stored as an MPI with leading zeros stripped
+fn mpi_to_32_bytes(mpi: &[u8]) -> Result<[u8; 32]> {
+    if mpi.len() > 32 {
+        return Err(OpenPgpError::Malformed);
+    }
+    let mut bytes = [0; 32];
+    bytes[32 - mpi.len()..].copy_from_slice(mpi);
+    Ok(bytes)
+}
+
+/// Decode ASCII-armored data; binary data is returned as is
+///
+/// The armor checksum is not verified: the packets are.
+pub fn dearmor(data: &[u8]) -> Result<Vec<u8>> {
+    let text = match std::str::from_utf8(data) {
+        Ok(text) if text.trim_start().starts_with("-----BEGIN PGP ") => text,
+        _ => return Ok(data.to_vec()),
+    };
+    let mut lines = text
+        .lines()
+        .map(str::trim)
+        .skip_while(|line| !line.starts_with("-----BEGIN PGP "))
+        .skip(1);
+    // armor headers end with an empty line
+    if !lines.any(str::is_empty) {
+        return Err(OpenPgpError::InvalidArmor);
+    }
+    let body: String = lines
+        .take_while(|line| !line.starts_with('=') && !line.starts_with("-----END PGP "))
+        .collect();
+    base64::decode(&body).map_err(|_| OpenPgpError::InvalidArmor)
+}
+
+fn hash_with<'a, D: Digest>(parts: impl IntoIterator<Item = &'a [u8]>) -> Vec<u8> {
+    let mut hasher = D::new();
+    for part in parts {
+        hasher.input(part);
+    }
+    hasher.result().to_vec()
+}
+
+fn hash<'a>(algo: u8, parts: impl IntoIterator<Item = &'a [u8]>) -> Result<Vec<u8>> {
+    Ok(match algo {
+        HASH_SHA1 => hash_with::<sha1::Sha1>(parts),
+        HASH_SHA256 => hash_with::<sha2::Sha256>(parts),
+        HASH_SHA384 => hash_with::<sha2::Sha384>(parts),
+        HASH_SHA512 => hash_with::<sha2::Sha512>(parts),
+        HASH_SHA224 => hash_with::<sha2::Sha224>(parts),
+        algo => {
+            return Err(OpenPgpError::UnsupportedSignature(
+                format!("hash algorithm {}", algo).into(),
+            ))
+        }
+    })
+}
+
+/// Parse the public part of a key packet, returning the Ed25519 key
+fn read_public_key(reader: &mut Reader<'_>) -> Result<ed25519_dalek::PublicKey> {
+    if reader.u8()? != 4 {
+        return Err(OpenPgpError::UnsupportedKey);
+    }
+    let _creation_time = reader.u32()?;
+    if reader.u8()? != ALGO_EDDSA {
+        return Err(OpenPgpError::UnsupportedKey);
+    }
+    let oid_len = reader.u8()? as usize;
+    if reader.take(oid_len)? != ED25519_OID {
+        return Err(OpenPgpError::UnsupportedKey);
+    }
+    match reader.mpi()? {
+        [ED25519_POINT_PREFIX, point @ ..] => {
+            ed25519_dalek::PublicKey::from_bytes(point).map_err(|_| OpenPgpError::UnsupportedKey)
+        }
+        _ => Err(OpenPgpError::UnsupportedKey),
+    }
+}
+
+/// Ed25519 key of the given public key packet body
+pub fn public_key(key: &[u8]) -> Result<ed25519_dalek::PublicKey> {
+    let mut reader = Reader(key);
+    let public_key = read_public_key(&mut reader)?;
+    if !reader.0.is_empty() {
+        return Err(OpenPgpError::Malformed);
+    }
+    Ok(public_key)
+}
+
+/// V4 fingerprint of the given public key packet body
+pub fn fingerprint(key: &[u8]) -> [u8; 20] {
+    let len = (key.len() as u16).to_be_bytes();
+    hash_with::<sha1::Sha1>(vec![&[0x99][..], &len, key])[..]
+        .try_into()
+        .expect("SHA-1 is 20 bytes")
+}
+
+/// Digest signed by a v4 signature with the given hashed part
+fn signature_digest(hash_algo: u8, msg: &[u8], hashed: &[u8]) -> Result<Vec<u8>> {
+    let trailer_len = (hashed.len() as u32).to_be_bytes();
+    hash(hash_algo, vec![msg, hashed, &[4, 0xff], &trailer_len])
+}
+
+/// Create a detached signature (a binary signature packet) of `msg`
+///
+/// `key` is the public key packet body, and `sign_ed25519` makes
+/// the Ed25519 signature with the corresponding secret key.
+pub fn sign<E>(
+    key: &[u8],
+    msg: &[u8],
+    created: u32,
+    sign_ed25519: impl FnOnce(&[u8]) -> std::result::Result<[u8; 64], E>,
+) -> std::result::Result<Vec<u8>, E> {
+    let mut hashed_subpackets = vec![5, SUBPACKET_CREATION_TIME];
+    hashed_subpackets.extend_from_slice(&created.to_be_bytes());
+    hashed_subpackets.extend_from_slice(&[22, SUBPACKET_ISSUER_FINGERPRINT, 4]);
+    hashed_subpackets.extend_from_slice(&fingerprint(key));
+
+    sign_with(key, msg, HASH_SHA256, &hashed_subpackets, sign_ed25519)
+}
+
+/// Create a detached signature of `msg` with the given hash algorithm and
+/// (encoded) hashed subpackets
+pub(crate) fn sign_with<E>(
+    key: &[u8],
+    msg: &[u8],
+    hash_algo: u8,
+    hashed_subpackets: &[u8],
+    sign_ed25519: impl FnOnce(&[u8]) -> std::result::Result<[u8; 64], E>,
+) -> std::result::Result<Vec<u8>, E> {
+    let fingerprint = fingerprint(key);
+
+    let mut body = vec![4, SIGNATURE_TYPE_BINARY, ALGO_EDDSA, hash_algo];
+    body.extend_from_slice(&(hashed_subpackets.len() as u16).to_be_bytes());
+    body.extend_from_slice(hashed_subpackets);
+    let digest = signature_digest(hash_algo, msg, &body).expect("hash algorithm is supported");
+    let signature = sign_ed25519(&digest)?;
+
+    // for older implementations, that only understand key ids
+    let mut unhashed_subpackets = vec![9, SUBPACKET_ISSUER];
+    unhashed_subpackets.extend_from_slice(&fingerprint[12..]);
+    body.extend_from_slice(&(unhashed_subpackets.len() as u16).to_be_bytes());
+    body.extend_from_slice(&unhashed_subpackets);
+    body.extend_from_slice(&digest[..2]);
+    write_mpi(&mut body, &signature[..32]);
+    write_mpi(&mut body, &signature[32..]);
+
+    Ok(write_packet(TAG_SIGNATURE, &body))
+}
+
+/// Subpackets of a signature, as `(type, is_critical, body)`
+fn read_subpackets(data: &[u8]) -> Result<Vec<(u8, bool, &[u8])>> {
+    let mut reader = Reader(data);
+    let mut subpackets = vec![];
+    while !reader.0.is_empty() {
+        let len = match reader.u8()? {
+            len @ 0..=191 => len as usize,
+            len @ 192..=254 => ((len as usize - 192) << 8) + reader.u8()? as usize + 192,
+            255 => reader.u32()? as usize,
+        };
+        let mut subpacket = Reader(reader.take(len)?);
+        let tag = subpacket.u8()?;
+        subpackets.push((tag & 0x7f, tag & 0x80 != 0, subpacket.0));
+    }
+    Ok(subpackets)
+}
+
+/// Check the hashed subpackets of a signature: it has to have a creation time,
+/// can't be expired at `now`, and can't have critical subpackets we don't know
+fn check_hashed_subpackets(data: &[u8], now: i64) -> Result<()> {
+    let mut created = None;
+    let mut expires_after = None;
+    for (tag, is_critical, body) in read_subpackets(data)? {
+        match tag {
+            SUBPACKET_CREATION_TIME => created = Some(Reader(body).u32()?),
+            SUBPACKET_SIGNATURE_EXPIRATION_TIME => expires_after = Some(Reader(body).u32()?),
+            SUBPACKET_ISSUER | SUBPACKET_ISSUER_FINGERPRINT => {}
+            tag if is_critical => {
+                return Err(OpenPgpError::UnsupportedSignature(
+                    format!("critical subpacket {}", tag).into(),
+                ))
+            }
+            _ => {}
+        }
+    }
+    let created = created
+        .ok_or_else(|| OpenPgpError::UnsupportedSignature("no signature creation time".into()))?;
+    // zero means it never expires
+    match expires_after {
+        Some(expires_after)
+            if expires_after != 0 && i64::from(created) + i64::from(expires_after) <= now =>
+        {
+            Err(OpenPgpError::SignatureExpired)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Verify a detached signature (a binary signature packet) of `msg`
+///
+/// `key` is the public key packet body of the signer.
+pub fn verify(key: &[u8], msg: &[u8], signature: &[u8]) -> Result<()> {
+    let public_key = public_key(key)?;
+    let packets = read_packets(signature)?;
+    let body = match packets.as_slice() {
+        [packet] if packet.tag == TAG_SIGNATURE => packet.body,
+        _ => return Err(OpenPgpError::PacketNotFound("signature")),
+    };
+
+    let mut reader = Reader(body);
+    if reader.u8()? != 4 {
+        return Err(OpenPgpError::UnsupportedSignature(
+            "only v4 signatures are supported".into(),
+        ));
+    }
+    if reader.u8()? != SIGNATURE_TYPE_BINARY {
+        return Err(OpenPgpError::UnsupportedSignature(
+            "not a signature of a binary document".into(),
+        ));
+    }
+    if reader.u8()? != ALGO_EDDSA {
+        return Err(OpenPgpError::UnsupportedSignature(
+            "not an EdDSA signature".into(),
+        ));
+    }
+    let hash_algo = reader.u8()?;
+    // SHA-1 and SHA-224 are too weak for signatures (but fine for fingerprints and S2K)
+    if ![HASH_SHA256, HASH_SHA384, HASH_SHA512].contains(&hash_algo) {
+        return Err(OpenPgpError::UnsupportedSignature(
+            format!("hash algorithm {}", hash_algo).into(),
+        ));
+    }
+    let hashed_subpackets_len = reader.u16()? as usize;
+    check_hashed_subpackets(
+        reader.take(hashed_subpackets_len)?,
+        crev_common::now().timestamp(),
+    )?;
+    let hashed = &body[..body.len() - reader.0.len()];
+    let unhashed_subpackets_len = reader.u16()? as usize;
+    reader.take(unhashed_subpackets_len)?;
+    let digest_prefix = reader.take(2)?;
+    let r = mpi_to_32_bytes(reader.mpi()?)?;
+    let s = mpi_to_32_bytes(reader.mpi()?)?;
+
+    let digest = signature_digest(hash_algo, msg, hashed)?;
+    if digest[..2] != *digest_prefix {
+        return Err(OpenPgpError::InvalidSignature);
+    }
+    let signature = ed25519_dalek::Signature::from_bytes(&[r, s].concat())
+        .map_err(|_| OpenPgpError::InvalidSignature)?;
+    public_key
+        .verify(&digest, &signature)
+        .map_err(|_| OpenPgpError::InvalidSignature)
+}
+
+/// Secret key of an Ed25519 OpenPGP key, e.g. exported with `gpg --export-secret-keys`
+pub struct SecretKey {
+    /// Body of the corresponding public key packet
+    public_key: Vec<u8>,
+    /// The rest of the secret key packet: protection and (protected) secret key
+    secret: Vec<u8>,
+}
+
+impl SecretKey {
+    /// Read the primary key from (possibly ASCII-armored) exported secret keys
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let data = dearmor(data)?;
+        let packets = read_packets(&data)?;
+        let body = packets
+            .iter()
+            .find(|packet| packet.tag == TAG_SECRET_KEY)
+            .ok_or(OpenPgpError::PacketNotFound("secret key"))?
+            .body;
+
+        let mut reader = Reader(body);
+        read_public_key(&mut reader)?;
+        let public_len = body.len() - reader.0.len();
+        Ok(Self {
+            public_key: body[..public_len].to_vec(),
+            secret: body[public_len..].to_vec(),
+        })
+    }
+
+    /// Body of the corresponding public key packet
+    pub fn public_key(&self) -> &[u8] {
+        &self.public_key
+    }
+
+    /// Whether a passphrase is needed to `unlock` the key
+    pub fn is_encrypted(&self) -> bool {
+        self.secret.first() != Some(&0)
+    }
+
+    /// Get the Ed25519 secret key, decrypting it with `passphrase` if needed
+    pub fn unlock(&self, passphrase: &str) -> Result<ed25519_dalek::SecretKey> {
+        let mut reader = Reader(&self.secret);
+        let s2k_usage = reader.u8()?;
+        let secret = match s2k_usage {
+            0 => reader.0.to_vec(),
+            254 | 255 => {
+                let cipher = reader.u8()?;
+                let key = string_to_key(&mut reader, passphrase, cipher_key_len(cipher)?)?;
+                let iv = reader.take(16)?;
+                let mut secret = reader.0.to_vec();
+                match cipher {
+                    CIPHER_AES128 => {
+                        cfb_decrypt(&Aes128::new_varkey(&key).unwrap(), iv, &mut secret)
+                    }
+                    CIPHER_AES192 => {
+                        cfb_decrypt(&Aes192::new_varkey(&key).unwrap(), iv, &mut secret)
+                    }
+                    _ => cfb_decrypt(&Aes256::new_varkey(&key).unwrap(), iv, &mut secret),
+                }
+                secret
+            }
+            usage => {
+                return Err(OpenPgpError::UnsupportedProtection(
+                    format!("S2K usage {}", usage).into(),
+                ))
+            }
+        };
+
+        let checksum_len = if s2k_usage == 254 { 20 } else { 2 };
+        if secret.len() < checksum_len {
+            return Err(OpenPgpError::Malformed);
+        }
+        let (secret, checksum) = secret.split_at(secret.len() - checksum_len);
+        let is_checksum_valid = if s2k_usage == 254 {
+            hash_with::<sha1::Sha1>(iter::once(secret)) == checksum
+        } else {
+            let sum = secret
+                .iter()
+                .fold(0u16, |sum, &b| sum.wrapping_add(u16::from(b)));
+            sum.to_be_bytes() == checksum
+        };
+        if !is_checksum_valid {
+            return Err(if s2k_usage == 0 {
+                OpenPgpError::Malformed
+            } else {
+                OpenPgpError::IncorrectPassphrase
+            });
+        }
+
+        let secret = mpi_to_32_bytes(Reader(secret).mpi()?)?;
+        let secret_key = ed25519_dalek::SecretKey::from_bytes(&secret)
+            .map_err(|_| OpenPgpError::UnsupportedKey)?;
+        if ed25519_dalek::PublicKey::from(&secret_key) != public_key(&self.public_key)? {
+            return Err(OpenPgpError::Malformed);
+        }
+        Ok(secret_key)
+    }
+}
+
+fn cipher_key_len(cipher: u8) -> Result<usize> {
+    match cipher {
+        CIPHER_AES128 => Ok(16),
+        CIPHER_AES192 => Ok(24),
+        CIPHER_AES256 => Ok(32),
+        cipher => Err(OpenPgpError::UnsupportedProtection(
+            format!("cipher algorithm {}", cipher).into(),
+        )),
+    }
+}
+
+/// Read an S2K specifier and derive a `key_len` bytes long key from `passphrase`
+fn string_to_key(reader: &mut Reader<'_>, passphrase: &str, key_len: usize) -> Result<Vec<u8>> {
+    let s2k_type = reader.u8()?;
+    let hash_algo = reader.u8()?;
+    let salt = match s2k_type {
+        0 => &[][..],
+        1 | 3 => reader.take(8)?,
+        s2k_type => {
+            return Err(OpenPgpError::UnsupportedProtection(
+                format!("S2K type {}", s2k_type).into(),
+            ))
+        }
+    };
+    let salted = [salt, passphrase.as_bytes()].concat();
+    let count = if s2k_type == 3 {
+        let coded = reader.u8()?;
+        std::cmp::max(
+            (16 + (coded as usize & 15)) << ((coded >> 4) + 6),
+            salted.len(),
+        )
+    } else {
+        salted.len()
+    };
+
+    let zeros = vec![0; key_len];
+    let mut key = vec![];
+    // each next hash context is preloaded with one more zero byte
+    let mut preload = 0;
+    while key.len() < key_len {
+        let repeat = std::cmp::max(salted.len(), 1);
+        let parts = iter::once(&zeros[..preload])
+            .chain(iter::repeat(&salted[..]).take(count / repeat))
+            .chain(iter::once(&salted[..count % repeat]));
+        key.extend(hash(hash_algo, parts)?);
+        preload += 1;
+    }
+    key.truncate(key_len);
+    Ok(key)
+}
+
+/// CFB mode decryption (without the OpenPGP resynchronization)
+fn cfb_decrypt<C: BlockCipher>(cipher: &C, iv: &[u8], data: &mut [u8]) {
+    let mut feedback = GenericArray::clone_from_slice(iv);
+    for chunk in data.chunks_mut(iv.len()) {
+        let mut keystream = feedback.clone();
+        cipher.encrypt_block(&mut keystream);
+        feedback[..chunk.len()].copy_from_slice(chunk);
+        for (b, k) in chunk.iter_mut().zip(keystream) {
+            *b ^= k;
+        }
+    }
+}
@@ -98,6 +98,29 @@ pub enum ValidationError {
     AdvisoriesWithNoIDSAreNotAllowed,
     #[error("Advisories with an empty `id` field are not allowed")]
     AdvisoriesWithAnEmptyIDFieldAreNotAllowed,
+    #[error("Key link from an Id to itself")]
+    KeyLinkToSelf,
+    #[error("Invalid signature of the old Id in a key link")]
+    InvalidKeyLinkSignature,
+    #[error("Team threshold {} out of range for {} members", _0, _1)]
+    InvalidTeamThreshold(u64, usize),
+    #[error("A team can't be a member of itself")]
+    TeamMemberOfItself,
+    #[error("Notes with an empty `path` are not allowed")]
+    NotesWithAnEmptyPathAreNotAllowed,
+    #[error("Invalid line range of a note: {}", _0)]
+    InvalidNoteLineRange(String),
+    #[error("Path of a note is not inside the package: {}", _0)]
+    NotePathOutsideOfPackage(String),
+    #[error("Invalid CVSS vector: {}", _0)]
+    InvalidCvssVector(String),
+    #[error("Invalid CWE id: {}", _0)]
+    InvalidCweId(String),
+    #[error(
+        "Invalid advisory reference: {} (expected a RUSTSEC, CVE or GHSA id)",
+        _0
+    )]
+    InvalidAdvisoryReference(String),
 }
 
 pub type ValidationResult<T> = std::result::Result<T, ValidationError>;
@@ -179,9 +202,10 @@ pub trait ContentExt: Content {
         Ok(body)
     }
 
-    fn sign_by(&self, id: &crate::id::UnlockedId) -> Result<Proof> {
+    /// Sign with `signer`, which has to be the author of the content
+    fn sign_by(&self, signer: &dyn crate::signer::Signer) -> Result<Proof> {
         let body = self.serialize()?;
-        let signature = id.sign(&body.as_bytes());
+        let signature = signer.sign(&body.as_bytes())?;
         Ok(Proof {
             digest: crev_common::blake2b256sum(body.as_bytes()),
             body,
@@ -0,0 +1,108 @@
+use crate::{
+    proof::{
+        self,
+        content::{ValidationError, ValidationResult},
+        CommonOps, Content,
+    },
+    serde_content_serialize,
+    signer::{Signer, SignerError},
+    Id,
+};
+use derive_builder::Builder;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+const CURRENT_KEY_LINK_PROOF_SERIALIZATION_VERSION: i64 = -1;
+
+fn cur_version() -> i64 {
+    CURRENT_KEY_LINK_PROOF_SERIALIZATION_VERSION
+}
+
+/// Body of a Key Link Proof
+///
+/// Links an old Id to the new one (the author), after rotating keys.
+/// The proof is signed by the new Id, and contains a signature
+/// of the old Id over `KeyLink::statement`, so it can only be created
+/// by someone holding both secret keys.
+///
+/// Trust in the old Id applies to the new one.
+#[derive(Clone, Debug, Builder, Serialize, Deserialize)]
+pub struct KeyLink {
+    #[serde(flatten)]
+    pub common: proof::Common,
+    #[serde(rename = "old-id")]
+    pub old_id: crate::PublicId,
+    /// Signature of the old Id over `KeyLink::statement`
+    #[serde(rename = "old-id-signature")]
+    pub old_id_signature: String,
+    #[serde(skip_serializing_if = "String::is_empty", default = "Default::default")]
+    #[builder(default = "Default::default()")]
+    pub comment: String,
+}
+
+impl KeyLinkBuilder {
+    pub fn from<VALUE: Into<crate::PublicId>>(&mut self, value: VALUE) -> &mut Self {
+        if let Some(ref mut common) = self.common {
+            common.from = value.into();
+        } else {
+            self.common = Some(proof::Common {
+                kind: Some(KeyLink::KIND.into()),
+                version: cur_version(),
+                date: crev_common::now(),
+                from: value.into(),
+            });
+        }
+        self
+    }
+}
+
+impl fmt::Display for KeyLink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.serialize_to(f).map_err(|_| fmt::Error)
+    }
+}
+
+impl proof::CommonOps for KeyLink {
+    fn common(&self) -> &proof::Common {
+        &self.common
+    }
+}
+
+impl proof::Content for KeyLink {
+    fn serialize_to(&self, fmt: &mut dyn std::fmt::Write) -> fmt::Result {
+        serde_content_serialize!(self, fmt);
+        Ok(())
+    }
+
+    fn validate_data(&self) -> ValidationResult<()> {
+        self.ensure_kind_is(Self::KIND)?;
+
+        let new_id = &self.from().id;
+        if self.old_id.id == *new_id {
+            Err(ValidationError::KeyLinkToSelf)?;
+        }
+        self.old_id
+            .id
+            .verify_signature(
+                &Self::statement(&self.old_id.id, new_id),
+                &self.old_id_signature,
+            )
+            .map_err(|_| ValidationError::InvalidKeyLinkSignature)?;
+        Ok(())
+    }
+}
+
+impl KeyLink {
+    pub const KIND: &'static str = "key link";
+
+    /// What the old Id signs, to agree to be replaced by the new one
+    pub fn statement(old_id: &Id, new_id: &Id) -> Vec<u8> {
+        format!("crev key link from {} to {}", old_id, new_id).into_bytes()
+    }
+
+    /// Sign the link of `old_id` to `new_id` with the old Id's key
+    pub fn sign_statement(old_id: &dyn Signer, new_id: &Id) -> Result<String, SignerError> {
+        let statement = Self::statement(&old_id.public_id().id, new_id);
+        Ok(crev_common::base64_encode(&old_id.sign(&statement)?))
+    }
+}
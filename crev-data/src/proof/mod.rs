@@ -5,20 +5,26 @@ pub use crate::proof::content::{
 };
 use crate::{Error, ParseError, Result};
 use chrono::{self, prelude::*};
+pub use key_link::*;
 pub use package_info::*;
 pub use review::{Code as CodeReview, Package as PackageReview, *};
 pub use revision::*;
+pub use revocation::*;
 use std::{
     default, fmt,
     io::{self, BufRead},
     mem,
 };
+pub use team::*;
 pub use trust::*;
 
 pub mod content;
+pub mod key_link;
 pub mod package_info;
 pub mod review;
 pub mod revision;
+pub mod revocation;
+pub mod team;
 pub mod trust;
 
 const MAX_PROOF_BODY_LENGTH: usize = 32_000;
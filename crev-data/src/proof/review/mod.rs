@@ -3,7 +3,7 @@ pub use code::*;
 use derive_builder::Builder;
 pub use package::*;
 use serde::{Deserialize, Serialize};
-use std::default::Default;
+use std::{default::Default, fmt};
 
 pub mod code;
 pub mod package;
@@ -24,6 +24,17 @@ impl Default for Rating {
     }
 }
 
+impl fmt::Display for Rating {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Rating::Negative => "negative",
+            Rating::Neutral => "neutral",
+            Rating::Positive => "positive",
+            Rating::Strong => "strong",
+        })
+    }
+}
+
 /// Information about review result
 #[derive(Clone, Debug, Serialize, Deserialize, Builder, PartialEq, Eq)]
 pub struct Review {
@@ -3,12 +3,20 @@ use crate::{
     proof::content::{ValidationError, ValidationResult},
     serde_content_serialize, serde_draft_serialize, Error, Level, ParseError,
 };
-use crev_common::{self, is_equal_default, is_set_empty, is_vec_empty};
+use crev_common::{
+    self, is_equal_default, is_set_empty, is_vec_empty,
+    serde::{as_base64, as_rfc3339_fixed_opt, from_base64, from_rfc3339_fixed_opt},
+};
 use derive_builder::Builder;
 use proof::{CommonOps, Content};
-use semver::Version;
-use serde::{Deserialize, Serialize};
-use std::{collections::HashSet, default::Default, fmt, ops};
+use semver::{Version, VersionReq};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    collections::HashSet,
+    default::Default,
+    fmt, ops,
+    path::{Component, Path, PathBuf},
+};
 use typed_builder::TypedBuilder;
 
 const CURRENT_PACKAGE_REVIEW_PROOF_SERIALIZATION_VERSION: i64 = -1;
@@ -76,6 +84,9 @@ pub struct Package {
     #[builder(default = "Default::default()")]
     #[serde(skip_serializing_if = "is_vec_empty", default = "Default::default")]
     pub advisories: Vec<Advisory>,
+    #[builder(default = "Default::default()")]
+    #[serde(skip_serializing_if = "is_vec_empty", default = "Default::default")]
+    pub notes: Vec<Note>,
     #[serde(default = "Default::default", skip_serializing_if = "is_equal_default")]
     #[builder(default = "Default::default()")]
     pub flags: Flags,
@@ -85,6 +96,16 @@ pub struct Package {
     #[serde(skip_serializing_if = "String::is_empty", default = "Default::default")]
     #[builder(default = "Default::default()")]
     pub comment: String,
+    /// Review should not be relied on after this date
+    #[serde(
+        rename = "valid-until",
+        skip_serializing_if = "Option::is_none",
+        default = "Default::default",
+        serialize_with = "as_rfc3339_fixed_opt",
+        deserialize_with = "from_rfc3339_fixed_opt"
+    )]
+    #[builder(default = "Default::default()")]
+    pub valid_until: Option<proof::Date>,
 }
 
 impl PackageBuilder {
@@ -135,12 +156,22 @@ pub struct Draft {
     pub advisories: Vec<Advisory>,
     #[serde(default = "Default::default", skip_serializing_if = "is_vec_empty")]
     pub issues: Vec<Issue>,
+    #[serde(default = "Default::default", skip_serializing_if = "is_vec_empty")]
+    pub notes: Vec<Note>,
     #[serde(default = "Default::default", skip_serializing_if = "String::is_empty")]
     comment: String,
     #[serde(default = "Default::default")]
     pub flags: FlagsDraft,
     #[serde(default = "Default::default", skip_serializing_if = "is_set_empty")]
     pub alternatives: HashSet<proof::PackageId>,
+    #[serde(
+        rename = "valid-until",
+        default = "Default::default",
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "as_rfc3339_fixed_opt",
+        deserialize_with = "from_rfc3339_fixed_opt"
+    )]
+    pub valid_until: Option<proof::Date>,
 }
 
 impl Draft {
@@ -155,6 +186,15 @@ impl From<Package> for Draft {
             review: package.review,
             advisories: package.advisories,
             issues: package.issues,
+            // digests are filled in again for the edited notes
+            notes: package
+                .notes
+                .into_iter()
+                .map(|note| Note {
+                    digest: vec![],
+                    ..note
+                })
+                .collect(),
             comment: package.comment,
             alternatives: if package.alternatives.is_empty() {
                 // To give user a convenient template, we pre-fill with the same `source`,
@@ -169,6 +209,7 @@ impl From<Package> for Draft {
                 package.alternatives
             },
             flags: package.flags.into(),
+            valid_until: package.valid_until,
         }
     }
 }
@@ -189,6 +230,23 @@ impl proof::Content for Package {
             if issue.id.is_empty() {
                 Err(ValidationError::IssuesWithAnEmptyIDFieldAreNotAllowed)?;
             }
+            validate_vulnerability_details(&issue.cvss, &issue.cwe, &issue.references)?;
+        }
+
+        for note in &self.notes {
+            if note.path.as_os_str().is_empty() {
+                Err(ValidationError::NotesWithAnEmptyPathAreNotAllowed)?;
+            }
+            if !is_path_inside_package(&note.path) {
+                Err(ValidationError::NotePathOutsideOfPackage(
+                    note.path.display().to_string(),
+                ))?;
+            }
+            if note.lines.start == 0 || note.lines.end < note.lines.start {
+                Err(ValidationError::InvalidNoteLineRange(
+                    note.lines.to_string(),
+                ))?;
+            }
         }
 
         for advisory in &self.advisories {
@@ -201,6 +259,7 @@ impl proof::Content for Package {
                     Err(ValidationError::AdvisoriesWithAnEmptyIDFieldAreNotAllowed)?;
                 }
             }
+            validate_vulnerability_details(&advisory.cvss, &advisory.cwe, &advisory.references)?;
         }
         Ok(())
     }
@@ -230,12 +289,14 @@ impl proof::ContentWithDraft for Package {
         package.comment = draft.comment;
         package.advisories = draft.advisories;
         package.issues = draft.issues;
+        package.notes = draft.notes;
         package.alternatives = draft
             .alternatives
             .into_iter()
             .filter(|a| !a.name.is_empty())
             .collect();
         package.flags = draft.flags.into();
+        package.valid_until = draft.valid_until;
 
         package.validate_data()?;
         Ok(package)
@@ -245,6 +306,11 @@ impl proof::ContentWithDraft for Package {
 impl Package {
     pub const KIND: &'static str = "package review";
 
+    /// Whether `valid-until` of this review has already passed
+    pub fn is_expired_at(&self, now: proof::DateUtc) -> bool {
+        self.valid_until.map_or(false, |valid_until| valid_until < now)
+    }
+
     pub fn is_advisory_for(&self, version: &Version) -> bool {
         for advisory in &self.advisories {
             if advisory.is_for_version_when_reported_in_version(version, &self.package.id.version) {
@@ -262,6 +328,10 @@ impl fmt::Display for Draft {
     }
 }
 
+/// Versions affected, relative to the version an advisory or issue was reported in
+///
+/// Kept for compatibility with existing proofs; an explicit `affected`
+/// version requirement takes precedence over it.
 #[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
 #[serde(rename_all = "kebab-case")]
 pub enum VersionRange {
@@ -313,7 +383,9 @@ impl VersionRange {
 ///
 /// Advisory means a general important fix was included in this
 /// release, and all previous releases were potentially affected.
-/// We don't play with exact ranges.
+///
+/// Unless `affected` or `patched` versions are given explicitly,
+/// we don't play with exact ranges.
 #[derive(Clone, TypedBuilder, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct Advisory {
@@ -328,6 +400,27 @@ pub struct Advisory {
     )]
     pub range: VersionRange,
 
+    /// Versions affected by the problem
+    #[builder(default)]
+    #[serde(default = "Default::default", skip_serializing_if = "Option::is_none")]
+    pub affected: Option<VersionReq>,
+    /// Versions with the problem fixed
+    #[builder(default)]
+    #[serde(default = "Default::default", skip_serializing_if = "is_vec_empty")]
+    pub patched: Vec<VersionReq>,
+    /// CVSS v3 vector, like `CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H`
+    #[builder(default)]
+    #[serde(default = "Default::default", skip_serializing_if = "Option::is_none")]
+    pub cvss: Option<String>,
+    /// CWE ids, like `CWE-79`
+    #[builder(default)]
+    #[serde(default = "Default::default", skip_serializing_if = "is_vec_empty")]
+    pub cwe: Vec<String>,
+    /// Ids of the same problem in other databases: RUSTSEC, CVE or GHSA
+    #[builder(default)]
+    #[serde(default = "Default::default", skip_serializing_if = "is_vec_empty")]
+    pub references: Vec<String>,
+
     #[builder(default)]
     #[serde(default = "Default::default")]
     pub comment: String,
@@ -348,17 +441,37 @@ impl Default for Advisory {
             ids: vec![],
             range: VersionRange::default(),
             severity: Default::default(),
+            affected: None,
+            patched: vec![],
+            cvss: None,
+            cwe: vec![],
+            references: vec![],
             comment: "".to_string(),
         }
     }
 }
 
 impl Advisory {
+    /// Whether the affected versions are given explicitly
+    pub fn has_exact_versions(&self) -> bool {
+        self.affected.is_some() || !self.patched.is_empty()
+    }
+
     pub fn is_for_version_when_reported_in_version(
         &self,
         for_version: &Version,
         in_pkg_version: &Version,
     ) -> bool {
+        if self.has_exact_versions() {
+            return self
+                .affected
+                .as_ref()
+                .map_or(true, |affected| affected.matches(for_version))
+                && !self
+                    .patched
+                    .iter()
+                    .any(|patched| patched.matches(for_version));
+        }
         if for_version < in_pkg_version {
             match self.range {
                 VersionRange::All => return true,
@@ -386,8 +499,9 @@ impl Advisory {
 /// a problem with package in a given version. It leaves the
 /// question open if any previous and following versions might
 /// also be affected, but will be considered open and affecting
-/// all following versions withing the `range` until an advisory
-/// is found for it, matching the id.
+/// all following versions withing the `range` (or exactly the
+/// `affected` versions, if given) until an advisory is found for it,
+/// matching the id.
 #[derive(Clone, TypedBuilder, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct Issue {
@@ -402,6 +516,24 @@ pub struct Issue {
     )]
     pub range: VersionRange,
 
+    /// Versions affected by the problem
+    #[builder(default)]
+    #[serde(default = "Default::default", skip_serializing_if = "Option::is_none")]
+    pub affected: Option<VersionReq>,
+
+    /// CVSS v3 vector, like `CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H`
+    #[builder(default)]
+    #[serde(default = "Default::default", skip_serializing_if = "Option::is_none")]
+    pub cvss: Option<String>,
+    /// CWE ids, like `CWE-79`
+    #[builder(default)]
+    #[serde(default = "Default::default", skip_serializing_if = "is_vec_empty")]
+    pub cwe: Vec<String>,
+    /// Ids of the same problem in other databases: RUSTSEC, CVE or GHSA
+    #[builder(default)]
+    #[serde(default = "Default::default", skip_serializing_if = "is_vec_empty")]
+    pub references: Vec<String>,
+
     #[builder(default)]
     #[serde(default = "Default::default")]
     pub comment: String,
@@ -409,18 +541,17 @@ pub struct Issue {
 
 impl Issue {
     pub fn new(id: String) -> Self {
-        Self {
-            id,
-            range: Default::default(),
-            severity: Default::default(),
-            comment: Default::default(),
-        }
+        Self::new_with_severity(id, Default::default())
     }
     pub fn new_with_severity(id: String, severity: Level) -> Self {
         Self {
             id,
             range: Default::default(),
             severity,
+            affected: None,
+            cvss: None,
+            cwe: vec![],
+            references: vec![],
             comment: Default::default(),
         }
     }
@@ -429,6 +560,9 @@ impl Issue {
         for_version: &Version,
         in_pkg_version: &Version,
     ) -> bool {
+        if let Some(ref affected) = self.affected {
+            return affected.matches(for_version);
+        }
         if for_version >= in_pkg_version {
             match self.range {
                 VersionRange::All => return true,
@@ -449,3 +583,222 @@ impl Issue {
         false
     }
 }
+
+fn validate_vulnerability_details(
+    cvss: &Option<String>,
+    cwe: &[String],
+    references: &[String],
+) -> ValidationResult<()> {
+    if let Some(cvss) = cvss {
+        if !is_valid_cvss_vector(cvss) {
+            Err(ValidationError::InvalidCvssVector(cvss.to_owned()))?;
+        }
+    }
+    for cwe in cwe {
+        if !cwe.starts_with("CWE-") || !is_all_digits(&cwe[4..]) {
+            Err(ValidationError::InvalidCweId(cwe.to_owned()))?;
+        }
+    }
+    for reference in references {
+        if !is_valid_reference(reference) {
+            Err(ValidationError::InvalidAdvisoryReference(
+                reference.to_owned(),
+            ))?;
+        }
+    }
+    Ok(())
+}
+
+fn is_all_digits(s: &str) -> bool {
+    !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Check a CVSS v3 vector: all base metrics, each once, with allowed values
+fn is_valid_cvss_vector(vector: &str) -> bool {
+    const BASE_METRICS: &[(&str, &str)] = &[
+        ("AV", "NALP"),
+        ("AC", "LH"),
+        ("PR", "NLH"),
+        ("UI", "NR"),
+        ("S", "UC"),
+        ("C", "NLH"),
+        ("I", "NLH"),
+        ("A", "NLH"),
+    ];
+
+    let mut parts = vector.split('/');
+    match parts.next() {
+        Some("CVSS:3.0") | Some("CVSS:3.1") => {}
+        _ => return false,
+    }
+    let mut seen = HashSet::new();
+    for part in parts {
+        let mut metric = part.splitn(2, ':');
+        let (name, value) = match (metric.next(), metric.next()) {
+            (Some(name), Some(value)) if !name.is_empty() && !value.is_empty() => (name, value),
+            _ => return false,
+        };
+        if !seen.insert(name) {
+            return false;
+        }
+        if let Some((_, allowed)) = BASE_METRICS.iter().find(|(base, _)| *base == name) {
+            if value.len() != 1 || !allowed.contains(value) {
+                return false;
+            }
+        }
+    }
+    BASE_METRICS.iter().all(|(name, _)| seen.contains(name))
+}
+
+/// `RUSTSEC-YYYY-NNNN`, `CVE-YYYY-NNNN` or `GHSA-xxxx-xxxx-xxxx`
+fn is_valid_reference(reference: &str) -> bool {
+    let parts: Vec<_> = reference.split('-').collect();
+    match parts.as_slice() {
+        ["RUSTSEC", year, num] => {
+            year.len() == 4 && is_all_digits(year) && num.len() == 4 && is_all_digits(num)
+        }
+        ["CVE", year, num] => {
+            year.len() == 4 && is_all_digits(year) && num.len() >= 4 && is_all_digits(num)
+        }
+        ["GHSA", a, b, c] => [a, b, c].iter().all(|part| {
+            part.len() == 4
+                && part
+                    .bytes()
+                    .all(|b| b.is_ascii_digit() || b.is_ascii_lowercase())
+        }),
+        _ => false,
+    }
+}
+
+/// Lines of a file, from `start` to `end` (inclusive, counted from 1)
+///
+/// Written as `10-20`, or just `10` for a single line.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LineRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl LineRange {
+    pub fn contains(self, line: u64) -> bool {
+        self.start <= line && line <= self.end
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LineRangeParseError(String);
+
+impl fmt::Display for LineRangeParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Could not parse a line range: `{}`", self.0)
+    }
+}
+
+impl std::str::FromStr for LineRange {
+    type Err = LineRangeParseError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let parse_line = |line: &str| {
+            line.trim()
+                .parse()
+                .map_err(|_| LineRangeParseError(s.to_owned()))
+        };
+        Ok(match s.find('-') {
+            Some(i) => LineRange {
+                start: parse_line(&s[..i])?,
+                end: parse_line(&s[i + 1..])?,
+            },
+            None => {
+                let line = parse_line(s)?;
+                LineRange {
+                    start: line,
+                    end: line,
+                }
+            }
+        })
+    }
+}
+
+impl fmt::Display for LineRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.start == self.end {
+            write!(f, "{}", self.start)
+        } else {
+            write!(f, "{}-{}", self.start, self.end)
+        }
+    }
+}
+
+impl Serialize for LineRange {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        if self.start == self.end {
+            serializer.serialize_u64(self.start)
+        } else {
+            serializer.serialize_str(&self.to_string())
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for LineRange {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        struct LineRangeVisitor;
+
+        impl<'de> de::Visitor<'de> for LineRangeVisitor {
+            type Value = LineRange;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a line number, or a range of lines like `10-20`")
+            }
+
+            fn visit_u64<E: de::Error>(self, line: u64) -> std::result::Result<LineRange, E> {
+                Ok(LineRange {
+                    start: line,
+                    end: line,
+                })
+            }
+
+            fn visit_i64<E: de::Error>(self, line: i64) -> std::result::Result<LineRange, E> {
+                if line < 0 {
+                    return Err(E::custom(LineRangeParseError(line.to_string())));
+                }
+                self.visit_u64(line as u64)
+            }
+
+            fn visit_str<E: de::Error>(self, s: &str) -> std::result::Result<LineRange, E> {
+                s.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(LineRangeVisitor)
+    }
+}
+
+/// Whether a `path` relative to the package root stays inside of it
+pub fn is_path_inside_package(path: &Path) -> bool {
+    path.components()
+        .all(|component| matches!(component, Component::Normal(_)))
+}
+
+/// Reviewer's note about some lines of a file of the package
+///
+/// The `digest` of the file content is filled in when the review is created,
+/// so the note can be matched with the same file in other versions too.
+#[derive(Clone, TypedBuilder, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Note {
+    /// Path relative to the package root
+    pub path: PathBuf,
+    pub lines: LineRange,
+    #[builder(default)]
+    #[serde(
+        default = "Default::default",
+        skip_serializing_if = "is_vec_empty",
+        serialize_with = "as_base64",
+        deserialize_with = "from_base64"
+    )]
+    pub digest: Vec<u8>,
+    #[builder(default)]
+    #[serde(default = "Default::default")]
+    pub severity: Level,
+    pub text: String,
+}
@@ -0,0 +1,89 @@
+use crate::{
+    proof::{self, content::ValidationResult, CommonOps, Content},
+    serde_content_serialize,
+};
+use crev_common::serde::{as_rfc3339_fixed_opt, from_rfc3339_fixed_opt};
+use derive_builder::Builder;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+const CURRENT_REVOCATION_PROOF_SERIALIZATION_VERSION: i64 = -1;
+
+fn cur_version() -> i64 {
+    CURRENT_REVOCATION_PROOF_SERIALIZATION_VERSION
+}
+
+/// Body of a Revocation Proof
+///
+/// Declares the Id of its author compromised. Since it has to be
+/// signed by the Id itself, only someone holding its secret key
+/// (the owner, or whoever stole it) can create it.
+#[derive(Clone, Debug, Builder, Serialize, Deserialize)]
+pub struct Revocation {
+    #[serde(flatten)]
+    pub common: proof::Common,
+    /// Proofs of the Id created since this date are invalid;
+    /// all of them if not set
+    #[serde(
+        rename = "revoked-since",
+        skip_serializing_if = "Option::is_none",
+        default = "Default::default",
+        serialize_with = "as_rfc3339_fixed_opt",
+        deserialize_with = "from_rfc3339_fixed_opt"
+    )]
+    #[builder(default = "Default::default()")]
+    pub revoked_since: Option<proof::Date>,
+    #[serde(skip_serializing_if = "String::is_empty", default = "Default::default")]
+    #[builder(default = "Default::default()")]
+    pub comment: String,
+}
+
+impl RevocationBuilder {
+    pub fn from<VALUE: Into<crate::PublicId>>(&mut self, value: VALUE) -> &mut Self {
+        if let Some(ref mut common) = self.common {
+            common.from = value.into();
+        } else {
+            self.common = Some(proof::Common {
+                kind: Some(Revocation::KIND.into()),
+                version: cur_version(),
+                date: crev_common::now(),
+                from: value.into(),
+            });
+        }
+        self
+    }
+}
+
+impl fmt::Display for Revocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.serialize_to(f).map_err(|_| fmt::Error)
+    }
+}
+
+impl proof::CommonOps for Revocation {
+    fn common(&self) -> &proof::Common {
+        &self.common
+    }
+}
+
+impl proof::Content for Revocation {
+    fn serialize_to(&self, fmt: &mut dyn std::fmt::Write) -> fmt::Result {
+        serde_content_serialize!(self, fmt);
+        Ok(())
+    }
+
+    fn validate_data(&self) -> ValidationResult<()> {
+        self.ensure_kind_is(Self::KIND)?;
+        Ok(())
+    }
+}
+
+impl Revocation {
+    pub const KIND: &'static str = "revocation";
+
+    /// Whether a proof of the revoked Id created at `date` is no longer valid
+    pub fn revokes_proof_from(&self, date: proof::DateUtc) -> bool {
+        self.revoked_since
+            .map_or(true, |revoked_since| revoked_since <= date)
+    }
+}
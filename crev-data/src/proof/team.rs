@@ -0,0 +1,95 @@
+use crate::{
+    proof::{
+        self,
+        content::{ValidationError, ValidationResult},
+        CommonOps, Content,
+    },
+    serde_content_serialize,
+};
+use derive_builder::Builder;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+const CURRENT_TEAM_PROOF_SERIALIZATION_VERSION: i64 = -1;
+
+fn cur_version() -> i64 {
+    CURRENT_TEAM_PROOF_SERIALIZATION_VERSION
+}
+
+/// Body of a Team Proof
+///
+/// Declares its author a team Id, made of `members`. The team doesn't
+/// review on its own: a package review counts as the team's review
+/// once `threshold` members reviewed the same package digest.
+///
+/// The most recent team proof of an Id replaces the previous ones.
+#[derive(Clone, Debug, Builder, Serialize, Deserialize)]
+pub struct Team {
+    #[serde(flatten)]
+    pub common: proof::Common,
+    pub members: Vec<crate::PublicId>,
+    /// How many members have to review a package for it to count
+    /// as reviewed by the team
+    pub threshold: u64,
+    #[serde(skip_serializing_if = "String::is_empty", default = "Default::default")]
+    #[builder(default = "Default::default()")]
+    pub comment: String,
+}
+
+impl TeamBuilder {
+    pub fn from<VALUE: Into<crate::PublicId>>(&mut self, value: VALUE) -> &mut Self {
+        if let Some(ref mut common) = self.common {
+            common.from = value.into();
+        } else {
+            self.common = Some(proof::Common {
+                kind: Some(Team::KIND.into()),
+                version: cur_version(),
+                date: crev_common::now(),
+                from: value.into(),
+            });
+        }
+        self
+    }
+}
+
+impl fmt::Display for Team {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.serialize_to(f).map_err(|_| fmt::Error)
+    }
+}
+
+impl proof::CommonOps for Team {
+    fn common(&self) -> &proof::Common {
+        &self.common
+    }
+}
+
+impl proof::Content for Team {
+    fn serialize_to(&self, fmt: &mut dyn std::fmt::Write) -> fmt::Result {
+        serde_content_serialize!(self, fmt);
+        Ok(())
+    }
+
+    fn validate_data(&self) -> ValidationResult<()> {
+        self.ensure_kind_is(Self::KIND)?;
+
+        if self.threshold == 0 || self.members.len() < self.threshold as usize {
+            Err(ValidationError::InvalidTeamThreshold(
+                self.threshold,
+                self.members.len(),
+            ))?;
+        }
+        if self
+            .members
+            .iter()
+            .any(|member| member.id == self.from().id)
+        {
+            Err(ValidationError::TeamMemberOfItself)?;
+        }
+        Ok(())
+    }
+}
+
+impl Team {
+    pub const KIND: &'static str = "team";
+}
@@ -3,6 +3,7 @@ use crate::{
     serde_content_serialize, serde_draft_serialize, Error, Level, ParseError, Result,
 };
 
+use crev_common::serde::{as_rfc3339_fixed_opt, from_rfc3339_fixed_opt};
 use derive_builder::Builder;
 use serde::{Deserialize, Serialize};
 
@@ -79,6 +80,16 @@ pub struct Trust {
     #[serde(skip_serializing_if = "String::is_empty", default = "Default::default")]
     #[builder(default = "Default::default()")]
     pub comment: String,
+    /// Trust expires after this date, unless re-signed
+    #[serde(
+        rename = "valid-until",
+        skip_serializing_if = "Option::is_none",
+        default = "Default::default",
+        serialize_with = "as_rfc3339_fixed_opt",
+        deserialize_with = "from_rfc3339_fixed_opt"
+    )]
+    #[builder(default = "Default::default()")]
+    pub valid_until: Option<proof::Date>,
 }
 
 impl TrustBuilder {
@@ -116,6 +127,11 @@ impl proof::CommonOps for Trust {
 
 impl Trust {
     pub const KIND: &'static str = "trust";
+
+    /// Whether `valid-until` of this proof has already passed
+    pub fn is_expired_at(&self, now: proof::DateUtc) -> bool {
+        self.valid_until.map_or(false, |valid_until| valid_until < now)
+    }
 }
 
 /// Like `Trust` but serializes for interactive editing
@@ -124,6 +140,14 @@ pub struct Draft {
     pub trust: TrustLevel,
     #[serde(default = "Default::default", skip_serializing_if = "String::is_empty")]
     comment: String,
+    #[serde(
+        rename = "valid-until",
+        default = "Default::default",
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "as_rfc3339_fixed_opt",
+        deserialize_with = "from_rfc3339_fixed_opt"
+    )]
+    valid_until: Option<proof::Date>,
 }
 
 impl From<Trust> for Draft {
@@ -131,6 +155,7 @@ impl From<Trust> for Draft {
         Draft {
             trust: trust.trust,
             comment: trust.comment,
+            valid_until: trust.valid_until,
         }
     }
 }
@@ -183,6 +208,7 @@ impl proof::ContentWithDraft for Trust {
         let mut copy = self.clone();
         copy.trust = draft.trust;
         copy.comment = draft.comment;
+        copy.valid_until = draft.valid_until;
 
         copy.validate_data()?;
         Ok(copy)
@@ -0,0 +1,51 @@
+//! Signing proofs
+//!
+//! All Id types use Ed25519 keys, so a `Signer` only has to make raw
+//! Ed25519 signatures; formatting them for the type of the Id
+//! (plain, OpenPGP or SSH signatures) is common to all of them.
+//!
+//! `UnlockedId` signs with the secret key in memory, while the signers
+//! in this module delegate to where the key is held, so that it never
+//! has to be read.
+use crate::{id::IdError, openpgp, ssh, Id, PublicId};
+
+#[cfg(unix)]
+pub mod ssh_agent;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SignerError {
+    #[error("Signing agent: {}", _0)]
+    Agent(Box<str>),
+    #[error("ssh-agent: {}", _0)]
+    SshAgent(Box<str>),
+    #[error("SSH_AUTH_SOCK is not set; is ssh-agent running?")]
+    SshAgentNotRunning,
+    #[error(transparent)]
+    Id(#[from] IdError),
+    #[error("I/O: {}", _0)]
+    IO(#[from] std::io::Error),
+}
+
+/// Something that can sign proofs as an Id
+pub trait Signer {
+    /// The Id signing
+    fn public_id(&self) -> &PublicId;
+
+    /// Raw Ed25519 signature of `data`
+    fn sign_ed25519(&self, data: &[u8]) -> Result<[u8; 64], SignerError>;
+
+    /// Signature of `msg`, in the format of the Id type
+    fn sign(&self, msg: &[u8]) -> Result<Vec<u8>, SignerError> {
+        let sign_ed25519 = |data: &[u8]| self.sign_ed25519(data);
+        match &self.public_id().id {
+            Id::Crev { .. } => Ok(sign_ed25519(msg)?.to_vec()),
+            Id::OpenPgp { key } => openpgp::sign(
+                key,
+                msg,
+                crev_common::now().timestamp() as u32,
+                sign_ed25519,
+            ),
+            Id::Ssh { key } => ssh::sign(key, msg, sign_ed25519),
+        }
+    }
+}
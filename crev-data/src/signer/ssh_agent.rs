@@ -0,0 +1,111 @@
+//! Signing with keys held by `ssh-agent`
+use super::{Signer, SignerError};
+use crate::{
+    ssh::{self, write_string, Reader},
+    PublicId,
+};
+use std::{
+    io::{Read, Write},
+    os::unix::net::UnixStream,
+    path::PathBuf,
+};
+
+const SSH_AGENT_FAILURE: u8 = 5;
+const SSH_AGENTC_REQUEST_IDENTITIES: u8 = 11;
+const SSH_AGENT_IDENTITIES_ANSWER: u8 = 12;
+const SSH_AGENTC_SIGN_REQUEST: u8 = 13;
+const SSH_AGENT_SIGN_RESPONSE: u8 = 14;
+
+fn protocol_error() -> SignerError {
+    SignerError::SshAgent("invalid response".into())
+}
+
+/// Signs with a key held by `ssh-agent`, talking to it over its socket
+pub struct SshAgentSigner {
+    id: PublicId,
+    key_blob: Vec<u8>,
+    socket: PathBuf,
+}
+
+impl SshAgentSigner {
+    /// Sign as `id`, using the agent listening on `socket`
+    pub fn new(id: PublicId, socket: PathBuf) -> Result<Self, SignerError> {
+        let key_blob = ssh::public_key_blob(id.id.ed25519_public_key()?.as_bytes());
+        Ok(Self {
+            id,
+            key_blob,
+            socket,
+        })
+    }
+
+    /// Sign as `id`, using the agent of the `SSH_AUTH_SOCK` environment variable
+    pub fn from_env(id: PublicId) -> Result<Self, SignerError> {
+        let socket = std::env::var_os("SSH_AUTH_SOCK").ok_or(SignerError::SshAgentNotRunning)?;
+        Self::new(id, socket.into())
+    }
+
+    /// Whether the agent holds the key of the Id
+    pub fn has_key(&self) -> Result<bool, SignerError> {
+        let response = self.request(SSH_AGENTC_REQUEST_IDENTITIES, &[])?;
+        let mut reader = Reader(&response);
+        if reader.take(1) != Some(&[SSH_AGENT_IDENTITIES_ANSWER]) {
+            return Err(protocol_error());
+        }
+        let count = reader.u32().ok_or_else(protocol_error)?;
+        for _ in 0..count {
+            let key_blob = reader.string().ok_or_else(protocol_error)?;
+            let _comment = reader.string().ok_or_else(protocol_error)?;
+            if key_blob == self.key_blob.as_slice() {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Send a message to the agent, and read its response
+    fn request(&self, message_type: u8, payload: &[u8]) -> Result<Vec<u8>, SignerError> {
+        let mut message = vec![message_type];
+        message.extend_from_slice(payload);
+        let mut stream = UnixStream::connect(&self.socket)?;
+        stream.write_all(&(message.len() as u32).to_be_bytes())?;
+        stream.write_all(&message)?;
+
+        let mut len = [0; 4];
+        stream.read_exact(&mut len)?;
+        let mut response = vec![0; u32::from_be_bytes(len) as usize];
+        stream.read_exact(&mut response)?;
+        if response.first() == Some(&SSH_AGENT_FAILURE) {
+            return Err(SignerError::SshAgent("request failed".into()));
+        }
+        Ok(response)
+    }
+}
+
+impl Signer for SshAgentSigner {
+    fn public_id(&self) -> &PublicId {
+        &self.id
+    }
+
+    fn sign_ed25519(&self, data: &[u8]) -> Result<[u8; 64], SignerError> {
+        let mut payload = vec![];
+        write_string(&mut payload, &self.key_blob);
+        write_string(&mut payload, data);
+        payload.extend_from_slice(&0u32.to_be_bytes());
+        let response = self.request(SSH_AGENTC_SIGN_REQUEST, &payload)?;
+
+        let mut reader = Reader(&response);
+        if reader.take(1) != Some(&[SSH_AGENT_SIGN_RESPONSE]) {
+            return Err(protocol_error());
+        }
+        let mut signature_blob = Reader(reader.string().ok_or_else(protocol_error)?);
+        if signature_blob.string() != Some(b"ssh-ed25519") {
+            return Err(protocol_error());
+        }
+        let mut signature = [0; 64];
+        match signature_blob.string() {
+            Some(bytes) if bytes.len() == signature.len() => signature.copy_from_slice(bytes),
+            _ => return Err(protocol_error()),
+        }
+        Ok(signature)
+    }
+}
@@ -0,0 +1,286 @@
+//! Advisories of a RustSec advisory database
+//!
+//! [RustSec advisories](https://github.com/rustsec/advisory-db) aren't
+//! crev proofs, but Markdown files with a TOML front matter (or plain
+//! TOML files in the older format), one per advisory. They are converted
+//! to unsigned package reviews with an `advisory`, all authored by an Id
+//! derived from the location of the database, so they can be queried
+//! together with the advisories of crev reviewers.
+use crate::{Error, Result};
+use chrono::{FixedOffset, NaiveDate, TimeZone};
+use crev_data::{
+    proof::{self, review},
+    Id, Level, PublicId, Url,
+};
+use log::debug;
+use semver::{Version, VersionReq};
+use serde::Deserialize;
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    path::Path,
+};
+
+/// Advisories in RustSec are only about crates from crates.io
+const CRATES_IO_SOURCE: &str = "https://crates.io";
+
+#[derive(Deserialize)]
+struct AdvisoryFile {
+    advisory: AdvisoryMetadata,
+    #[serde(default)]
+    versions: AdvisoryVersions,
+}
+
+#[derive(Deserialize)]
+struct AdvisoryMetadata {
+    id: String,
+    package: String,
+    date: String,
+    #[serde(default)]
+    title: Option<String>,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    url: Option<String>,
+    #[serde(default)]
+    aliases: Vec<String>,
+    #[serde(default)]
+    cvss: Option<String>,
+    #[serde(default)]
+    informational: Option<String>,
+    #[serde(default)]
+    withdrawn: Option<String>,
+    // versions, in the older format
+    #[serde(default)]
+    patched_versions: Vec<String>,
+    #[serde(default)]
+    unaffected_versions: Vec<String>,
+}
+
+#[derive(Deserialize, Default)]
+struct AdvisoryVersions {
+    #[serde(default)]
+    patched: Vec<String>,
+    #[serde(default)]
+    unaffected: Vec<String>,
+}
+
+/// Id that advisories of the database at `location` (a path or git URL) are authored by
+pub fn advisory_db_public_id(location: &str) -> PublicId {
+    let id = Id::new_crev(crev_common::blake2b256sum(location.as_bytes()))
+        .expect("digest has the length of an Id");
+    PublicId::new(id, Url::new_git(location))
+}
+
+/// Read all the advisories of a database checked out at `path`
+///
+/// Withdrawn advisories are skipped, and so are the files that
+/// can't be parsed (these are only logged). Advisories filed under
+/// the same version of a crate are in one review, as an author has
+/// only one review of a package version.
+pub fn read_advisory_db(path: &Path, location: &str) -> Result<Vec<review::Package>> {
+    let from = advisory_db_public_id(location);
+    let crates_path = path.join("crates");
+    let root = if crates_path.is_dir() {
+        crates_path
+    } else {
+        path.to_owned()
+    };
+
+    let mut reviews: Vec<review::Package> = vec![];
+    let mut review_idx_by_package = HashMap::new();
+    for entry in walkdir::WalkDir::new(root)
+        .into_iter()
+        // skip dotfiles, .git dir
+        .filter_entry(|e| e.file_name().to_str().map_or(true, |f| !f.starts_with('.')))
+    {
+        let entry = entry.map_err(|e| {
+            Error::ErrorIteratingLocalProofStore(Box::new((path.to_owned(), e.to_string())))
+        })?;
+        if !is_advisory_file(entry.path()) {
+            continue;
+        }
+        match read_advisory(entry.path(), &from) {
+            Ok(Some(review)) => match review_idx_by_package.entry(review.package.id.clone()) {
+                Entry::Occupied(entry) => merge_advisory(&mut reviews[*entry.get()], review),
+                Entry::Vacant(entry) => {
+                    entry.insert(reviews.len());
+                    reviews.push(review);
+                }
+            },
+            Ok(None) => {}
+            Err(e) => debug!("Skipping advisory {}: {}", entry.path().display(), e),
+        }
+    }
+    Ok(reviews)
+}
+
+/// Add the advisories of `other` review of the same package version to `review`
+fn merge_advisory(review: &mut review::Package, other: review::Package) {
+    review.common.date = review.common.date.max(other.common.date);
+    review.flags.unmaintained |= other.flags.unmaintained;
+    review.advisories.extend(other.advisories);
+}
+
+fn is_advisory_file(path: &Path) -> bool {
+    let is_rustsec_id = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .map_or(false, |stem| stem.starts_with("RUSTSEC-"));
+    let ext = path.extension().and_then(|ext| ext.to_str());
+    is_rustsec_id && (ext == Some("md") || ext == Some("toml")) && path.is_file()
+}
+
+fn invalid_advisory(path: &Path, msg: impl ToString) -> Error {
+    Error::InvalidAdvisory(Box::new((path.to_owned(), msg.to_string())))
+}
+
+/// Parse an advisory file into a review, or `None` if it was withdrawn
+fn read_advisory(path: &Path, from: &PublicId) -> Result<Option<review::Package>> {
+    let content = std::fs::read_to_string(path)?;
+    let (front_matter, markdown) = if path.extension().map_or(false, |ext| ext == "md") {
+        split_front_matter(&content)
+            .ok_or_else(|| invalid_advisory(path, "no TOML front matter"))?
+    } else {
+        (content.as_str(), "")
+    };
+    let AdvisoryFile {
+        advisory: metadata,
+        versions,
+    } = toml::from_str(front_matter).map_err(|e| invalid_advisory(path, e))?;
+
+    if metadata.withdrawn.is_some() {
+        return Ok(None);
+    }
+
+    let patched: Vec<String> = versions
+        .patched
+        .into_iter()
+        .chain(metadata.patched_versions)
+        .collect();
+    let unaffected: Vec<String> = versions
+        .unaffected
+        .into_iter()
+        .chain(metadata.unaffected_versions)
+        .collect();
+    let parse_req = |req: &String| VersionReq::parse(req).map_err(|e| invalid_advisory(path, e));
+    let advisory = review::Advisory::builder()
+        .ids(vec![metadata.id.clone()])
+        .severity(match metadata.informational.as_deref() {
+            None => Level::High,
+            Some("unsound") => Level::Medium,
+            Some(_) => Level::Low,
+        })
+        .affected(Some(VersionReq::any()))
+        // versions not affected are as good as patched ones
+        .patched(
+            patched
+                .iter()
+                .chain(unaffected.iter())
+                .map(parse_req)
+                .collect::<Result<_>>()?,
+        )
+        .cvss(metadata.cvss)
+        .references(metadata.aliases)
+        .comment(advisory_comment(
+            &metadata.id,
+            metadata.title.as_deref(),
+            metadata.description.as_deref().unwrap_or(markdown),
+            metadata.url.as_deref(),
+        ))
+        .build();
+
+    let date = NaiveDate::parse_from_str(&metadata.date, "%Y-%m-%d")
+        .map_err(|e| invalid_advisory(path, e))?;
+
+    let mut review = review::PackageBuilder::default()
+        .from(from.to_owned())
+        .package(proof::PackageInfo {
+            id: proof::PackageVersionId::new(
+                CRATES_IO_SOURCE.into(),
+                metadata.package,
+                first_patched_version(&patched),
+            ),
+            digest: vec![],
+            digest_type: proof::default_digest_type(),
+            revision: "".into(),
+            revision_type: proof::default_revision_type(),
+        })
+        .advisories(vec![advisory])
+        .build()
+        .map_err(|e| invalid_advisory(path, e))?;
+    review.common.date = FixedOffset::east(0).from_utc_datetime(&date.and_hms(0, 0, 0));
+    review.flags.unmaintained = metadata.informational.as_deref() == Some("unmaintained");
+    Ok(Some(review))
+}
+
+/// Split a Markdown advisory into its ```` ```toml ```` front matter and the rest
+fn split_front_matter(content: &str) -> Option<(&str, &str)> {
+    let content = content.trim_start().strip_prefix("```toml")?;
+    let end = content.find("\n```")?;
+    Some((&content[..end], &content[end + 4..]))
+}
+
+fn advisory_comment(id: &str, title: Option<&str>, description: &str, url: Option<&str>) -> String {
+    let mut description = description.trim();
+    let mut title = title.map(str::to_owned);
+    // in Markdown advisories the title is the first heading
+    if title.is_none() {
+        if let Some(heading) = description
+            .lines()
+            .next()
+            .and_then(|line| line.strip_prefix("# "))
+        {
+            title = Some(heading.trim().to_owned());
+            description = description
+                .find('\n')
+                .map_or("", |end| description[end..].trim());
+        }
+    }
+
+    let mut comment = String::new();
+    if let Some(title) = title {
+        comment.push_str(&title);
+        comment.push_str("\n\n");
+    }
+    if !description.is_empty() {
+        comment.push_str(description);
+        comment.push_str("\n\n");
+    }
+    comment.push_str(&format!("https://rustsec.org/advisories/{}", id));
+    if let Some(url) = url {
+        comment.push('\n');
+        comment.push_str(url);
+    }
+    comment
+}
+
+/// The earliest version with the fix, which the advisory is filed under
+///
+/// Like for advisories of crev reviewers, that's the version to upgrade to.
+/// `0.0.0` if there's no fix.
+fn first_patched_version(patched: &[String]) -> Version {
+    patched
+        .iter()
+        .flat_map(|req| req.split(','))
+        .filter_map(|comparator| {
+            let comparator = comparator.trim();
+            let version = comparator
+                .strip_prefix(">=")
+                .or_else(|| comparator.strip_prefix('^'))
+                .or_else(|| comparator.strip_prefix('='))
+                .or_else(|| {
+                    if comparator.starts_with(|c: char| c.is_ascii_digit()) {
+                        Some(comparator)
+                    } else {
+                        None
+                    }
+                })?
+                .trim();
+            Version::parse(version)
+                .or_else(|_| Version::parse(&format!("{}.0", version)))
+                .or_else(|_| Version::parse(&format!("{}.0.0", version)))
+                .ok()
+        })
+        .min()
+        .unwrap_or_else(|| Version::new(0, 0, 0))
+}
@@ -0,0 +1,320 @@
+//! Agent keeping Ids unlocked for a while
+//!
+//! Like `ssh-agent` or `gpg-agent`: the agent process unlocks an Id once,
+//! and then signs for it when asked to over a Unix socket, until it hasn't
+//! been used for the timeout. This way the passphrase doesn't have to be
+//! entered for every proof, and the secret key never leaves the agent.
+use crate::{Error, Result};
+use crev_data::{
+    id::UnlockedId,
+    signer::{Signer, SignerError},
+    Id, PublicId,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Read, Write},
+    net::Shutdown,
+    os::unix::{
+        fs::{DirBuilderExt, MetadataExt, PermissionsExt},
+        io::AsRawFd,
+        net::{UnixListener, UnixStream},
+    },
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+#[derive(Serialize, Deserialize)]
+enum Request {
+    Unlock { id: Id, passphrase: String },
+    Sign { id: Id, data: Vec<u8> },
+    Status,
+    Lock,
+}
+
+#[derive(Serialize, Deserialize)]
+enum Response {
+    Ok,
+    Signature(Vec<u8>),
+    /// Unlocked Ids, with the seconds until they get locked
+    Status(Vec<(Id, u64)>),
+    NotUnlocked,
+    Error(String),
+}
+
+type UnlockedIds = Arc<Mutex<HashMap<Id, (UnlockedId, Instant)>>>;
+
+/// How long a client has to send its request and read the response,
+/// so that a stuck client can't block the agent
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Remove the Ids that haven't been used for `timeout`
+fn lock_expired(ids: &UnlockedIds, timeout: Duration) {
+    ids.lock()
+        .expect("lock")
+        .retain(|_, (_, last_used)| last_used.elapsed() < timeout);
+}
+
+/// Uid of the current user
+fn current_uid() -> u32 {
+    unsafe { libc::geteuid() }
+}
+
+/// Uid of the user of the process on the other end of `stream`
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn peer_uid(stream: &UnixStream) -> io::Result<u32> {
+    let mut cred = libc::ucred {
+        pid: 0,
+        uid: 0,
+        gid: 0,
+    };
+    let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+    let ret = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut cred as *mut libc::ucred as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if ret != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(cred.uid)
+}
+
+/// Uid of the user of the process on the other end of `stream`
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn peer_uid(stream: &UnixStream) -> io::Result<u32> {
+    let mut uid = 0;
+    let mut gid = 0;
+    if unsafe { libc::getpeereid(stream.as_raw_fd(), &mut uid, &mut gid) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(uid)
+}
+
+/// Create `dir` accessible only to the current user, if it doesn't exist
+///
+/// The socket is created inside it, so that other users can't connect to it,
+/// not even before its own permissions are restricted.
+fn create_private_dir(dir: &Path) -> Result<()> {
+    fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir)?;
+    let metadata = fs::symlink_metadata(dir)?;
+    if !metadata.is_dir() || metadata.uid() != current_uid() {
+        return Err(Error::AgentDirNotPrivate(dir.into()));
+    }
+    fs::set_permissions(dir, fs::Permissions::from_mode(0o700))?;
+    Ok(())
+}
+
+/// Run the agent, listening on `socket_path`, until killed
+///
+/// Ids are unlocked with `unlock` (given the passphrase), and locked
+/// again when not used for `timeout`. The directory of the socket is made
+/// private to the current user, and only clients of the same user are served.
+pub fn serve(
+    socket_path: &Path,
+    timeout: Duration,
+    unlock: impl Fn(&Id, &str) -> Result<UnlockedId>,
+) -> Result<()> {
+    if AgentClient::new(socket_path.to_owned()).is_running() {
+        return Err(Error::AgentAlreadyRunning(socket_path.into()));
+    }
+    if let Some(dir) = socket_path.parent() {
+        create_private_dir(dir)?;
+    }
+    if socket_path.exists() {
+        // left by an agent that didn't exit cleanly
+        fs::remove_file(socket_path)?;
+    }
+    let listener = UnixListener::bind(socket_path)?;
+    crate::util::chmod_path_to_600(socket_path)?;
+
+    let ids: UnlockedIds = Arc::default();
+    {
+        let ids = ids.clone();
+        std::thread::spawn(move || loop {
+            std::thread::sleep(Duration::from_secs(1));
+            lock_expired(&ids, timeout);
+        });
+    }
+
+    for stream in listener.incoming() {
+        let mut stream = stream?;
+        match peer_uid(&stream) {
+            Ok(uid) if uid == current_uid() => {}
+            _ => continue,
+        }
+        stream.set_read_timeout(Some(CONNECTION_TIMEOUT))?;
+        stream.set_write_timeout(Some(CONNECTION_TIMEOUT))?;
+        let mut request = vec![];
+        if stream.read_to_end(&mut request).is_err() {
+            continue;
+        }
+        lock_expired(&ids, timeout);
+        let response = match serde_cbor::from_slice(&request) {
+            Ok(request) => handle_request(&unlock, &ids, timeout, request),
+            Err(e) => Response::Error(e.to_string()),
+        };
+        // the client might have gone away; nothing to do about it
+        let _ = stream.write_all(&serde_cbor::to_vec(&response)?);
+    }
+    Ok(())
+}
+
+fn handle_request(
+    unlock: &impl Fn(&Id, &str) -> Result<UnlockedId>,
+    ids: &UnlockedIds,
+    timeout: Duration,
+    request: Request,
+) -> Response {
+    let mut ids = ids.lock().expect("lock");
+    match request {
+        Request::Unlock { id, passphrase } => match unlock(&id, &passphrase) {
+            Ok(unlocked) => {
+                ids.insert(id, (unlocked, Instant::now()));
+                Response::Ok
+            }
+            Err(e) => Response::Error(e.to_string()),
+        },
+        Request::Sign { id, data } => match ids.get_mut(&id) {
+            Some((unlocked, last_used)) => {
+                *last_used = Instant::now();
+                match unlocked.sign_ed25519(&data) {
+                    Ok(signature) => Response::Signature(signature.to_vec()),
+                    Err(e) => Response::Error(e.to_string()),
+                }
+            }
+            None => Response::NotUnlocked,
+        },
+        Request::Status => Response::Status(
+            ids.iter()
+                .map(|(id, (_, last_used))| {
+                    let left = timeout.checked_sub(last_used.elapsed()).unwrap_or_default();
+                    (id.clone(), left.as_secs())
+                })
+                .collect(),
+        ),
+        Request::Lock => {
+            ids.clear();
+            Response::Ok
+        }
+    }
+}
+
+/// Client of an agent
+#[derive(Clone)]
+pub struct AgentClient {
+    socket_path: PathBuf,
+}
+
+impl AgentClient {
+    pub fn new(socket_path: PathBuf) -> Self {
+        Self { socket_path }
+    }
+
+    /// Whether an agent is listening on the socket
+    pub fn is_running(&self) -> bool {
+        UnixStream::connect(&self.socket_path).is_ok()
+    }
+
+    fn request(&self, request: &Request) -> Result<Response> {
+        let mut stream = UnixStream::connect(&self.socket_path)?;
+        stream.write_all(&serde_cbor::to_vec(request)?)?;
+        stream.shutdown(Shutdown::Write)?;
+        let mut response = vec![];
+        stream.read_to_end(&mut response)?;
+        match serde_cbor::from_slice(&response)? {
+            Response::Error(e) => Err(Error::Agent(e.into())),
+            response => Ok(response),
+        }
+    }
+
+    /// Have the agent unlock `id` with `passphrase`
+    pub fn unlock(&self, id: &Id, passphrase: &str) -> Result<()> {
+        let request = Request::Unlock {
+            id: id.clone(),
+            passphrase: passphrase.to_owned(),
+        };
+        match self.request(&request)? {
+            Response::Ok => Ok(()),
+            _ => Err(Error::Agent("unexpected response".into())),
+        }
+    }
+
+    /// Raw Ed25519 signature of `data` made by `id`, if it's unlocked
+    pub fn sign(&self, id: &Id, data: &[u8]) -> Result<Option<[u8; 64]>> {
+        let request = Request::Sign {
+            id: id.clone(),
+            data: data.to_owned(),
+        };
+        match self.request(&request)? {
+            Response::Signature(bytes) if bytes.len() == 64 => {
+                let mut signature = [0; 64];
+                signature.copy_from_slice(&bytes);
+                Ok(Some(signature))
+            }
+            Response::NotUnlocked => Ok(None),
+            _ => Err(Error::Agent("unexpected response".into())),
+        }
+    }
+
+    /// Ids held unlocked, with the time left until they get locked
+    pub fn status(&self) -> Result<Vec<(Id, Duration)>> {
+        match self.request(&Request::Status)? {
+            Response::Status(ids) => Ok(ids
+                .into_iter()
+                .map(|(id, secs)| (id, Duration::from_secs(secs)))
+                .collect()),
+            _ => Err(Error::Agent("unexpected response".into())),
+        }
+    }
+
+    /// Whether `id` is unlocked
+    pub fn is_unlocked(&self, id: &Id) -> Result<bool> {
+        Ok(self.status()?.iter().any(|(unlocked, _)| unlocked == id))
+    }
+
+    /// Lock all Ids
+    pub fn lock(&self) -> Result<()> {
+        match self.request(&Request::Lock)? {
+            Response::Ok => Ok(()),
+            _ => Err(Error::Agent("unexpected response".into())),
+        }
+    }
+}
+
+/// Signs with an Id unlocked in the agent
+pub struct AgentSigner {
+    client: AgentClient,
+    id: PublicId,
+}
+
+impl AgentSigner {
+    pub fn new(client: AgentClient, id: PublicId) -> Self {
+        Self { client, id }
+    }
+}
+
+impl Signer for AgentSigner {
+    fn public_id(&self) -> &PublicId {
+        &self.id
+    }
+
+    fn sign_ed25519(&self, data: &[u8]) -> std::result::Result<[u8; 64], SignerError> {
+        match self.client.sign(&self.id.id, data) {
+            Ok(Some(signature)) => Ok(signature),
+            Ok(None) => Err(SignerError::Agent(
+                format!("{} is no longer unlocked", self.id.id).into(),
+            )),
+            Err(e) => Err(SignerError::Agent(e.to_string().into())),
+        }
+    }
+}
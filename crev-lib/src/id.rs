@@ -2,11 +2,15 @@ use crate::{Error, Result};
 use argon2::{self, Config};
 use crev_common::{
     rand::random_vec,
-    serde::{as_base64, from_base64},
+    serde::{as_base64, as_base64_opt, from_base64, from_base64_opt},
 };
-use crev_data::id::{PublicId, UnlockedId};
+use crev_data::id::{Id, IdError, PublicId, UnlockedId};
 use serde::{Deserialize, Serialize};
-use std::{self, fmt, io::Write, path::Path};
+use std::{
+    self, fmt,
+    io::Write,
+    path::{Path, PathBuf},
+};
 
 const CURRENT_LOCKED_ID_SERIALIZATION_VERSION: i64 = -1;
 pub type PassphraseFn<'a> = &'a dyn Fn() -> std::io::Result<String>;
@@ -32,14 +36,35 @@ pub struct LockedId {
     #[serde(serialize_with = "as_base64", deserialize_with = "from_base64")]
     #[serde(rename = "public-key")]
     pub public_key: Vec<u8>,
+    /// Public key packet of OpenPGP Ids
+    #[serde(
+        rename = "openpgp-public-key",
+        skip_serializing_if = "Option::is_none",
+        default,
+        serialize_with = "as_base64_opt",
+        deserialize_with = "from_base64_opt"
+    )]
+    pub openpgp_public_key: Option<Vec<u8>>,
+    /// Private key file of SSH Ids, which have no sealed secret key
+    #[serde(
+        rename = "ssh-key-file",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub ssh_key_file: Option<PathBuf>,
     #[serde(serialize_with = "as_base64", deserialize_with = "from_base64")]
-    #[serde(rename = "sealed-secret-key")]
+    #[serde(
+        rename = "sealed-secret-key",
+        skip_serializing_if = "Vec::is_empty",
+        default
+    )]
     sealed_secret_key: Vec<u8>,
 
     #[serde(serialize_with = "as_base64", deserialize_with = "from_base64")]
-    #[serde(rename = "seal-nonce")]
+    #[serde(rename = "seal-nonce", skip_serializing_if = "Vec::is_empty", default)]
     seal_nonce: Vec<u8>,
-    pass: PassConfig,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pass: Option<PassConfig>,
 }
 
 impl fmt::Display for LockedId {
@@ -61,6 +86,10 @@ impl LockedId {
     pub fn from_unlocked_id(unlocked_id: &UnlockedId, passphrase: &str) -> Result<LockedId> {
         use miscreant::aead::Aead;
 
+        if let Id::Ssh { .. } = unlocked_id.as_public_id().id {
+            return Err(Error::SshIdNotLockable);
+        }
+
         let config = Config {
             variant: argon2::Variant::Argon2id,
             version: argon2::Version::Version13,
@@ -87,24 +116,65 @@ impl LockedId {
         Ok(LockedId {
             version: CURRENT_LOCKED_ID_SERIALIZATION_VERSION,
             public_key: unlocked_id.keypair.public.to_bytes().to_vec(),
+            openpgp_public_key: match &unlocked_id.as_public_id().id {
+                Id::OpenPgp { key } => Some(key.clone()),
+                _ => None,
+            },
+            ssh_key_file: None,
             sealed_secret_key: siv.seal(&seal_nonce, &[], unlocked_id.keypair.secret.as_bytes()),
             seal_nonce,
             url: unlocked_id.url().clone(),
-            pass: PassConfig {
+            pass: Some(PassConfig {
                 salt: pwsalt,
                 iterations: config.time_cost,
                 memory_size: config.mem_cost,
                 version: 0x13,
                 lanes: Some(config.lanes),
                 variant: config.variant.as_lowercase_str().to_string(),
-            },
+            }),
+        })
+    }
+
+    /// Create an SSH Id, using the (Ed25519) private key in `key_file`
+    ///
+    /// The key is not copied: it's read from the file every time
+    /// the Id is unlocked, using the passphrase of the key file (if any).
+    pub fn new_ssh(url: crev_data::Url, key_file: &Path) -> Result<LockedId> {
+        let key_file = key_file.canonicalize()?;
+        let private_key = read_ssh_private_key(&key_file)?;
+        Ok(LockedId {
+            version: CURRENT_LOCKED_ID_SERIALIZATION_VERSION,
+            url,
+            public_key: private_key.public_key().to_bytes().to_vec(),
+            openpgp_public_key: None,
+            ssh_key_file: Some(key_file),
+            sealed_secret_key: vec![],
+            seal_nonce: vec![],
+            pass: None,
         })
     }
 
     /// Extract only the public identity part from all data
     pub fn to_public_id(&self) -> PublicId {
-        PublicId::new_from_pubkey(self.public_key.to_owned(), self.url.clone())
-            .expect("Invalid locked id.")
+        match (&self.openpgp_public_key, &self.ssh_key_file) {
+            (Some(key), _) => {
+                Id::new_openpgp(key.to_owned()).map(|id| PublicId::new(id, self.url.clone()))
+            }
+            (None, Some(_)) => Id::new_ssh(self.public_key.to_owned())
+                .map(|id| PublicId::new(id, self.url.clone())),
+            (None, None) => PublicId::new_from_pubkey(self.public_key.to_owned(), self.url.clone()),
+        }
+        .expect("Invalid locked id.")
+    }
+
+    /// Whether `to_unlocked` needs a passphrase
+    ///
+    /// Only SSH Ids with unencrypted key files don't.
+    pub fn needs_passphrase(&self) -> Result<bool> {
+        match &self.ssh_key_file {
+            Some(key_file) => Ok(read_ssh_private_key(key_file)?.is_encrypted()),
+            None => Ok(true),
+        }
     }
 
     pub fn pub_key_as_base64(&self) -> String {
@@ -134,10 +204,23 @@ impl LockedId {
     }
 
     pub fn to_unlocked(&self, passphrase: &str) -> Result<UnlockedId> {
+        if let Some(key_file) = &self.ssh_key_file {
+            let secret_key = read_ssh_private_key(key_file)?
+                .unlock(passphrase)
+                .map_err(IdError::from)?;
+            let result = UnlockedId::new_ssh(self.url.clone(), secret_key.as_bytes().to_vec())?;
+            if self.public_key != result.keypair.public.to_bytes() {
+                Err(Error::PubKeyMismatch)?;
+            }
+            return Ok(result);
+        }
+
         let LockedId {
             ref version,
             ref url,
             ref public_key,
+            ref openpgp_public_key,
+            ssh_key_file: _,
             ref sealed_secret_key,
             ref seal_nonce,
             ref pass,
@@ -148,6 +231,7 @@ impl LockedId {
             }
             use miscreant::aead::Aead;
 
+            let pass = pass.as_ref().ok_or(Error::SealedSecretKeyMissing)?;
             let mut config = Config {
                 variant: argon2::Variant::from_str(&pass.variant)?,
                 version: argon2::Version::Version13,
@@ -181,7 +265,10 @@ impl LockedId {
 
             assert!(!secret_key.is_empty());
 
-            let result = UnlockedId::new(url.to_owned(), secret_key)?;
+            let result = match openpgp_public_key {
+                None => UnlockedId::new(url.to_owned(), secret_key)?,
+                Some(key) => UnlockedId::new_openpgp(url.to_owned(), key.to_owned(), secret_key)?,
+            };
             if public_key != &result.keypair.public.to_bytes() {
                 Err(Error::PubKeyMismatch)?;
             }
@@ -189,3 +276,7 @@ impl LockedId {
         }
     }
 }
+
+fn read_ssh_private_key(key_file: &Path) -> Result<crev_data::ssh::PrivateKey> {
+    Ok(crev_data::ssh::PrivateKey::from_bytes(&std::fs::read(key_file)?).map_err(IdError::from)?)
+}
@@ -1,26 +1,33 @@
 #![type_length_limit = "10709970"]
 
 pub mod activity;
+pub mod advisory_db;
+#[cfg(unix)]
+pub mod agent;
 pub mod id;
 pub mod local;
+pub mod osv;
 pub mod proof;
+mod proof_cache;
 pub mod repo;
 pub mod staging;
 pub mod util;
 pub use crate::local::Local;
 pub use activity::{ReviewActivity, ReviewMode};
+use chrono::{DateTime, Utc};
 use crev_data::{
     self,
     id::IdError,
     proof::{
         review::{self, Rating},
         trust::TrustLevel,
-        CommonOps,
+        CommonOps, WithReview,
     },
     Digest, Id,
 };
 pub use crev_wot::TrustDistanceParams;
 use semver::Version;
+use serde::{Deserialize, Serialize};
 use std::{
     collections::{HashMap, HashSet},
     fmt,
@@ -59,6 +66,21 @@ pub enum Error {
     #[error("Incorrect passphrase")]
     IncorrectPassphrase,
 
+    #[error("SSH Ids can't be locked with a passphrase; their keys stay in key files")]
+    SshIdNotLockable,
+
+    #[error("Id file has no sealed secret key")]
+    SealedSecretKeyMissing,
+
+    #[error("Agent already running at {}", _0.display())]
+    AgentAlreadyRunning(Box<Path>),
+
+    #[error("Agent socket directory {} is not a directory of the current user", _0.display())]
+    AgentDirNotPrivate(Box<Path>),
+
+    #[error("Agent: {}", _0)]
+    Agent(Box<str>),
+
     #[error("Current Id not set")]
     CurrentIDNotSet,
 
@@ -71,6 +93,9 @@ pub enum Error {
     #[error("Error iterating local ProofStore at {}: {}", _0.0.display(), _0.1)]
     ErrorIteratingLocalProofStore(Box<(PathBuf, String)>),
 
+    #[error("Invalid advisory {}: {}", _0.0.display(), _0.1)]
+    InvalidAdvisory(Box<(PathBuf, String)>),
+
     #[error("File {} not current. Review again use `crev add` to update.", _0.display())]
     FileNotCurrent(Box<Path>),
 
@@ -118,6 +143,9 @@ pub enum Error {
 
     #[error(transparent)]
     Id(#[from] IdError),
+
+    #[error(transparent)]
+    Signer(#[from] crev_data::signer::SignerError),
 }
 
 type Result<T> = std::result::Result<T, Error>;
@@ -174,6 +202,8 @@ pub struct VerificationRequirements {
     pub understanding: crev_data::Level,
     pub thoroughness: crev_data::Level,
     pub redundancy: u64,
+    /// Reviews older than this count as if their author was trusted one level less
+    pub review_max_age: Option<chrono::Duration>,
 }
 
 impl Default for VerificationRequirements {
@@ -183,16 +213,28 @@ impl Default for VerificationRequirements {
             understanding: Default::default(),
             thoroughness: Default::default(),
             redundancy: 1,
+            review_max_age: None,
         }
     }
 }
+
+impl VerificationRequirements {
+    /// Whether the review is older than `review_max_age`
+    pub fn is_review_stale(&self, review: &impl CommonOps, now: DateTime<Utc>) -> bool {
+        self.review_max_age
+            .map_or(false, |max_age| review.date_utc() + max_age < now)
+    }
+}
 /// Result of verification
 ///
 /// Not named `Result` to avoid confusion with `Result` type.
-#[derive(Copy, Clone, PartialEq, Eq, Debug, PartialOrd, Ord)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum VerificationStatus {
     Negative,
     Insufficient,
+    /// Not verified, but explicitly exempted from verification (for now)
+    Exempted,
     Verified,
     Local,
 }
@@ -221,12 +263,322 @@ impl fmt::Display for VerificationStatus {
         match self {
             VerificationStatus::Local => f.pad("locl"),
             VerificationStatus::Verified => f.pad("pass"),
+            VerificationStatus::Exempted => f.pad("exmt"),
             VerificationStatus::Insufficient => f.pad("none"),
             VerificationStatus::Negative => f.pad("warn"),
         }
     }
 }
 
+/// How a single review was taken into account when verifying a package
+#[derive(Copy, Clone, PartialEq, Eq, Debug, PartialOrd, Ord)]
+pub enum ReviewVerdict {
+    /// Negative review from a trusted reviewer - fails the verification
+    Negative,
+    /// Counted towards the required redundancy
+    Counted,
+    /// Reviewer is trusted, but less than required
+    ReviewerTrustTooLow,
+    /// Thoroughness lower than required
+    ThoroughnessTooLow,
+    /// Understanding lower than required
+    UnderstandingTooLow,
+    /// Too few members of the team reviewed the package
+    TeamThresholdNotMet,
+    /// Review has no rating, thoroughness or understanding
+    EmptyReview,
+    /// `valid-until` of the review has passed
+    Expired,
+    /// Reviewer's Id was revoked before the review was created
+    Revoked,
+    /// Reviewer is not in the trust set
+    ReviewerNotTrusted,
+}
+
+impl fmt::Display for ReviewVerdict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            ReviewVerdict::Negative => "negative",
+            ReviewVerdict::Counted => "counted",
+            ReviewVerdict::ReviewerTrustTooLow => "trust too low",
+            ReviewVerdict::ThoroughnessTooLow => "thoroughness too low",
+            ReviewVerdict::UnderstandingTooLow => "understanding too low",
+            ReviewVerdict::TeamThresholdNotMet => "team threshold not met",
+            ReviewVerdict::EmptyReview => "empty review",
+            ReviewVerdict::Expired => "expired",
+            ReviewVerdict::Revoked => "revoked",
+            ReviewVerdict::ReviewerNotTrusted => "not trusted",
+        })
+    }
+}
+
+/// One level lower trust, for reviews that got stale
+fn decay_trust_level(level: TrustLevel) -> TrustLevel {
+    match level {
+        TrustLevel::High => TrustLevel::Medium,
+        TrustLevel::Medium => TrustLevel::Low,
+        TrustLevel::Low | TrustLevel::None => TrustLevel::None,
+        TrustLevel::Distrust => TrustLevel::Distrust,
+    }
+}
+
+/// Trust in the author of a review, taking its age into account
+fn review_trust_level(
+    reviewer: &Id,
+    review: &impl CommonOps,
+    trust_set: &crev_wot::TrustSet,
+    requirements: &VerificationRequirements,
+    now: DateTime<Utc>,
+) -> TrustLevel {
+    let level = trust_set.get_effective_trust_level(reviewer);
+    if requirements.is_review_stale(review, now) {
+        decay_trust_level(level)
+    } else {
+        level
+    }
+}
+
+fn review_verdict(
+    reviewer: &Id,
+    package_review: &review::Package,
+    trust_set: &crev_wot::TrustSet,
+    requirements: &VerificationRequirements,
+    db: &crev_wot::ProofDB,
+    now: DateTime<Utc>,
+) -> ReviewVerdict {
+    proof_review_verdict(
+        reviewer,
+        package_review,
+        package_review.is_expired_at(now),
+        trust_set,
+        requirements,
+        db,
+        now,
+    )
+}
+
+/// Verdict of a package or code review, `expired` if its `valid-until` has passed
+fn proof_review_verdict<R: WithReview + CommonOps>(
+    reviewer: &Id,
+    proof_review: &R,
+    expired: bool,
+    trust_set: &crev_wot::TrustSet,
+    requirements: &VerificationRequirements,
+    db: &crev_wot::ProofDB,
+    now: DateTime<Utc>,
+) -> ReviewVerdict {
+    let review = proof_review.review();
+    if db.is_proof_revoked(reviewer, proof_review.date_utc()) {
+        ReviewVerdict::Revoked
+    } else if !trust_set.contains_trusted(reviewer) {
+        ReviewVerdict::ReviewerNotTrusted
+    } else if expired {
+        ReviewVerdict::Expired
+    } else if !review.is_none()
+        && Rating::Neutral <= review.rating
+        && requirements.thoroughness <= review.thoroughness
+        && requirements.understanding <= review.understanding
+    {
+        if TrustLevel::from(requirements.trust_level)
+            <= review_trust_level(reviewer, proof_review, trust_set, requirements, now)
+        {
+            ReviewVerdict::Counted
+        } else {
+            ReviewVerdict::ReviewerTrustTooLow
+        }
+    } else if review.rating <= Rating::Negative {
+        ReviewVerdict::Negative
+    } else if review.is_none() {
+        ReviewVerdict::EmptyReview
+    } else if review.thoroughness < requirements.thoroughness {
+        ReviewVerdict::ThoroughnessTooLow
+    } else {
+        ReviewVerdict::UnderstandingTooLow
+    }
+}
+
+/// A trusted team, and whether enough of its members reviewed a package
+#[derive(Clone, Debug)]
+pub struct TeamTrace {
+    pub team: Id,
+    /// Members whose reviews meet the requirements, and don't count on their own
+    pub counted_members: Vec<Id>,
+    /// Members whose reviews are negative
+    pub negative_members: Vec<Id>,
+    pub threshold: u64,
+    pub effective_trust: TrustLevel,
+    pub distance: Option<u64>,
+    pub verdict: ReviewVerdict,
+}
+
+/// Verdicts of the trusted teams, for the `reviews` of a package
+///
+/// A team reviews a package once `threshold` of its members did.
+/// The members don't have to be trusted themselves: the trust in the
+/// team applies to the reviews they make together. Reviews of members
+/// trusted enough to count on their own are not counted a second time,
+/// and distrusted members are ignored.
+fn team_traces(
+    reviews: &HashMap<Id, review::Package>,
+    trust_set: &crev_wot::TrustSet,
+    requirements: &VerificationRequirements,
+    db: &crev_wot::ProofDB,
+    now: DateTime<Utc>,
+) -> Vec<TeamTrace> {
+    let mut traces: Vec<_> = trust_set
+        .trusted_ids()
+        .filter_map(|team_id| db.get_team(team_id).map(|team| (team_id, team)))
+        .map(|(team_id, team)| {
+            let member_reviews = team
+                .members
+                .iter()
+                .filter(|member| !trust_set.contains_distrusted(member))
+                .filter_map(|member| {
+                    reviews
+                        .get(member)
+                        .filter(|review| {
+                            !db.is_proof_revoked(member, review.date_utc())
+                                && !review.is_expired_at(now)
+                                && review_verdict(member, review, trust_set, requirements, db, now)
+                                    != ReviewVerdict::Counted
+                        })
+                        .map(|review| (member, &review.review))
+                });
+            let mut counted_members = vec![];
+            let mut negative_members = vec![];
+            for (member, review) in member_reviews {
+                if !review.is_none()
+                    && Rating::Neutral <= review.rating
+                    && requirements.thoroughness <= review.thoroughness
+                    && requirements.understanding <= review.understanding
+                {
+                    counted_members.push(member.clone());
+                } else if review.rating <= Rating::Negative {
+                    negative_members.push(member.clone());
+                }
+            }
+            let effective_trust = trust_set.get_effective_trust_level(team_id);
+            let verdict = if negative_members.len() as u64 >= team.threshold {
+                ReviewVerdict::Negative
+            } else if (counted_members.len() as u64) < team.threshold {
+                ReviewVerdict::TeamThresholdNotMet
+            } else if TrustLevel::from(requirements.trust_level) <= effective_trust {
+                ReviewVerdict::Counted
+            } else {
+                ReviewVerdict::ReviewerTrustTooLow
+            };
+            TeamTrace {
+                team: team_id.clone(),
+                counted_members,
+                negative_members,
+                threshold: team.threshold,
+                effective_trust,
+                distance: trust_set.get_distance(team_id),
+                verdict,
+            }
+        })
+        .collect();
+    traces.sort_by(|a, b| {
+        a.verdict
+            .cmp(&b.verdict)
+            .then_with(|| a.distance.cmp(&b.distance))
+            .then_with(|| a.team.to_string().cmp(&b.team.to_string()))
+    });
+    traces
+}
+
+/// A review considered when verifying a package, and what came out of it
+#[derive(Clone, Debug)]
+pub struct ReviewTrace {
+    pub review: review::Package,
+    /// Effective trust of the reviewer, lowered if the review is stale
+    pub effective_trust: TrustLevel,
+    /// Review is older than `review_max_age` of the requirements
+    pub stale: bool,
+    /// Distance of the reviewer in the trust graph
+    pub distance: Option<u64>,
+    pub verdict: ReviewVerdict,
+}
+
+/// Detailed result of a package verification
+///
+/// See `explain_package_digest_verification`.
+#[derive(Clone, Debug)]
+pub struct VerificationTrace {
+    pub status: VerificationStatus,
+    /// Number of reviews that counted towards `required`
+    pub counted: u64,
+    /// Required number of reviews (`redundancy`)
+    pub required: u64,
+    /// All the reviews of the package, most relevant first
+    pub reviews: Vec<ReviewTrace>,
+    /// The trusted teams, most relevant first
+    pub teams: Vec<TeamTrace>,
+}
+
+/// Like `verify_package_digest`, but returns the details of the decision
+pub fn explain_package_digest_verification(
+    digest: &Digest,
+    trust_set: &crev_wot::TrustSet,
+    requirements: &VerificationRequirements,
+    db: &crev_wot::ProofDB,
+) -> VerificationTrace {
+    let reviews: HashMap<Id, review::Package> = db
+        .get_package_reviews_by_digest(digest)
+        .map(|review| (review.from().id.clone(), review))
+        .collect();
+
+    let now = crev_common::now().with_timezone(&Utc);
+    let teams = team_traces(&reviews, trust_set, requirements, db, now);
+    let mut traces: Vec<_> = reviews
+        .into_iter()
+        .map(|(reviewer, review)| ReviewTrace {
+            verdict: review_verdict(&reviewer, &review, trust_set, requirements, db, now),
+            effective_trust: review_trust_level(&reviewer, &review, trust_set, requirements, now),
+            stale: requirements.is_review_stale(&review, now),
+            distance: trust_set.get_distance(&reviewer),
+            review,
+        })
+        .collect();
+    traces.sort_by(|a, b| {
+        a.verdict
+            .cmp(&b.verdict)
+            .then_with(|| a.distance.cmp(&b.distance))
+            .then_with(|| {
+                a.review
+                    .from()
+                    .id
+                    .to_string()
+                    .cmp(&b.review.from().id.to_string())
+            })
+    });
+
+    let verdicts = || {
+        traces
+            .iter()
+            .map(|trace| trace.verdict)
+            .chain(teams.iter().map(|trace| trace.verdict))
+    };
+    let counted = verdicts()
+        .filter(|verdict| *verdict == ReviewVerdict::Counted)
+        .count() as u64;
+    let negative = verdicts().any(|verdict| verdict == ReviewVerdict::Negative);
+
+    VerificationTrace {
+        status: if negative {
+            VerificationStatus::Negative
+        } else if counted >= requirements.redundancy {
+            VerificationStatus::Verified
+        } else {
+            VerificationStatus::Insufficient
+        },
+        counted,
+        required: requirements.redundancy,
+        reviews: traces,
+        teams,
+    }
+}
+
 pub fn verify_package_digest(
     digest: &Digest,
     trust_set: &crev_wot::TrustSet,
@@ -241,22 +593,22 @@ pub fn verify_package_digest(
     let reviews_by: HashSet<Id, _> = reviews.keys().cloned().collect();
     let trusted_ids: HashSet<_> = trust_set.trusted_ids().cloned().collect();
     let matching_reviewers = trusted_ids.intersection(&reviews_by);
+    let now = crev_common::now().with_timezone(&Utc);
     let mut trust_count = 0;
     let mut negative_count = 0;
     for matching_reviewer in matching_reviewers {
-        let review = &reviews[matching_reviewer].review;
-        if !review.is_none()
-            && Rating::Neutral <= review.rating
-            && requirements.thoroughness <= review.thoroughness
-            && requirements.understanding <= review.understanding
-        {
-            if TrustLevel::from(requirements.trust_level)
-                <= trust_set.get_effective_trust_level(matching_reviewer)
-            {
-                trust_count += 1;
-            }
-        } else if review.rating <= Rating::Negative {
-            negative_count += 1;
+        let review = &reviews[matching_reviewer];
+        match review_verdict(matching_reviewer, review, trust_set, requirements, db, now) {
+            ReviewVerdict::Counted => trust_count += 1,
+            ReviewVerdict::Negative => negative_count += 1,
+            _ => {}
+        }
+    }
+    for team in team_traces(&reviews, trust_set, requirements, db, now) {
+        match team.verdict {
+            ReviewVerdict::Counted => trust_count += 1,
+            ReviewVerdict::Negative => negative_count += 1,
+            _ => {}
         }
     }
 
@@ -269,6 +621,193 @@ pub fn verify_package_digest(
     }
 }
 
+/// Trusted reviews of the same revision of a package, but with a different digest than `digest`
+///
+/// A git commit always checks out to the same code, so these mean that either
+/// the reviewed code, or the local checkout was modified.
+pub fn find_revision_digest_mismatches<'a>(
+    source: &str,
+    name: &'a str,
+    revision: &'a str,
+    digest: &Digest,
+    trust_set: &'a crev_wot::TrustSet,
+    db: &'a crev_wot::ProofDB,
+) -> Vec<&'a review::Package> {
+    db.get_pkg_reviews_for_revision(source, name, revision)
+        .filter(|review| review.package.digest != digest.as_slice())
+        .filter(|review| trust_set.contains_trusted(&review.from().id))
+        .collect()
+}
+
+/// A file of a package, and whether the code reviews of its content verify it
+#[derive(Clone, Debug)]
+pub struct FileCoverage {
+    /// Path relative to the package root
+    pub path: PathBuf,
+    pub digest: Vec<u8>,
+    pub status: VerificationStatus,
+}
+
+/// Verification of a package through code reviews of its individual files
+#[derive(Clone, Debug, Default)]
+pub struct FilesCoverage {
+    pub files: Vec<FileCoverage>,
+}
+
+impl FilesCoverage {
+    /// Number of files verified by code reviews
+    pub fn covered_count(&self) -> usize {
+        self.files
+            .iter()
+            .filter(|file| file.status.is_verified())
+            .count()
+    }
+
+    /// Verified only if every file is, negative if any file is
+    pub fn status(&self) -> VerificationStatus {
+        if self.files.is_empty() {
+            return VerificationStatus::Insufficient;
+        }
+        self.files
+            .iter()
+            .fold(VerificationStatus::Verified, |status, file| {
+                status.min(file.status)
+            })
+    }
+
+    /// Lines of code in the files verified by code reviews, and in all the files,
+    /// counting only files matching `filter`
+    pub fn covered_loc(
+        &self,
+        loc_by_file: &HashMap<PathBuf, u64>,
+        filter: impl Fn(&Path) -> bool,
+    ) -> (u64, u64) {
+        let reviewed = self
+            .files
+            .iter()
+            .filter(|file| file.status.is_verified() && filter(&file.path))
+            .filter_map(|file| loc_by_file.get(&file.path))
+            .sum();
+        let total = loc_by_file
+            .iter()
+            .filter(|(path, _)| filter(path))
+            .map(|(_, loc)| loc)
+            .sum();
+        (reviewed, total)
+    }
+
+    /// Whether code reviews verify enough of the lines of code
+    ///
+    /// At least `min_coverage` percent of all the lines have to be verified,
+    /// so without `min_coverage` the coverage never verifies a package.
+    /// `min_unsafe_coverage` is an additional requirement for the lines in
+    /// `unsafe_files`, if there are any. A package without code is not covered.
+    pub fn meets_coverage(
+        &self,
+        loc_by_file: &HashMap<PathBuf, u64>,
+        unsafe_files: &HashSet<PathBuf>,
+        min_coverage: Option<u64>,
+        min_unsafe_coverage: Option<u64>,
+    ) -> bool {
+        let is_percent_of =
+            |min_percent: u64, (reviewed, total): (u64, u64)| reviewed * 100 >= min_percent * total;
+        let (reviewed, total) = self.covered_loc(loc_by_file, |_| true);
+        let (unsafe_reviewed, unsafe_total) =
+            self.covered_loc(loc_by_file, |path| unsafe_files.contains(path));
+        min_coverage.map_or(false, |min_coverage| {
+            total > 0 && is_percent_of(min_coverage, (reviewed, total))
+        }) && min_unsafe_coverage.map_or(true, |min_unsafe_coverage| {
+            is_percent_of(min_unsafe_coverage, (unsafe_reviewed, unsafe_total))
+        })
+    }
+}
+
+/// Check whether code reviews of the file at `path` of a package, with this content,
+/// meet the requirements
+///
+/// Code reviews don't expire, and teams don't review files.
+pub fn verify_file_digest(
+    source: &str,
+    name: &str,
+    path: &Path,
+    digest: &[u8],
+    trust_set: &crev_wot::TrustSet,
+    requirements: &VerificationRequirements,
+    db: &crev_wot::ProofDB,
+) -> VerificationStatus {
+    let now = crev_common::now().with_timezone(&Utc);
+    let mut trust_count = 0;
+    let mut negative_count = 0;
+    for code_review in db.get_code_reviews_for_file(source, name, path, digest) {
+        let reviewer = &code_review.from().id;
+        match proof_review_verdict(
+            reviewer,
+            code_review,
+            false,
+            trust_set,
+            requirements,
+            db,
+            now,
+        ) {
+            ReviewVerdict::Counted => trust_count += 1,
+            ReviewVerdict::Negative => negative_count += 1,
+            _ => {}
+        }
+    }
+
+    if negative_count > 0 {
+        VerificationStatus::Negative
+    } else if trust_count >= requirements.redundancy {
+        VerificationStatus::Verified
+    } else {
+        VerificationStatus::Insufficient
+    }
+}
+
+/// Verify every file of the package at `root_path` with code reviews
+///
+/// Files are the same as the package digest is calculated from.
+pub fn get_dir_files_coverage(
+    source: &str,
+    name: &str,
+    root_path: &Path,
+    rel_path_ignore_list: &fnv::FnvHashSet<PathBuf>,
+    trust_set: &crev_wot::TrustSet,
+    requirements: &VerificationRequirements,
+    db: &crev_wot::ProofDB,
+) -> Result<FilesCoverage> {
+    let mut files = vec![];
+    let entries = walkdir::WalkDir::new(root_path)
+        .sort_by(|a, b| a.file_name().cmp(b.file_name()))
+        .into_iter()
+        .filter_entry(|entry| {
+            let rel_path = entry
+                .path()
+                .strip_prefix(root_path)
+                .expect("must be prefix");
+            !rel_path_ignore_list.contains(rel_path)
+        });
+    for entry in entries {
+        let entry = entry.map_err(std::io::Error::from)?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let digest = crev_common::blake2b256sum_file(entry.path())?;
+        let path = entry
+            .path()
+            .strip_prefix(root_path)
+            .expect("must be prefix")
+            .to_owned();
+        files.push(FileCoverage {
+            status: verify_file_digest(source, name, &path, &digest, trust_set, requirements, db),
+            path,
+            digest,
+        });
+    }
+
+    Ok(FilesCoverage { files })
+}
+
 pub fn find_latest_trusted_version(
     trust_set: &crev_wot::TrustSet,
     source: &str,
@@ -1,6 +1,9 @@
+#[cfg(unix)]
+use crate::agent::{AgentClient, AgentSigner};
 use crate::{
     activity::ReviewActivity,
     id::{self, LockedId, PassphraseFn},
+    proof_cache::ProofCache,
     util, Error, ProofStore, Result, TrustProofType,
 };
 use crev_common::{
@@ -9,10 +12,13 @@ use crev_common::{
     sanitize_name_for_fs, sanitize_url_for_fs,
     serde::{as_base64, from_base64},
 };
+#[cfg(unix)]
+use crev_data::signer::ssh_agent::SshAgentSigner;
 use crev_data::{
-    id::UnlockedId,
-    proof::{self, trust::TrustLevel},
-    Id, PublicId, Url,
+    id::{IdError, UnlockedId},
+    openpgp,
+    proof::{self, trust::TrustLevel, Content, ContentExt},
+    Id, PublicId, Signer, Url,
 };
 use default::default;
 use directories::ProjectDirs;
@@ -74,6 +80,14 @@ pub struct UserConfig {
         default = "Option::default"
     )]
     pub open_cmd: Option<String>,
+
+    /// Advisory databases to import advisories from
+    #[serde(
+        rename = "advisory-dbs",
+        skip_serializing_if = "Vec::is_empty",
+        default = "Vec::new"
+    )]
+    pub advisory_dbs: Vec<AdvisoryDbConfig>,
 }
 
 impl Default for UserConfig {
@@ -83,10 +97,20 @@ impl Default for UserConfig {
             current_id: None,
             host_salt: generete_salt(),
             open_cmd: None,
+            advisory_dbs: vec![],
         }
     }
 }
 
+/// RustSec advisory database fetched with `fetch_advisory_db`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AdvisoryDbConfig {
+    /// Local directory, or git URL
+    pub location: String,
+    /// How much its advisories are trusted
+    pub trust: TrustLevel,
+}
+
 impl UserConfig {
     pub fn get_current_userid(&self) -> Result<&Id> {
         self.get_current_userid_opt()
@@ -187,7 +211,7 @@ impl Local {
     /// * otherwise return current id
     pub fn get_for_id_from_str_opt(&self, id_str: Option<&str>) -> Result<Option<Id>> {
         id_str
-            .map(|s| crev_data::id::Id::crevid_from_str(s).map_err(Error::from))
+            .map(|s| self.id_from_str(s))
             .or_else(|| self.read_current_id_opt().inside_out())
             .inside_out()
     }
@@ -252,6 +276,26 @@ impl Local {
             Id::Crev { id } => self
                 .user_ids_path()
                 .join(format!("{}.yaml", crev_common::base64_encode(id))),
+            Id::OpenPgp { .. } | Id::Ssh { .. } => self
+                .user_ids_path()
+                .join(format!("{}.yaml", id.to_file_name())),
+        }
+    }
+
+    /// Parse an Id given as a string
+    ///
+    /// OpenPGP Ids are given by their fingerprints, so only the ones
+    /// belonging to the current user can be found.
+    pub fn id_from_str(&self, id_str: &str) -> Result<Id> {
+        match Id::crevid_from_str(id_str) {
+            Ok(id) => Ok(id),
+            Err(e) => self
+                .get_current_user_public_ids()
+                .into_iter()
+                .flatten()
+                .map(|public_id| public_id.id)
+                .find(|id| id.to_string().eq_ignore_ascii_case(id_str))
+                .ok_or_else(|| e.into()),
         }
     }
 
@@ -277,6 +321,16 @@ impl Local {
         self.cache_path.join("remotes")
     }
 
+    /// Path where git checkouts of advisory databases are stored
+    fn cache_advisory_dbs_path(&self) -> PathBuf {
+        self.cache_path.join("advisory-dbs")
+    }
+
+    /// Cache of already verified proofs from `cache_remotes_path`
+    fn proof_cache(&self) -> ProofCache {
+        ProofCache::new(self.cache_path.join("proofdb"))
+    }
+
     /// Cache where metadata about in-progress reviews (etc) is stored
     fn cache_activity_path(&self) -> PathBuf {
         self.cache_path.join("activity")
@@ -403,6 +457,87 @@ impl Local {
             .ok_or_else(|| Error::CurrentIDNotSet)
     }
 
+    /// Get a `Signer` for the current Id, see `read_signer`
+    pub fn read_current_signer(
+        &self,
+        passphrase_callback: PassphraseFn<'_>,
+    ) -> Result<Box<dyn Signer>> {
+        self.read_signer(&self.get_current_userid()?, passphrase_callback)
+    }
+
+    /// Get a `Signer` to sign proofs as `id`
+    ///
+    /// SSH Ids whose key is held by `ssh-agent` sign with the agent,
+    /// without reading the key file. If `cargo crev agent` is running,
+    /// the Id is unlocked in it (if it isn't already), and it signs.
+    /// Otherwise the Id is unlocked here.
+    pub fn read_signer(
+        &self,
+        id: &Id,
+        passphrase_callback: PassphraseFn<'_>,
+    ) -> Result<Box<dyn Signer>> {
+        #[cfg(unix)]
+        {
+            let locked = self.read_locked_id(id)?;
+            if let Id::Ssh { .. } = id {
+                if let Ok(signer) = SshAgentSigner::from_env(locked.to_public_id()) {
+                    if signer.has_key().unwrap_or(false) {
+                        return Ok(Box::new(signer));
+                    }
+                }
+            }
+            let agent = AgentClient::new(self.agent_socket_path());
+            if locked.needs_passphrase()? && agent.is_running() {
+                if !agent.is_unlocked(id)? {
+                    self.unlock_in_agent(&agent, id, passphrase_callback)?;
+                }
+                return Ok(Box::new(AgentSigner::new(agent, locked.to_public_id())));
+            }
+        }
+        Ok(Box::new(self.read_unlocked_id(id, passphrase_callback)?))
+    }
+
+    /// Path of the socket `cargo crev agent` listens on
+    ///
+    /// It's in a directory of its own, which the agent makes private.
+    pub fn agent_socket_path(&self) -> PathBuf {
+        self.cache_path.join("agent").join("agent.sock")
+    }
+
+    /// Run the agent keeping Ids unlocked for `timeout`, see `agent::serve`
+    #[cfg(unix)]
+    pub fn serve_agent(&self, timeout: std::time::Duration) -> Result<()> {
+        crate::agent::serve(&self.agent_socket_path(), timeout, |id, passphrase| {
+            self.read_locked_id(id)?.to_unlocked(passphrase)
+        })
+    }
+
+    /// Unlock `id` in the agent
+    ///
+    /// Asks for passphrase up to 5 times
+    #[cfg(unix)]
+    fn unlock_in_agent(
+        &self,
+        agent: &AgentClient,
+        id: &Id,
+        passphrase_callback: PassphraseFn<'_>,
+    ) -> Result<()> {
+        let mut i = 0;
+        loop {
+            let passphrase = passphrase_callback()?;
+            match agent.unlock(id, &passphrase) {
+                Ok(()) => return Ok(()),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    if i == 5 {
+                        return Err(e);
+                    }
+                }
+            }
+            i += 1;
+        }
+    }
+
     /// Just reads the yaml file and unlocks it, doesn't change anything
     ///
     /// Asks for passphrase up to 5 times
@@ -412,6 +547,9 @@ impl Local {
         passphrase_callback: PassphraseFn<'_>,
     ) -> Result<UnlockedId> {
         let locked = self.read_locked_id(id)?;
+        if !locked.needs_passphrase()? {
+            return locked.to_unlocked("");
+        }
         let mut i = 0;
         loop {
             let passphrase = passphrase_callback()?;
@@ -575,23 +713,8 @@ impl Local {
             .ok_or_else(|| Error::CurrentIDNotSet)
     }
 
-    /// Creates new unsigned trust proof object, not edited
-    ///
-    /// Ensures the proof contains valid URLs for Ids where possible.
-    ///
-    /// Currently ignores previous proofs
-    ///
-    /// See `trust.sign_by(ownid)`
-    pub fn build_trust_proof(
-        &self,
-        from_id: &PublicId,
-        ids: Vec<Id>,
-        trust_or_distrust: TrustProofType,
-    ) -> Result<proof::trust::Trust> {
-        if ids.is_empty() {
-            Err(Error::NoIdsGiven)?;
-        }
-
+    /// `PublicId`s of `ids`, with the URLs they reported for themselves
+    fn public_ids_with_urls(&self, ids: Vec<Id>) -> Result<Vec<PublicId>> {
         let mut db = self.load_db()?;
         let mut public_ids = Vec::with_capacity(ids.len());
 
@@ -614,6 +737,28 @@ impl Local {
             }
         }
 
+        Ok(public_ids)
+    }
+
+    /// Creates new unsigned trust proof object, not edited
+    ///
+    /// Ensures the proof contains valid URLs for Ids where possible.
+    ///
+    /// Currently ignores previous proofs
+    ///
+    /// See `trust.sign_by(ownid)`
+    pub fn build_trust_proof(
+        &self,
+        from_id: &PublicId,
+        ids: Vec<Id>,
+        trust_or_distrust: TrustProofType,
+    ) -> Result<proof::trust::Trust> {
+        if ids.is_empty() {
+            Err(Error::NoIdsGiven)?;
+        }
+
+        let public_ids = self.public_ids_with_urls(ids)?;
+
         Ok(from_id.create_trust_proof(
             &public_ids,
             match trust_or_distrust {
@@ -624,6 +769,25 @@ impl Local {
         )?)
     }
 
+    /// Declare `from_id` a team of `members`, reviewing as one once
+    /// `threshold` of them reviewed a package
+    pub fn build_team_proof(
+        &self,
+        from_id: &PublicId,
+        members: Vec<Id>,
+        threshold: u64,
+        comment: String,
+    ) -> Result<proof::Team> {
+        if members.is_empty() {
+            Err(Error::NoIdsGiven)?;
+        }
+
+        let members = self.public_ids_with_urls(members)?;
+        let team = from_id.create_team_proof(members, threshold, comment)?;
+        team.validate_data().map_err(crev_data::Error::from)?;
+        Ok(team)
+    }
+
     /// Fetch other people's proof repostiory from a git URL, into the current database on disk
     pub fn fetch_url(&self, url: &str) -> Result<()> {
         let mut db = self.load_db()?;
@@ -722,7 +886,7 @@ impl Local {
             for id in ids {
                 let tx = tx.clone();
 
-                if already_fetched_ids.contains(&id) {
+                if already_fetched_ids.contains(&id) || db.is_unsigned_author(&id) {
                     continue;
                 }
 
@@ -794,6 +958,7 @@ impl Local {
     /// Adds the repo to the local proof repo cache.
     pub fn fetch_remote_git(&self, url: &str) -> Result<PathBuf> {
         let dir = self.get_remote_git_cache_path(url)?;
+        self.proof_cache().invalidate(&dir);
 
         if dir.exists() {
             let repo = git2::Repository::open(&dir)?;
@@ -842,6 +1007,73 @@ impl Local {
         Ok(())
     }
 
+    /// Directory with the advisory database at `location`
+    ///
+    /// That's `location` itself, if it's a local directory,
+    /// or a checkout of the git URL in the cache.
+    fn advisory_db_dir(&self, location: &str) -> PathBuf {
+        let path = Path::new(location);
+        if path.is_dir() {
+            path.to_owned()
+        } else {
+            self.cache_advisory_dbs_path()
+                .join(sanitize_url_for_fs(location))
+        }
+    }
+
+    /// Fetch a RustSec advisory database, from a local directory or a git URL
+    ///
+    /// Its advisories are imported by `load_db` from now on, trusted with `trust`.
+    /// Returns the number of advisories found.
+    pub fn fetch_advisory_db(&self, location: &str, trust: TrustLevel) -> Result<usize> {
+        let location = match Path::new(location).canonicalize() {
+            Ok(path) if path.is_dir() => path.display().to_string(),
+            _ => location.to_owned(),
+        };
+        let dir = self.advisory_db_dir(&location);
+        if !Path::new(&location).is_dir() {
+            if dir.exists() {
+                let repo = git2::Repository::open(&dir)?;
+                util::git::fetch_and_checkout_git_ref(&repo, "HEAD")?;
+            } else {
+                git2::Repository::clone(&location, &dir)?;
+            }
+        }
+        let count = crate::advisory_db::read_advisory_db(&dir, &location)?.len();
+
+        let mut config = self.load_user_config()?;
+        config.advisory_dbs.retain(|db| db.location != location);
+        config
+            .advisory_dbs
+            .push(AdvisoryDbConfig { location, trust });
+        self.store_user_config(&config)?;
+        Ok(count)
+    }
+
+    /// Import advisories of the fetched advisory databases, trusted by the current Id
+    fn import_advisory_dbs(&self, db: &mut crev_wot::ProofDB) -> Result<()> {
+        let config = self.load_user_config()?;
+        for advisory_db in &config.advisory_dbs {
+            let dir = self.advisory_db_dir(&advisory_db.location);
+            if !dir.exists() {
+                continue;
+            }
+            crate::advisory_db::read_advisory_db(&dir, &advisory_db.location)
+                .map(|reviews| {
+                    db.import_unsigned_package_reviews(
+                        reviews.into_iter(),
+                        crev_wot::FetchSource::AdvisoryDb(Arc::new(Url::new_git(
+                            advisory_db.location.clone(),
+                        ))),
+                        config.current_id.as_ref(),
+                        advisory_db.trust,
+                    )
+                })
+                .err_eprint_and_ignore();
+        }
+        Ok(())
+    }
+
     /// Fetch and discover proof repos. Like `fetch_all_ids_recursively`,
     /// but adds `https://github.com/dpc/crev-proofs` and repos in cache that didn't belong to any Ids.
     pub fn fetch_all(&self) -> Result<()> {
@@ -889,6 +1121,12 @@ impl Local {
 
         self.fetch_all_ids_recursively(fetched_urls, &mut db)?;
 
+        for advisory_db in self.load_user_config()?.advisory_dbs {
+            self.fetch_advisory_db(&advisory_db.location, advisory_db.trust)
+                .map(|count| eprintln!("{:<60} {} advisories", advisory_db.location, count))
+                .err_eprint_and_ignore();
+        }
+
         Ok(())
     }
 
@@ -933,7 +1171,9 @@ impl Local {
         );
         db.import_from_iter(proofs_iter_for_remotes_checkouts(
             self.cache_remotes_path(),
+            self.proof_cache(),
         )?);
+        self.import_advisory_dbs(&mut db)?;
         Ok(db)
     }
 
@@ -1003,9 +1243,93 @@ impl Local {
         Ok(locked_id)
     }
 
+    /// Import an existing OpenPGP key as a new Id
+    ///
+    /// `key` is the secret key, as exported with `gpg --export-secret-keys`
+    /// (only Ed25519 keys are supported). `openpgp_passphrase` is only called
+    /// if the key is protected.
+    pub fn import_openpgp_id(
+        &self,
+        url: &str,
+        use_https_push: bool,
+        key: &[u8],
+        openpgp_passphrase: PassphraseFn<'_>,
+        read_new_passphrase: impl FnOnce() -> std::io::Result<String>,
+    ) -> Result<id::LockedId> {
+        let secret_key = openpgp::SecretKey::from_bytes(key).map_err(IdError::from)?;
+        let openpgp_passphrase = if secret_key.is_encrypted() {
+            openpgp_passphrase()?
+        } else {
+            String::new()
+        };
+        let sec_key = secret_key
+            .unlock(&openpgp_passphrase)
+            .map_err(IdError::from)?;
+        let unlocked_id = UnlockedId::new_openpgp(
+            crev_data::Url::new_git(url),
+            secret_key.public_key().to_vec(),
+            sec_key.as_bytes().to_vec(),
+        )?;
+
+        self.clone_proof_dir_from_git(&url, use_https_push)?;
+        let passphrase = read_new_passphrase()?;
+        let locked_id = id::LockedId::from_unlocked_id(&unlocked_id, &passphrase)?;
+
+        self.save_locked_id(&locked_id)?;
+        self.save_current_id(unlocked_id.as_ref())?;
+        self.init_repo_readme_using_template()?;
+        Ok(locked_id)
+    }
+
+    /// Use an existing SSH key as a new Id
+    ///
+    /// Only a reference to `key_file` (an Ed25519 OpenSSH private key) is stored,
+    /// so the Id is protected by the passphrase of the key, if any.
+    pub fn add_ssh_id(
+        &self,
+        url: &str,
+        use_https_push: bool,
+        key_file: &Path,
+    ) -> Result<id::LockedId> {
+        let locked_id = id::LockedId::new_ssh(crev_data::Url::new_git(url), key_file)?;
+
+        self.clone_proof_dir_from_git(&url, use_https_push)?;
+        self.save_locked_id(&locked_id)?;
+        self.save_current_id(&locked_id.to_public_id().id)?;
+        self.init_repo_readme_using_template()?;
+        Ok(locked_id)
+    }
+
+    /// Replace the current Id with a newly generated one
+    ///
+    /// The new Id uses the same proof repository, and becomes the current one.
+    /// Returns the new Id, and a key link proof (signed with both keys)
+    /// that carries the trust in the old Id over to the new one.
+    /// The proof is not stored.
+    pub fn rotate_id(
+        &self,
+        passphrase_callback: PassphraseFn<'_>,
+        read_new_passphrase: impl FnOnce() -> std::io::Result<String>,
+        comment: String,
+    ) -> Result<(id::LockedId, proof::Proof)> {
+        let old_locked_id = self.read_current_locked_id()?;
+        let old_id = self.read_current_signer(passphrase_callback)?;
+        let new_id = UnlockedId::generate(old_locked_id.url.clone());
+        let passphrase = read_new_passphrase()?;
+        let locked_id = id::LockedId::from_unlocked_id(&new_id, &passphrase)?;
+
+        let proof = new_id
+            .create_key_link_proof(old_id.as_ref(), comment)?
+            .sign_by(&new_id)?;
+
+        self.save_locked_id(&locked_id)?;
+        self.save_current_id(new_id.as_ref())?;
+        Ok((locked_id, proof))
+    }
+
     /// Set given Id as the current one
     pub fn switch_id(&self, id_str: &str) -> Result<()> {
-        let id: Id = Id::crevid_from_str(id_str)?;
+        let id = self.id_from_str(id_str)?;
         self.save_current_id(&id)?;
 
         Ok(())
@@ -1014,7 +1338,7 @@ impl Local {
     /// See `read_locked_id`
     pub fn export_locked_id(&self, id_str: Option<String>) -> Result<String> {
         let id = if let Some(id_str) = id_str {
-            let id = Id::crevid_from_str(&id_str)?;
+            let id = self.id_from_str(&id_str)?;
             self.read_locked_id(&id)?
         } else {
             self.read_current_locked_id()?
@@ -1078,8 +1402,11 @@ impl ProofStore for Local {
 }
 
 /// Scan a directory of git checkouts. Assumes fetch source is the origin URL.
+///
+/// Proofs of checkouts that didn't change since the last scan are taken from the `cache`.
 fn proofs_iter_for_remotes_checkouts(
     path: PathBuf,
+    cache: ProofCache,
 ) -> Result<impl Iterator<Item = (proof::Proof, crev_wot::FetchSource)>> {
     let dir = std::fs::read_dir(&path)?;
     Ok(dir
@@ -1096,7 +1423,15 @@ fn proofs_iter_for_remotes_checkouts(
             let repo = git2::Repository::open(&path).ok()?;
             let origin = repo.find_remote("origin").ok()?;
             let fetch_source = crev_wot::FetchSource::Url(Arc::new(Url::new_git(origin.url()?)));
-            Some(proofs_iter_for_path(path).map(move |p| (p, fetch_source.clone())))
+            let proofs = match ProofCache::key_for_git_repo(&repo) {
+                Some(key) => cache.load(&path, &key).unwrap_or_else(|| {
+                    let proofs: Vec<_> = proofs_iter_for_path(path.clone()).collect();
+                    cache.store(&path, &key, &proofs).err_eprint_and_ignore();
+                    proofs
+                }),
+                None => proofs_iter_for_path(path).collect(),
+            };
+            Some(proofs.into_iter().map(move |p| (p, fetch_source.clone())))
         })
         .flat_map(|iter| iter))
 }
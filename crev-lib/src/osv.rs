@@ -0,0 +1,571 @@
+//! Advisories and issues in the [OSV](https://ossf.github.io/osv-schema/) format
+//!
+//! Every advisory and issue of a package review becomes an OSV record.
+//! Affected versions are derived from the `affected` and `patched`
+//! version requirements, when given, or from the `range` relative to
+//! the reviewed version. Advisories and issues without any affected
+//! versions that can be expressed in OSV have no record.
+use chrono::SecondsFormat;
+use crev_data::{
+    proof::{
+        review::{self, package::VersionRange},
+        CommonOps,
+    },
+    Level,
+};
+use semver::{Version, VersionReq};
+use serde::Serialize;
+
+pub const SCHEMA_VERSION: &str = "1.4.0";
+
+/// OSV ecosystem of the packages from crates.io, the only one that can be exported
+pub const ECOSYSTEM: &str = "crates.io";
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Record {
+    pub schema_version: String,
+    pub id: String,
+    pub modified: String,
+    pub published: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+    pub summary: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub details: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub severity: Vec<Severity>,
+    pub affected: Vec<Affected>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub references: Vec<Reference>,
+    pub database_specific: DatabaseSpecific,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Severity {
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub score: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Affected {
+    pub package: Package,
+    pub ranges: Vec<Range>,
+    pub ecosystem_specific: EcosystemSpecific,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Package {
+    pub ecosystem: String,
+    pub name: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Range {
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub events: Vec<Event>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Event {
+    Introduced(String),
+    Fixed(String),
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct EcosystemSpecific {
+    pub severity: Level,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Reference {
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub url: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct DatabaseSpecific {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub cwe_ids: Vec<String>,
+    pub crev: CrevDetails,
+}
+
+/// Where the record comes from
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct CrevDetails {
+    /// `advisory` or `issue`
+    pub kind: String,
+    pub ids: Vec<String>,
+    pub reviewer: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reviewer_url: Option<String>,
+    /// Version the advisory or issue was reported in
+    pub version: String,
+    pub range: VersionRange,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub affected: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub patched: Vec<String>,
+}
+
+/// OSV record of an `advisory` of the `review`
+///
+/// `None` if the affected versions can't be expressed in OSV, or there are none.
+pub fn advisory_to_osv(review: &review::Package, advisory: &review::Advisory) -> Option<Record> {
+    let version = &review.package.id.version;
+    let affected = if advisory.has_exact_versions() {
+        let affected = match advisory.affected {
+            Some(ref affected) => Interval::from_req(affected)?,
+            None => Interval::all(),
+        };
+        let patched: Vec<_> = advisory
+            .patched
+            .iter()
+            .map(Interval::from_req)
+            .collect::<Option<_>>()?;
+        affected.subtract(&patched)
+    } else {
+        let start = match advisory.range {
+            VersionRange::All => Version::new(0, 0, 0),
+            VersionRange::Major => Version::new(version.major, 0, 0),
+            VersionRange::Minor => Version::new(version.major, version.minor, 0),
+        };
+        Interval {
+            start,
+            end: Some(version.clone()),
+        }
+        .subtract(&[])
+    };
+    if affected.is_empty() {
+        return None;
+    }
+
+    Some(to_osv(
+        review,
+        "advisory",
+        &advisory.ids,
+        advisory.severity,
+        advisory.range,
+        &advisory.affected,
+        &advisory.patched,
+        &advisory.cvss,
+        &advisory.cwe,
+        &advisory.references,
+        &advisory.comment,
+        affected,
+    ))
+}
+
+/// OSV record of an `issue` of the `review`
+///
+/// Like in `ProofDB::get_open_issues_for_version`, advisories with the id
+/// of the issue, from `advisory_reviews` of the same package, mark the
+/// versions they were fixed in. `None` if the affected versions can't be
+/// expressed in OSV, or there are none.
+pub fn issue_to_osv<'a>(
+    review: &review::Package,
+    issue: &review::Issue,
+    advisory_reviews: impl IntoIterator<Item = &'a review::Package>,
+) -> Option<Record> {
+    let version = &review.package.id.version;
+    let mut fixed = vec![];
+    for advisory_review in advisory_reviews {
+        for advisory in &advisory_review.advisories {
+            if !advisory.ids.contains(&issue.id)
+                || !advisory.is_for_version_when_reported_in_version(
+                    version,
+                    &advisory_review.package.id.version,
+                )
+            {
+                continue;
+            }
+            if advisory.has_exact_versions() {
+                fixed.extend(advisory.patched.iter().filter_map(Interval::from_req));
+            } else {
+                fixed.push(Interval {
+                    start: advisory_review.package.id.version.clone(),
+                    end: None,
+                });
+            }
+        }
+    }
+    let affected = match issue.affected {
+        Some(ref affected) => Interval::from_req(affected)?,
+        None => Interval {
+            start: version.clone(),
+            end: match issue.range {
+                VersionRange::All => None,
+                VersionRange::Major => Some(Version::new(version.major + 1, 0, 0)),
+                VersionRange::Minor => Some(Version::new(version.major, version.minor + 1, 0)),
+            },
+        },
+    }
+    .subtract(&fixed);
+    if affected.is_empty() {
+        return None;
+    }
+
+    Some(to_osv(
+        review,
+        "issue",
+        std::slice::from_ref(&issue.id),
+        issue.severity,
+        issue.range,
+        &issue.affected,
+        &[],
+        &issue.cvss,
+        &issue.cwe,
+        &issue.references,
+        &issue.comment,
+        affected,
+    ))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn to_osv(
+    review: &review::Package,
+    kind: &str,
+    ids: &[String],
+    severity: Level,
+    range: VersionRange,
+    affected: &Option<VersionReq>,
+    patched: &[VersionReq],
+    cvss: &Option<String>,
+    cwe: &[String],
+    references: &[String],
+    comment: &str,
+    affected_intervals: Vec<Interval>,
+) -> Record {
+    let package = &review.package.id;
+    let date = review.date_utc().to_rfc3339_opts(SecondsFormat::Secs, true);
+    // stable, so the record of the same advisory can be updated
+    let id = format!(
+        "CREV-{}",
+        crev_common::base64_encode(
+            &crev_common::blake2b256sum(
+                format!(
+                    "{}/{}/{}/{}/{}/{}",
+                    review.from().id,
+                    package.id.source,
+                    package.id.name,
+                    package.version,
+                    kind,
+                    ids.join(",")
+                )
+                .as_bytes()
+            )[..12]
+        )
+    );
+    let summary = comment
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .map(|line| line.trim_start_matches('#').trim().to_owned())
+        .unwrap_or_else(|| format!("{} {} of {}", kind, ids.join(", "), package.id.name));
+
+    Record {
+        schema_version: SCHEMA_VERSION.into(),
+        id,
+        modified: date.clone(),
+        published: date,
+        aliases: references.to_vec(),
+        summary,
+        details: comment.to_owned(),
+        severity: cvss
+            .iter()
+            .map(|cvss| Severity {
+                type_: "CVSS_V3".into(),
+                score: cvss.clone(),
+            })
+            .collect(),
+        affected: vec![Affected {
+            package: Package {
+                ecosystem: ECOSYSTEM.into(),
+                name: package.id.name.clone(),
+            },
+            ranges: vec![Range {
+                type_: "SEMVER".into(),
+                events: events(&affected_intervals),
+            }],
+            ecosystem_specific: EcosystemSpecific { severity },
+        }],
+        references: references
+            .iter()
+            .filter_map(|reference| reference_url(reference))
+            .map(|url| Reference {
+                type_: "ADVISORY".into(),
+                url,
+            })
+            .collect(),
+        database_specific: DatabaseSpecific {
+            cwe_ids: cwe.to_vec(),
+            crev: CrevDetails {
+                kind: kind.into(),
+                ids: ids.to_vec(),
+                reviewer: review.from().id.to_string(),
+                reviewer_url: review.from().url.as_ref().map(|url| url.url.clone()),
+                version: package.version.to_string(),
+                range,
+                affected: affected.as_ref().map(VersionReq::to_string),
+                patched: patched.iter().map(VersionReq::to_string).collect(),
+            },
+        },
+    }
+}
+
+fn reference_url(reference: &str) -> Option<String> {
+    if reference.starts_with("RUSTSEC-") {
+        Some(format!("https://rustsec.org/advisories/{}", reference))
+    } else if reference.starts_with("CVE-") {
+        Some(format!("https://nvd.nist.gov/vuln/detail/{}", reference))
+    } else if reference.starts_with("GHSA-") {
+        Some(format!("https://github.com/advisories/{}", reference))
+    } else {
+        None
+    }
+}
+
+/// OSV `SEMVER` range events for sorted, disjoint `intervals`
+fn events(intervals: &[Interval]) -> Vec<Event> {
+    let mut events = vec![];
+    for interval in intervals {
+        events.push(Event::Introduced(
+            if interval.start == Version::new(0, 0, 0) {
+                "0".into()
+            } else {
+                interval.start.to_string()
+            },
+        ));
+        if let Some(ref end) = interval.end {
+            events.push(Event::Fixed(end.to_string()));
+        }
+    }
+    events
+}
+
+/// Versions from `start` (inclusive), up to `end` (exclusive; `None` if unbounded)
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Interval {
+    start: Version,
+    end: Option<Version>,
+}
+
+impl Interval {
+    fn all() -> Self {
+        Interval {
+            start: Version::new(0, 0, 0),
+            end: None,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.end.as_ref().map_or(false, |end| *end <= self.start)
+    }
+
+    fn intersect(self, other: Interval) -> Interval {
+        Interval {
+            start: std::cmp::max(self.start, other.start),
+            end: match (self.end, other.end) {
+                (Some(a), Some(b)) => Some(std::cmp::min(a, b)),
+                (a, b) => a.or(b),
+            },
+        }
+    }
+
+    /// Parts of `self` not in any of `removed`
+    fn subtract(self, removed: &[Interval]) -> Vec<Interval> {
+        let mut removed: Vec<_> = removed.iter().filter(|i| !i.is_empty()).collect();
+        removed.sort_by(|a, b| a.start.cmp(&b.start));
+
+        let mut result = vec![];
+        let mut rest = Some(self);
+        for removed in removed {
+            let current = match rest.take() {
+                Some(current) => current,
+                None => break,
+            };
+            let before = Interval {
+                start: current.start.clone(),
+                end: Some(match current.end {
+                    Some(ref end) => std::cmp::min(end.clone(), removed.start.clone()),
+                    None => removed.start.clone(),
+                }),
+            };
+            if !before.is_empty() {
+                result.push(before);
+            }
+            rest = removed.end.as_ref().map(|removed_end| Interval {
+                start: std::cmp::max(current.start.clone(), removed_end.clone()),
+                end: current.end.clone(),
+            });
+        }
+        result.extend(rest.filter(|rest| !rest.is_empty()));
+        result
+    }
+
+    /// Versions matching `req`, if it can be expressed as a single interval
+    fn from_req(req: &VersionReq) -> Option<Interval> {
+        let req = req.to_string();
+        if req == "*" {
+            return Some(Interval::all());
+        }
+        req.split(", ")
+            .map(Interval::from_predicate)
+            .try_fold(Interval::all(), |acc, interval| {
+                Some(acc.intersect(interval?))
+            })
+    }
+
+    /// Interval of a single predicate, as displayed by `VersionReq`
+    fn from_predicate(predicate: &str) -> Option<Interval> {
+        let ops = [">=", "<=", ">", "<", "=", "~", "^"];
+        let (op, version) = match ops.iter().find(|op| predicate.starts_with(*op)) {
+            Some(op) => (*op, predicate[op.len()..].trim()),
+            // wildcards, like `1.2.*`
+            None => ("=", predicate),
+        };
+        let partial = PartialVersion::parse(version)?;
+        let zero = Version::new(0, 0, 0);
+
+        let (start, end) = match op {
+            ">=" => (partial.lowest(), None),
+            ">" => (partial.after(), None),
+            "<" => (zero, Some(partial.lowest())),
+            "<=" => (zero, Some(partial.after())),
+            "=" => (partial.lowest(), Some(partial.after())),
+            "~" => {
+                let end = match partial.minor {
+                    None => Version::new(partial.major + 1, 0, 0),
+                    Some(minor) => Version::new(partial.major, minor + 1, 0),
+                };
+                (partial.lowest(), Some(end))
+            }
+            "^" => {
+                let end = match (partial.major, partial.minor, partial.patch) {
+                    (0, None, _) => Version::new(1, 0, 0),
+                    (0, Some(0), None) => Version::new(0, 1, 0),
+                    (0, Some(0), Some(patch)) => Version::new(0, 0, patch + 1),
+                    (0, Some(minor), _) => Version::new(0, minor + 1, 0),
+                    (major, _, _) => Version::new(major + 1, 0, 0),
+                };
+                (partial.lowest(), Some(end))
+            }
+            _ => return None,
+        };
+        Some(Interval { start, end })
+    }
+}
+
+/// Version in a requirement, where minor and patch versions can be missing
+struct PartialVersion {
+    major: u64,
+    minor: Option<u64>,
+    patch: Option<u64>,
+    full: Option<Version>,
+}
+
+impl PartialVersion {
+    fn parse(s: &str) -> Option<Self> {
+        if let Ok(full) = Version::parse(s) {
+            return Some(PartialVersion {
+                major: full.major,
+                minor: Some(full.minor),
+                patch: Some(full.patch),
+                full: Some(full),
+            });
+        }
+        let mut parts = s.split('.').filter(|part| *part != "*");
+        let major = parts.next()?.parse().ok()?;
+        let minor = parts.next().map(str::parse).transpose().ok()?;
+        if parts.next().is_some() {
+            return None;
+        }
+        Some(PartialVersion {
+            major,
+            minor,
+            patch: None,
+            full: None,
+        })
+    }
+
+    /// The lowest version matching the partial version
+    fn lowest(&self) -> Version {
+        self.full.clone().unwrap_or_else(|| {
+            Version::new(self.major, self.minor.unwrap_or(0), self.patch.unwrap_or(0))
+        })
+    }
+
+    /// The lowest version after all the versions matching the partial version
+    fn after(&self) -> Version {
+        match (self.minor, self.patch) {
+            (None, _) => Version::new(self.major + 1, 0, 0),
+            (Some(minor), None) => Version::new(self.major, minor + 1, 0),
+            (Some(minor), Some(patch)) => Version::new(self.major, minor, patch + 1),
+        }
+    }
+}
+
+#[test]
+fn interval_from_req() {
+    let interval = |req: &str| Interval::from_req(&VersionReq::parse(req).unwrap());
+    let v = |s: &str| Version::parse(s).unwrap();
+
+    assert_eq!(
+        interval(">= 1.2.0, < 1.4.4"),
+        Some(Interval {
+            start: v("1.2.0"),
+            end: Some(v("1.4.4"))
+        })
+    );
+    assert_eq!(
+        interval("^0.3.1"),
+        Some(Interval {
+            start: v("0.3.1"),
+            end: Some(v("0.4.0"))
+        })
+    );
+    assert_eq!(
+        interval("1.2.*"),
+        Some(Interval {
+            start: v("1.2.0"),
+            end: Some(v("1.3.0"))
+        })
+    );
+    assert_eq!(
+        interval("<= 1.4"),
+        Some(Interval {
+            start: v("0.0.0"),
+            end: Some(v("1.5.0"))
+        })
+    );
+    assert_eq!(interval("*"), Some(Interval::all()));
+}
+
+#[test]
+fn interval_subtract() {
+    let v = |s: &str| Version::parse(s).unwrap();
+    let interval = |start: &str, end: Option<&str>| Interval {
+        start: v(start),
+        end: end.map(v),
+    };
+
+    assert_eq!(
+        Interval::all().subtract(&[
+            interval("1.4.4", None),
+            interval("0.0.0", Some("1.2.0")),
+            interval("1.3.2", Some("1.4.0")),
+        ]),
+        vec![
+            interval("1.2.0", Some("1.3.2")),
+            interval("1.4.0", Some("1.4.4"))
+        ]
+    );
+    assert_eq!(
+        events(&Interval::all().subtract(&[interval("2.0.0", None)])),
+        vec![Event::Introduced("0".into()), Event::Fixed("2.0.0".into())]
+    );
+}
@@ -6,6 +6,9 @@ fn proof_store_names(proof: &proof::Proof) -> (&str, Option<&str>) {
         proof::CodeReview::KIND => ("reviews", Some("code")),
         proof::PackageReview::KIND => ("reviews", Some("package")),
         proof::Trust::KIND => ("trust", None),
+        proof::Revocation::KIND => ("revocations", None),
+        proof::KeyLink::KIND => ("key-links", None),
+        proof::Team::KIND => ("teams", None),
         _ => ("other", None),
     }
 }
@@ -14,7 +17,7 @@ fn proof_store_names(proof: &proof::Proof) -> (&str, Option<&str>) {
 pub(crate) fn rel_store_path(proof: &proof::Proof, host_salt: &[u8]) -> PathBuf {
     let (type_name, type_subname) = proof_store_names(proof);
     let date = proof.date_utc().format("%Y-%m").to_string();
-    let path = PathBuf::from(proof.author_id().to_string()).join(type_name);
+    let path = PathBuf::from(proof.author_id().to_file_name()).join(type_name);
     let mut host_full_id = host_salt.to_vec();
     host_full_id.append(&mut proof.author_id().to_bytes());
     let host_plus_id_digest = crev_common::blake2b256sum(&host_full_id);
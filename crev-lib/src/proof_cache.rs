@@ -0,0 +1,112 @@
+//! On-disk cache of already verified proofs of fetched proof repositories
+//!
+//! Parsing and verifying every proof of every fetched repository
+//! on each start gets slow as the number of repositories grows.
+//! For each repository we store the verified proofs, together with
+//! the git `HEAD` they were read at. As long as the `HEAD` (and the version
+//! of crev-lib, which might parse more proofs) didn't change,
+//! the proofs are taken from the cache without walking the checkout
+//! and verifying signatures again.
+use crate::Result;
+use crev_common::result::ResultExt as _;
+use crev_data::proof;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Bump on every change of the cache file format, or of the proofs that can be parsed
+const CACHE_FORMAT_VERSION: i64 = -2;
+
+#[derive(Serialize, Deserialize)]
+struct CachedProof {
+    body: String,
+    signature: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedRepoProofs {
+    version: i64,
+    key: String,
+    proofs: Vec<CachedProof>,
+}
+
+/// Cache of proofs, one file per proof repository
+pub struct ProofCache {
+    dir: PathBuf,
+}
+
+impl ProofCache {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// Cache file of a repository checkout
+    fn path_for(&self, repo_path: &Path) -> Option<PathBuf> {
+        let name = repo_path.file_name()?;
+        Some(self.dir.join(name).with_extension("cbor"))
+    }
+
+    /// Key identifying current state of a git checkout
+    ///
+    /// Includes the version of this crate, as proofs an older version
+    /// couldn't parse (e.g. of new kinds) are missing from its cache.
+    pub fn key_for_git_repo(repo: &git2::Repository) -> Option<String> {
+        Some(format!(
+            "{}/{}",
+            env!("CARGO_PKG_VERSION"),
+            repo.head().ok()?.target()?
+        ))
+    }
+
+    /// Load proofs of a repository, if they were cached for the same `key`
+    pub fn load(&self, repo_path: &Path, key: &str) -> Option<Vec<proof::Proof>> {
+        let path = self.path_for(repo_path)?;
+        let file = fs::File::open(&path).ok()?;
+        let cached: CachedRepoProofs = serde_cbor::from_reader(file).ok()?;
+
+        if cached.version != CACHE_FORMAT_VERSION || cached.key != key {
+            return None;
+        }
+
+        cached
+            .proofs
+            .into_iter()
+            .map(|p| proof::Proof::from_parts(p.body, p.signature))
+            .collect::<std::result::Result<_, _>>()
+            .ok()
+    }
+
+    /// Store already verified proofs of a repository
+    pub fn store(&self, repo_path: &Path, key: &str, proofs: &[proof::Proof]) -> Result<()> {
+        let path = match self.path_for(repo_path) {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let cached = CachedRepoProofs {
+            version: CACHE_FORMAT_VERSION,
+            key: key.to_owned(),
+            proofs: proofs
+                .iter()
+                .map(|p| CachedProof {
+                    body: p.body().to_owned(),
+                    signature: p.signature().to_owned(),
+                })
+                .collect(),
+        };
+
+        fs::create_dir_all(&self.dir)?;
+        crev_common::store_to_file_with(&path, |file| serde_cbor::to_writer(file, &cached))??;
+        Ok(())
+    }
+
+    /// Drop cached proofs of a repository
+    pub fn invalidate(&self, repo_path: &Path) {
+        if let Some(path) = self.path_for(repo_path) {
+            if path.exists() {
+                fs::remove_file(&path).err_eprint_and_ignore();
+            }
+        }
+    }
+}
@@ -0,0 +1,191 @@
+use super::*;
+
+use crate::advisory_db::{advisory_db_public_id, read_advisory_db};
+use crev_data::{TrustLevel, UnlockedId};
+use crev_wot::FetchSource;
+use semver::Version;
+use std::fs;
+
+const SOURCE: &str = "https://crates.io";
+const LOCATION: &str = "https://github.com/rustsec/advisory-db";
+
+const MARKDOWN_ADVISORY: &str = r#"```toml
+[advisory]
+id = "RUSTSEC-2020-0001"
+package = "foo"
+date = "2020-01-02"
+aliases = ["CVE-2020-12345"]
+cvss = "CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H"
+
+[versions]
+patched = [">= 1.4.4"]
+unaffected = ["< 1.2.0"]
+```
+
+# Overflow in `foo::parse`
+
+Lengths are not checked.
+"#;
+
+const TOML_ADVISORY: &str = r#"[advisory]
+id = "RUSTSEC-2019-0002"
+package = "bar"
+date = "2019-03-04"
+title = "bar is unmaintained"
+description = "Use baz instead."
+informational = "unmaintained"
+"#;
+
+const OTHER_MARKDOWN_ADVISORY: &str = r#"```toml
+[advisory]
+id = "RUSTSEC-2020-0004"
+package = "foo"
+date = "2020-03-04"
+
+[versions]
+patched = [">= 1.4.4"]
+```
+
+# Use after free in `foo::Buf`
+"#;
+
+const WITHDRAWN_ADVISORY: &str = r#"```toml
+[advisory]
+id = "RUSTSEC-2020-0003"
+package = "foo"
+date = "2020-05-06"
+withdrawn = "2020-05-07"
+```
+
+# Not a bug
+"#;
+
+#[test]
+fn advisory_db_import() -> Result<()> {
+    let tmp_dir = tempdir::TempDir::new("crev-advisory-db")?;
+    for (name, file, content) in &[
+        ("foo", "RUSTSEC-2020-0001.md", MARKDOWN_ADVISORY),
+        ("bar", "RUSTSEC-2019-0002.toml", TOML_ADVISORY),
+        ("foo", "RUSTSEC-2020-0003.md", WITHDRAWN_ADVISORY),
+        ("foo", "README.md", "not an advisory"),
+    ] {
+        let dir = tmp_dir.path().join("crates").join(name);
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join(file), content)?;
+    }
+
+    let reviews = read_advisory_db(tmp_dir.path(), LOCATION)?;
+    assert_eq!(reviews.len(), 2);
+    let foo = reviews
+        .iter()
+        .find(|review| review.package.id.id.name == "foo")
+        .unwrap();
+    assert_eq!(foo.package.id.version, Version::parse("1.4.4").unwrap());
+    assert_eq!(foo.from().url.as_ref().unwrap().url, LOCATION);
+    assert_eq!(foo.advisories[0].references, vec!["CVE-2020-12345"]);
+    assert!(foo.advisories[0]
+        .comment
+        .starts_with("Overflow in `foo::parse`\n\nLengths are not checked."));
+    let bar = reviews
+        .iter()
+        .find(|review| review.package.id.id.name == "bar")
+        .unwrap();
+    assert!(bar.flags.unmaintained);
+
+    let id = UnlockedId::generate_for_git_url("https://a");
+    let mut proofdb = ProofDB::new();
+    proofdb.import_unsigned_package_reviews(
+        reviews.into_iter(),
+        FetchSource::AdvisoryDb(Arc::new(Url::new_git(LOCATION))),
+        Some(id.as_ref()),
+        TrustLevel::Low,
+    );
+    let trust_set = proofdb.calculate_trust_set(id.as_ref(), &TrustDistanceParams::default());
+    assert_eq!(
+        trust_set.get_effective_trust_level(&advisory_db_public_id(LOCATION).id),
+        TrustLevel::Low
+    );
+    // other Ids don't trust the database just because the local user does
+    let other_id = UnlockedId::generate_for_git_url("https://b");
+    let other_trust_set =
+        proofdb.calculate_trust_set(other_id.as_ref(), &TrustDistanceParams::default());
+    assert!(!other_trust_set.contains_trusted(&advisory_db_public_id(LOCATION).id));
+
+    let advisory_count = |version: &str| {
+        proofdb
+            .get_advisories_for_version(SOURCE, "foo", &Version::parse(version).unwrap())
+            .count()
+    };
+    assert_eq!(advisory_count("1.1.0"), 0);
+    assert_eq!(advisory_count("1.3.0"), 1);
+    assert_eq!(advisory_count("1.4.4"), 0);
+
+    let open_issues = |version: &str, trust_level: TrustLevel| {
+        proofdb
+            .get_open_issues_for_version(
+                SOURCE,
+                "foo",
+                &Version::parse(version).unwrap(),
+                &trust_set,
+                trust_level,
+            )
+            .len()
+    };
+    assert_eq!(open_issues("1.3.0", TrustLevel::Low), 1);
+    assert_eq!(open_issues("1.3.0", TrustLevel::Medium), 0);
+
+    // the database can be distrusted like any other Id
+    proofdb.import_from_iter(
+        vec![(
+            id.create_signed_trust_proof(
+                vec![&advisory_db_public_id(LOCATION)],
+                TrustLevel::Distrust,
+            )?,
+            FetchSource::LocalUser,
+        )]
+        .into_iter(),
+    );
+    let trust_set = proofdb.calculate_trust_set(id.as_ref(), &TrustDistanceParams::default());
+    assert!(!trust_set.contains_trusted(&advisory_db_public_id(LOCATION).id));
+    assert!(trust_set.contains_distrusted(&advisory_db_public_id(LOCATION).id));
+    Ok(())
+}
+
+#[test]
+fn advisory_db_merges_advisories_of_a_version() -> Result<()> {
+    let tmp_dir = tempdir::TempDir::new("crev-advisory-db")?;
+    let dir = tmp_dir.path().join("crates").join("foo");
+    fs::create_dir_all(&dir)?;
+    fs::write(dir.join("RUSTSEC-2020-0001.md"), MARKDOWN_ADVISORY)?;
+    fs::write(dir.join("RUSTSEC-2020-0004.md"), OTHER_MARKDOWN_ADVISORY)?;
+
+    // both are filed under the first patched version, 1.4.4
+    let reviews = read_advisory_db(tmp_dir.path(), LOCATION)?;
+    assert_eq!(reviews.len(), 1);
+    assert_eq!(reviews[0].advisories.len(), 2);
+
+    let id = UnlockedId::generate_for_git_url("https://a");
+    let mut proofdb = ProofDB::new();
+    proofdb.import_unsigned_package_reviews(
+        reviews.into_iter(),
+        FetchSource::AdvisoryDb(Arc::new(Url::new_git(LOCATION))),
+        Some(id.as_ref()),
+        TrustLevel::Low,
+    );
+    let trust_set = proofdb.calculate_trust_set(id.as_ref(), &TrustDistanceParams::default());
+    let open_issues = |version: &str| {
+        proofdb
+            .get_open_issues_for_version(
+                SOURCE,
+                "foo",
+                &Version::parse(version).unwrap(),
+                &trust_set,
+                TrustLevel::Low,
+            )
+            .len()
+    };
+    assert_eq!(open_issues("1.1.0"), 1);
+    assert_eq!(open_issues("1.3.0"), 2);
+    assert_eq!(open_issues("1.4.4"), 0);
+    Ok(())
+}
@@ -49,7 +49,7 @@ fn build_proof_with_advisories(
         .build()
         .unwrap();
 
-    review.sign_by(&id).unwrap()
+    review.sign_by(id).unwrap()
 }
 
 fn build_proof_with_issues(id: &UnlockedId, version: Version, issues: Vec<Issue>) -> proof::Proof {
@@ -68,7 +68,7 @@ fn build_proof_with_issues(id: &UnlockedId, version: Version, issues: Vec<Issue>
         .build()
         .unwrap();
 
-    review.sign_by(&id).unwrap()
+    review.sign_by(id).unwrap()
 }
 
 #[test]
@@ -141,6 +141,33 @@ fn advisories_sanity() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn advisories_with_exact_versions() -> Result<()> {
+    let url = FetchSource::LocalUser;
+    let id = UnlockedId::generate_for_git_url("https://a");
+
+    // reported in a review of an older version, for versions after it
+    let mut advisory = build_advisory("someid", VersionRange::All);
+    advisory.affected = Some(semver::VersionReq::parse(">= 1.2.0, < 1.4.4").unwrap());
+    let proof = build_proof_with_advisories(&id, Version::parse("1.0.0").unwrap(), vec![advisory]);
+
+    let mut proofdb = ProofDB::new();
+    proofdb.import_from_iter(vec![(proof, url)].into_iter());
+
+    let count = |version: &str| {
+        proofdb
+            .get_advisories_for_version(SOURCE, NAME, &Version::parse(version).unwrap())
+            .count()
+    };
+    assert_eq!(count("0.9.0"), 0);
+    assert_eq!(count("1.0.0"), 0);
+    assert_eq!(count("1.2.0"), 1);
+    assert_eq!(count("1.4.3"), 1);
+    assert_eq!(count("1.4.4"), 0);
+
+    Ok(())
+}
+
 #[test]
 fn issues_sanity() -> Result<()> {
     let url = FetchSource::LocalUser;
@@ -329,3 +356,45 @@ fn issues_sanity() -> Result<()> {
     );
     Ok(())
 }
+
+#[test]
+fn issues_with_exact_versions() -> Result<()> {
+    let url = FetchSource::LocalUser;
+    let id = UnlockedId::generate_for_git_url("https://a");
+    let mut trustdb = ProofDB::new();
+    let trust_set = trustdb.calculate_trust_set(id.as_ref(), &TrustDistanceParams::new_no_wot());
+
+    // found while reviewing 1.3.0, but affecting earlier versions too
+    let mut issue = build_issue("issueX");
+    issue.affected = Some(semver::VersionReq::parse(">= 1.2.0, <= 1.4.3").unwrap());
+    let proof = build_proof_with_issues(&id, Version::parse("1.3.0").unwrap(), vec![issue]);
+    trustdb.import_from_iter(vec![(proof, url.clone())].into_iter());
+
+    let open_issues = |trustdb: &ProofDB, version: &str| {
+        trustdb
+            .get_open_issues_for_version(
+                SOURCE,
+                NAME,
+                &Version::parse(version).unwrap(),
+                &trust_set,
+                TrustLevel::Medium,
+            )
+            .len()
+    };
+    assert_eq!(open_issues(&trustdb, "1.1.9"), 0);
+    assert_eq!(open_issues(&trustdb, "1.2.0"), 1);
+    assert_eq!(open_issues(&trustdb, "1.4.3"), 1);
+    assert_eq!(open_issues(&trustdb, "1.4.4"), 0);
+
+    // an advisory covering the version the issue was found in supersedes the issue
+    let mut advisory = build_advisory("issueX", VersionRange::All);
+    advisory.affected = Some(semver::VersionReq::parse(">= 1.2.0, < 1.4.0").unwrap());
+    let proof = build_proof_with_advisories(&id, Version::parse("1.4.0").unwrap(), vec![advisory]);
+    trustdb.import_from_iter(vec![(proof, url)].into_iter());
+
+    assert_eq!(open_issues(&trustdb, "1.2.0"), 1);
+    assert_eq!(open_issues(&trustdb, "1.3.9"), 1);
+    assert_eq!(open_issues(&trustdb, "1.4.0"), 0);
+    assert_eq!(open_issues(&trustdb, "1.4.3"), 0);
+    Ok(())
+}
@@ -0,0 +1,142 @@
+use super::*;
+
+use crate::osv::{advisory_to_osv, issue_to_osv, Event};
+use crev_data::{
+    proof,
+    review::{Advisory, Issue, VersionRange},
+    UnlockedId,
+};
+use semver::{Version, VersionReq};
+
+fn build_review(version: &str, advisories: Vec<Advisory>, issues: Vec<Issue>) -> review::Package {
+    let id = UnlockedId::generate_for_git_url("https://a");
+    proof::review::PackageBuilder::default()
+        .from(id.id.to_owned())
+        .package(proof::PackageInfo {
+            id: proof::PackageVersionId::new(
+                "https://crates.io".into(),
+                "foo".into(),
+                Version::parse(version).unwrap(),
+            ),
+            digest: vec![0, 1, 2, 3],
+            digest_type: proof::default_digest_type(),
+            revision: "".into(),
+            revision_type: proof::default_revision_type(),
+        })
+        .advisories(advisories)
+        .issues(issues)
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn osv_export() -> Result<()> {
+    let advisory = Advisory::builder()
+        .ids(vec!["overflow".into()])
+        .range(VersionRange::Minor)
+        .references(vec!["RUSTSEC-2020-0001".into()])
+        .comment("Overflow in `foo::parse`\n\nLengths are not checked.".into())
+        .build();
+    let exact_advisory = Advisory::builder()
+        .ids(vec!["panic".into()])
+        .affected(Some(VersionReq::any()))
+        .patched(vec![
+            VersionReq::parse(">= 1.4.4").unwrap(),
+            VersionReq::parse("< 1.2.0").unwrap(),
+        ])
+        .build();
+    let issue = Issue::builder()
+        .id("leak".into())
+        .affected(Some(VersionReq::parse(">= 1.2.0, <= 1.4.3").unwrap()))
+        .build();
+    let review = build_review("1.4.4", vec![advisory, exact_advisory], vec![issue]);
+
+    let record = advisory_to_osv(&review, &review.advisories[0]).unwrap();
+    assert!(record.id.starts_with("CREV-"));
+    assert_eq!(record.summary, "Overflow in `foo::parse`");
+    assert_eq!(record.aliases, vec!["RUSTSEC-2020-0001"]);
+    assert_eq!(
+        record.references[0].url,
+        "https://rustsec.org/advisories/RUSTSEC-2020-0001"
+    );
+    assert_eq!(record.affected[0].package.name, "foo");
+    assert_eq!(
+        record.affected[0].ranges[0].events,
+        vec![
+            Event::Introduced("1.4.0".into()),
+            Event::Fixed("1.4.4".into())
+        ]
+    );
+
+    let record = advisory_to_osv(&review, &review.advisories[1]).unwrap();
+    assert_eq!(
+        record.affected[0].ranges[0].events,
+        vec![
+            Event::Introduced("1.2.0".into()),
+            Event::Fixed("1.4.4".into())
+        ]
+    );
+
+    let record = issue_to_osv(&review, &review.issues[0], vec![]).unwrap();
+    assert_eq!(
+        record.affected[0].ranges[0].events,
+        vec![
+            Event::Introduced("1.2.0".into()),
+            Event::Fixed("1.4.4".into())
+        ]
+    );
+    let json = serde_yaml::to_value(&record)?;
+    assert_eq!(json["affected"][0]["ranges"][0]["type"], "SEMVER");
+    assert_eq!(
+        json["affected"][0]["ranges"][0]["events"][1]["fixed"],
+        "1.4.4"
+    );
+    assert_eq!(json["database_specific"]["crev"]["kind"], "issue");
+    Ok(())
+}
+
+#[test]
+fn osv_export_skips_unaffected_and_fixed_versions() -> Result<()> {
+    // every affected version is patched
+    let advisory = Advisory::builder()
+        .ids(vec!["panic".into()])
+        .affected(Some(VersionReq::parse(">= 2.0.0").unwrap()))
+        .patched(vec![VersionReq::parse(">= 1.0.0").unwrap()])
+        .build();
+    let review = build_review("2.1.0", vec![advisory], vec![]);
+    assert!(advisory_to_osv(&review, &review.advisories[0]).is_none());
+
+    let issue = Issue::builder()
+        .id("leak".into())
+        .range(VersionRange::All)
+        .build();
+    let issue_review = build_review("1.2.0", vec![], vec![issue]);
+    let fix = Advisory::builder()
+        .ids(vec!["leak".into()])
+        .range(VersionRange::All)
+        .build();
+    let fix_review = build_review("1.4.4", vec![fix], vec![]);
+
+    let record = issue_to_osv(&issue_review, &issue_review.issues[0], vec![]).unwrap();
+    assert_eq!(
+        record.affected[0].ranges[0].events,
+        vec![Event::Introduced("1.2.0".into())]
+    );
+    let record = issue_to_osv(&issue_review, &issue_review.issues[0], vec![&fix_review]).unwrap();
+    assert_eq!(
+        record.affected[0].ranges[0].events,
+        vec![
+            Event::Introduced("1.2.0".into()),
+            Event::Fixed("1.4.4".into())
+        ]
+    );
+
+    // fixed before it was reported
+    let issue_review = build_review("1.5.0", vec![], issue_review.issues.clone());
+    let record = issue_to_osv(&issue_review, &issue_review.issues[0], vec![&fix_review]).unwrap();
+    assert_eq!(
+        record.affected[0].ranges[0].events,
+        vec![Event::Introduced("1.5.0".into())]
+    );
+    Ok(())
+}
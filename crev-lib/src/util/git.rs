@@ -34,7 +34,12 @@ pub fn parse_git_url_https(http_url: &str) -> Option<GitUrlComponents> {
 }
 
 pub fn fetch_and_checkout_git_repo(repo: &git2::Repository) -> Result<()> {
-    repo.find_remote("origin")?.fetch(&["master"], None, None)?;
+    fetch_and_checkout_git_ref(repo, "master")
+}
+
+/// Fetch `git_ref` (like a branch name, or `HEAD`) from `origin` and check it out
+pub fn fetch_and_checkout_git_ref(repo: &git2::Repository, git_ref: &str) -> Result<()> {
+    repo.find_remote("origin")?.fetch(&[git_ref], None, None)?;
     repo.set_head("FETCH_HEAD")?;
     let mut opts = git2::build::CheckoutBuilder::new();
     opts.force();
@@ -0,0 +1,235 @@
+//! Export of the trust graph, for visualisation and other tools
+//!
+//! The graph consists of the Ids reachable from the root of a `TrustSet`
+//! (and the Ids distrusted by them), with the trust proofs between them
+//! as edges.
+use crate::{ProofDB, TrustSet, UrlOfId};
+use crev_data::{proof::trust::TrustLevel, Id};
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    fmt::{self, Write as _},
+};
+
+/// How much a URL of an Id can be relied on
+#[derive(Copy, Clone, Debug, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum UrlStatus {
+    /// Signed by the Id and verified by fetching
+    Verified,
+    /// Signed by the Id
+    SelfReported,
+    /// Reported by someone else
+    ReportedByOthers,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct TrustGraphNode {
+    pub id: Id,
+    pub url: Option<String>,
+    pub url_status: Option<UrlStatus>,
+    /// Effective trust from the root (`distrust` for distrusted Ids)
+    pub effective_trust: TrustLevel,
+    /// Distance from the root, as used by trust calculation
+    pub distance: Option<u64>,
+    /// Number of trust edges from the root
+    pub depth: u64,
+    pub distrusted_by: Vec<Id>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct TrustGraphEdge {
+    pub from: Id,
+    pub to: Id,
+    pub trust: TrustLevel,
+}
+
+/// Trust graph as seen from a root Id
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct TrustGraph {
+    pub root: Id,
+    pub nodes: Vec<TrustGraphNode>,
+    pub edges: Vec<TrustGraphEdge>,
+}
+
+impl ProofDB {
+    /// All trust proofs: who trusts whom and how much (most recent proof only)
+    pub fn get_trust_edges(&self) -> impl Iterator<Item = (&Id, &Id, TrustLevel)> {
+        self.trust_id_to_id.iter().flat_map(|(from, to_map)| {
+            to_map
+                .iter()
+                .map(move |(to, trust)| (from, to, trust.value.level))
+        })
+    }
+
+    /// Build the trust graph of `trust_set`, calculated for `root`
+    ///
+    /// Only Ids at most `max_depth` trust edges away from the root are included.
+    /// Only edges starting at trusted Ids are included, as proofs of
+    /// anyone else don't affect the trust calculation.
+    pub fn get_trust_graph(
+        &self,
+        root: &Id,
+        trust_set: &TrustSet,
+        max_depth: Option<u64>,
+    ) -> TrustGraph {
+        let root = root.clone();
+        // breadth-first, so every Id gets the lowest depth
+        let mut depths: HashMap<Id, u64> = HashMap::new();
+        let mut pending = VecDeque::new();
+        depths.insert(root.clone(), 0);
+        pending.push_back(root.clone());
+
+        while let Some(id) = pending.pop_front() {
+            let depth = depths[&id];
+            if !trust_set.contains_trusted(&id) || max_depth.map_or(false, |max| max <= depth) {
+                continue;
+            }
+            for (to, _) in self
+                .trust_id_to_id
+                .get(&id)
+                .into_iter()
+                .flat_map(|to_map| to_map.iter())
+            {
+                let is_relevant =
+                    trust_set.contains_trusted(to) || trust_set.contains_distrusted(to);
+                if is_relevant && !depths.contains_key(to) {
+                    depths.insert(to.clone(), depth + 1);
+                    pending.push_back(to.clone());
+                }
+            }
+        }
+
+        let mut nodes: Vec<_> = depths
+            .iter()
+            .map(|(id, &depth)| {
+                let url = self.lookup_url(id);
+                let url_status = match url {
+                    UrlOfId::FromSelfVerified(_) => Some(UrlStatus::Verified),
+                    UrlOfId::FromSelf(_) => Some(UrlStatus::SelfReported),
+                    UrlOfId::FromOthers(_) => Some(UrlStatus::ReportedByOthers),
+                    UrlOfId::None => None,
+                };
+                let mut distrusted_by: Vec<_> = trust_set
+                    .get_distrusted_by(id)
+                    .into_iter()
+                    .flat_map(|ids| ids.iter())
+                    .filter(|id| depths.contains_key(id))
+                    .cloned()
+                    .collect();
+                distrusted_by.sort();
+
+                TrustGraphNode {
+                    id: id.clone(),
+                    url: url.any_unverified().map(|url| url.url.clone()),
+                    url_status,
+                    effective_trust: if trust_set.contains_distrusted(id) {
+                        TrustLevel::Distrust
+                    } else {
+                        trust_set.get_effective_trust_level(id)
+                    },
+                    distance: trust_set.get_distance(id),
+                    depth,
+                    distrusted_by,
+                }
+            })
+            .collect();
+        nodes.sort_by(|a, b| a.depth.cmp(&b.depth).then_with(|| a.id.cmp(&b.id)));
+
+        let mut edges: Vec<_> = self
+            .get_trust_edges()
+            .filter(|(from, to, _)| {
+                trust_set.contains_trusted(from)
+                    && depths.contains_key(from)
+                    && depths.contains_key(to)
+                    && *from != *to
+            })
+            .filter(|(from, _, _)| max_depth.map_or(true, |max| depths[*from] < max))
+            .map(|(from, to, trust)| TrustGraphEdge {
+                from: from.clone(),
+                to: to.clone(),
+                trust,
+            })
+            .collect();
+        edges.sort_by(|a, b| a.from.cmp(&b.from).then_with(|| a.to.cmp(&b.to)));
+
+        TrustGraph { root, nodes, edges }
+    }
+}
+
+impl TrustGraph {
+    /// Render as Graphviz DOT
+    pub fn to_dot(&self) -> String {
+        let mut out = String::new();
+        self.write_dot(&mut out)
+            .expect("writing to a String can't fail");
+        out
+    }
+
+    fn write_dot(&self, out: &mut impl fmt::Write) -> fmt::Result {
+        // shortened Ids keep the picture readable
+        let short_ids: BTreeMap<&Id, String> = self
+            .nodes
+            .iter()
+            .map(|node| (&node.id, node.id.to_string().chars().take(8).collect()))
+            .collect();
+
+        writeln!(out, "digraph trust {{")?;
+        writeln!(out, "  node [shape=box, style=rounded];")?;
+        for node in &self.nodes {
+            let mut label = short_ids[&node.id].clone();
+            if let Some(url) = &node.url {
+                write!(label, "\\n{}", dot_escape(url))?;
+            }
+            write!(label, "\\n{}", node.effective_trust)?;
+            if let Some(distance) = node.distance {
+                write!(label, " ({})", distance)?;
+            }
+            writeln!(
+                out,
+                "  \"{}\" [label=\"{}\", color={}{}];",
+                node.id,
+                label,
+                trust_color(node.effective_trust),
+                if node.id == self.root {
+                    ", penwidth=2"
+                } else {
+                    ""
+                }
+            )?;
+        }
+        for edge in &self.edges {
+            writeln!(
+                out,
+                "  \"{}\" -> \"{}\" [label=\"{}\", color={}{}];",
+                edge.from,
+                edge.to,
+                edge.trust,
+                trust_color(edge.trust),
+                if edge.trust == TrustLevel::Distrust {
+                    ", style=dashed"
+                } else {
+                    ""
+                }
+            )?;
+        }
+        writeln!(out, "}}")
+    }
+}
+
+fn trust_color(trust: TrustLevel) -> &'static str {
+    match trust {
+        TrustLevel::Distrust => "red",
+        TrustLevel::None => "gray",
+        TrustLevel::Low => "orange",
+        TrustLevel::Medium => "blue",
+        TrustLevel::High => "darkgreen",
+    }
+}
+
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -23,9 +23,14 @@ use log::debug;
 use semver::Version;
 use std::{
     collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    path::{Path, PathBuf},
     sync,
 };
 
+mod graph;
+
+pub use graph::{TrustGraph, TrustGraphEdge, TrustGraphNode, UrlStatus};
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("Unknown proof type '{}'", _0)]
@@ -44,6 +49,8 @@ pub enum FetchSource {
     Url(sync::Arc<Url>),
     /// One of user's own proof repos, which are assumed to contain only verified information
     LocalUser,
+    /// Advisory database (like RustSec's), with unsigned advisories
+    AdvisoryDb(sync::Arc<Url>),
 }
 
 /// A `T` with a timestamp
@@ -87,16 +94,34 @@ where
 
 pub type Signature = String;
 type TimestampedUrl = Timestamped<Url>;
-type TimestampedTrustLevel = Timestamped<TrustLevel>;
+type TimestampedTrustEdge = Timestamped<TrustEdge>;
 type TimestampedReview = Timestamped<review::Review>;
 type TimestampedSignature = Timestamped<Signature>;
 type TimestampedFlags = Timestamped<proof::Flags>;
 
-impl From<proof::Trust> for TimestampedTrustLevel {
-    fn from(trust: proof::Trust) -> Self {
-        TimestampedTrustLevel {
+/// Members of a team Id, as declared by its most recent team proof
+#[derive(Clone, Debug)]
+pub struct Team {
+    pub members: Vec<Id>,
+    /// Number of member reviews making a review of the team
+    pub threshold: u64,
+}
+
+/// Trust of one Id in another, as stated by the most recent trust proof
+#[derive(Clone, Debug)]
+struct TrustEdge {
+    level: TrustLevel,
+    valid_until: Option<DateTime<Utc>>,
+}
+
+impl From<&proof::Trust> for TimestampedTrustEdge {
+    fn from(trust: &proof::Trust) -> Self {
+        TimestampedTrustEdge {
             date: trust.date_utc(),
-            value: trust.trust,
+            value: TrustEdge {
+                level: trust.trust,
+                valid_until: trust.valid_until.map(|date| date.with_timezone(&Utc)),
+            },
         }
     }
 }
@@ -174,6 +199,9 @@ impl From<&review::Package> for PkgReviewId {
 pub type Source = String;
 pub type Name = String;
 
+/// Source and name of a package, path of a file in it, and digest of the file
+type FileReviewKey = (Source, Name, PathBuf, Vec<u8>);
+
 /// Alternatives relationship
 ///
 /// Derived from the data in the proofs
@@ -235,7 +263,7 @@ impl AlternativesData {
 /// of some kind.
 pub struct ProofDB {
     /// who -(trusts)-> whom
-    trust_id_to_id: HashMap<Id, HashMap<Id, TimestampedTrustLevel>>,
+    trust_id_to_id: HashMap<Id, HashMap<Id, TimestampedTrustEdge>>,
 
     /// Id->URL mapping verified by Id's signature
     /// boolean is whether it's been fetched from the same URL, or local trusted repo,
@@ -253,6 +281,12 @@ pub struct ProofDB {
         HashMap<Vec<u8>, HashMap<PkgVersionReviewId, TimestampedSignature>>,
     package_review_signatures_by_pkg_review_id: HashMap<PkgVersionReviewId, TimestampedSignature>,
 
+    // all code reviews, by signature
+    code_review_by_signature: HashMap<Signature, review::Code>,
+
+    // file -> reviewer -> the most recent code review of that file with that content
+    code_review_signatures_by_file: HashMap<FileReviewKey, HashMap<Id, TimestampedSignature>>,
+
     // pkg_review_id by package information, nicely grouped
     package_reviews:
         BTreeMap<Source, BTreeMap<Name, BTreeMap<Version, HashSet<PkgVersionReviewId>>>>,
@@ -265,6 +299,27 @@ pub struct ProofDB {
     // the newest alternatives list for a `(PackageId, reporting Id)` pair
     package_alternatives: HashMap<proof::PackageId, HashMap<Id, TimestampedSignature>>,
 
+    // revoked Ids, with the date since which their proofs are invalid
+    // (`None` if all of them are)
+    revoked_since: HashMap<Id, Option<DateTime<Utc>>>,
+
+    // old Id -> new Id it was rotated to
+    key_links: HashMap<Id, Timestamped<Id>>,
+
+    // team Id -> its members
+    teams: HashMap<Id, Timestamped<Team>>,
+
+    // authors of unsigned reviews (advisory databases) -> the Id trusting them, and how much
+    unsigned_authors_trust: HashMap<Id, Option<(Id, TrustLevel)>>,
+
+    // all imported proofs, and unsigned reviews with the signatures they are
+    // stored by, to import them again when a revocation invalidates some
+    proofs: Vec<(proof::Proof, FetchSource)>,
+    unsigned_package_reviews: Vec<(review::Package, Signature, FetchSource)>,
+
+    // date of the most recent imported proof of every author
+    last_proof_date_by_author: HashMap<Id, DateTime<Utc>>,
+
     // derived data about pkg alternatives
     // it is hard to keep track of some data when proofs are being added
     // which can override previously stored information; because of that
@@ -278,12 +333,21 @@ impl Default for ProofDB {
     fn default() -> Self {
         ProofDB {
             trust_id_to_id: default(),
+            revoked_since: default(),
+            key_links: default(),
+            teams: default(),
+            unsigned_authors_trust: default(),
+            proofs: default(),
+            unsigned_package_reviews: default(),
+            last_proof_date_by_author: default(),
             url_by_id_self_reported: default(),
             url_by_id_reported_by_others: default(),
             package_review_signatures_by_package_digest: default(),
             package_review_signatures_by_pkg_review_id: default(),
             package_review_by_signature: default(),
             package_reviews: default(),
+            code_review_by_signature: default(),
+            code_review_signatures_by_file: default(),
             package_alternatives: default(),
             package_flags: default(),
 
@@ -394,6 +458,13 @@ impl ProofDB {
             .map(|(id, flags)| (id, &flags.value))
     }
 
+    /// Reviews of packages from all sources
+    pub fn get_pkg_reviews<'a>(&'a self) -> impl Iterator<Item = &'a proof::review::Package> {
+        self.package_reviews
+            .keys()
+            .flat_map(move |source| self.get_pkg_reviews_for_source(source))
+    }
+
     pub fn get_pkg_reviews_for_source<'a, 'b>(
         &'a self,
         source: &'b str,
@@ -427,6 +498,17 @@ impl ProofDB {
             })
     }
 
+    /// Reviews of a package at a given revision (a git commit), of any version
+    pub fn get_pkg_reviews_for_revision<'a, 'b, 'c: 'a, 'd: 'a>(
+        &'a self,
+        source: &'b str,
+        name: &'c str,
+        revision: &'d str,
+    ) -> impl Iterator<Item = &'a proof::review::Package> {
+        self.get_pkg_reviews_for_name(source, name)
+            .filter(move |review| !revision.is_empty() && review.package.revision == revision)
+    }
+
     pub fn get_pkg_reviews_for_version<'a, 'b, 'c: 'a, 'd: 'a>(
         &'a self,
         source: &'b str,
@@ -558,7 +640,8 @@ impl ProofDB {
         name: &'c str,
         version: &'d Version,
     ) -> impl Iterator<Item = &proof::review::Package> {
-        self.get_pkg_reviews_gte_version(source, name, version)
+        // advisories with explicit `affected` versions can come from reviews of any version
+        self.get_pkg_reviews_for_name(source, name)
             .filter(move |review| review.is_advisory_for(&version))
     }
 
@@ -615,10 +698,11 @@ impl ProofDB {
         // Here we track all the reported isue by issue id
         let mut issue_reports_by_id: HashMap<String, IssueDetails> = HashMap::new();
 
-        // First we go through all the reports in previous versions with `issues` fields and collect these.
-        // Easy.
+        // First we go through all the reports with `issues` fields and collect these.
+        // Usually only previous versions are relevant, but an issue with explicit
+        // `affected` versions can be reported in any version. Easy.
         for (review, issue) in self
-            .get_pkg_reviews_lte_version(source, name, queried_version)
+            .get_pkg_reviews_for_name(source, name)
             .filter(|review| {
                 let effective = trust_set.get_effective_trust_level(&review.from().id);
                 effective >= trust_level_required
@@ -765,14 +849,61 @@ impl ProofDB {
             .fold(0, |count, (_id, set)| count + set.len())
     }
 
-    fn add_code_review(&mut self, review: &review::Code, fetched_from: FetchSource) {
+    fn add_code_review(
+        &mut self,
+        review: &review::Code,
+        signature: &str,
+        fetched_from: FetchSource,
+    ) {
         let from = &review.from();
         self.record_url_from_from_field(&review.date_utc(), &from, &fetched_from);
-        for _file in &review.files {
-            // not implemented right now; just ignore
+
+        self.code_review_by_signature
+            .entry(signature.to_owned())
+            .or_insert_with(|| review.to_owned());
+
+        let timestamp_signature = TimestampedSignature::from((review.date(), signature.to_owned()));
+        for file in &review.files {
+            self.code_review_signatures_by_file
+                .entry((
+                    review.package.id.id.source.clone(),
+                    review.package.id.id.name.clone(),
+                    file.path.clone(),
+                    file.digest.clone(),
+                ))
+                .or_default()
+                .entry(from.id.clone())
+                .and_modify(|s| s.update_to_more_recent(&timestamp_signature))
+                .or_insert_with(|| timestamp_signature.clone());
         }
     }
 
+    /// Code reviews of the file at `path` (relative to the package root) of
+    /// any version of a package, with this content
+    ///
+    /// The most recent one of every reviewer.
+    pub fn get_code_reviews_for_file<'a>(
+        &'a self,
+        source: &str,
+        name: &str,
+        path: &Path,
+        digest: &[u8],
+    ) -> impl Iterator<Item = &'a review::Code> + 'a {
+        self.code_review_signatures_by_file
+            .get(&(
+                source.to_owned(),
+                name.to_owned(),
+                path.to_owned(),
+                digest.to_owned(),
+            ))
+            .into_iter()
+            .flat_map(move |signatures| {
+                signatures
+                    .values()
+                    .map(move |signature| &self.code_review_by_signature[&signature.value])
+            })
+    }
+
     fn add_package_review(
         &mut self,
         review: &review::Package,
@@ -871,8 +1002,7 @@ impl ProofDB {
         proofs
     }
 
-    fn add_trust_raw(&mut self, from: &Id, to: &Id, date: DateTime<Utc>, trust: TrustLevel) {
-        let tl = TimestampedTrustLevel { value: trust, date };
+    fn add_trust_raw(&mut self, from: &Id, to: &Id, tl: TimestampedTrustEdge) {
         self.trust_id_to_id
             .entry(from.to_owned())
             .or_insert_with(HashMap::new)
@@ -885,7 +1015,7 @@ impl ProofDB {
         let from = &trust.from();
         self.record_url_from_from_field(&trust.date_utc(), &from, &fetched_from);
         for to in &trust.ids {
-            self.add_trust_raw(&from.id, &to.id, trust.date_utc(), trust.trust);
+            self.add_trust_raw(&from.id, &to.id, trust.into());
         }
         for to in &trust.ids {
             // Others should not be making verified claims about this URL,
@@ -895,6 +1025,121 @@ impl ProofDB {
         }
     }
 
+    fn add_revocation(&mut self, revocation: &proof::Revocation, fetched_from: FetchSource) {
+        let from = &revocation.from();
+        self.record_url_from_from_field(&revocation.date_utc(), &from, &fetched_from);
+
+        let since = revocation
+            .revoked_since
+            .map(|since| since.with_timezone(&Utc));
+        self.revoked_since
+            .entry(from.id.clone())
+            // with multiple revocations, the earliest one wins
+            .and_modify(|existing| {
+                *existing = match (*existing, since) {
+                    (Some(existing), Some(since)) => Some(std::cmp::min(existing, since)),
+                    _ => None,
+                }
+            })
+            .or_insert(since);
+    }
+
+    fn add_key_link(&mut self, link: &proof::KeyLink, fetched_from: FetchSource) {
+        let from = &link.from();
+        self.record_url_from_from_field(&link.date_utc(), &from, &fetched_from);
+
+        let new_id = Timestamped {
+            date: link.date_utc(),
+            value: from.id.clone(),
+        };
+        self.key_links
+            .entry(link.old_id.id.clone())
+            .and_modify(|e| e.update_to_more_recent(&new_id))
+            .or_insert(new_id);
+    }
+
+    fn add_team(&mut self, team: &proof::Team, fetched_from: FetchSource) {
+        let from = &team.from();
+        self.record_url_from_from_field(&team.date_utc(), &from, &fetched_from);
+
+        let members = Timestamped {
+            date: team.date_utc(),
+            value: Team {
+                members: team
+                    .members
+                    .iter()
+                    .map(|member| member.id.clone())
+                    .collect(),
+                threshold: team.threshold,
+            },
+        };
+        self.teams
+            .entry(from.id.clone())
+            .and_modify(|e| e.update_to_more_recent(&members))
+            .or_insert(members);
+    }
+
+    /// Members of a team Id, if it is one
+    ///
+    /// Team declarations made after the team Id was revoked are ignored.
+    pub fn get_team(&self, id: &Id) -> Option<&Team> {
+        self.teams
+            .get(id)
+            .filter(|team| !self.is_proof_revoked(id, team.date))
+            .map(|team| &team.value)
+    }
+
+    /// Ids that replaced `id` through key rotation, in order
+    ///
+    /// Links created by an old Id after it was revoked are ignored,
+    /// as they might have been created by whoever stole the key.
+    pub fn get_linked_ids(&self, id: &Id) -> Vec<&Id> {
+        let mut linked = vec![];
+        let mut current = id;
+        while let Some(link) = self.key_links.get(current) {
+            if self.is_proof_revoked(current, link.date)
+                || link.value == *id
+                || linked.contains(&&link.value)
+            {
+                break;
+            }
+            linked.push(&link.value);
+            current = &link.value;
+        }
+        linked
+    }
+
+    /// Whether an Id has been revoked (entirely, or since some date)
+    pub fn is_revoked(&self, id: &Id) -> bool {
+        self.revoked_since.contains_key(id)
+    }
+
+    /// Date since which the proofs of a revoked Id are invalid
+    ///
+    /// `Some(None)` if all the proofs are invalid, `None` if the Id was not revoked.
+    pub fn get_revoked_since(&self, id: &Id) -> Option<Option<DateTime<Utc>>> {
+        self.revoked_since.get(id).copied()
+    }
+
+    /// Whether a proof by `author`, created at `date`, was invalidated by a revocation
+    pub fn is_proof_revoked(&self, author: &Id, date: DateTime<Utc>) -> bool {
+        match self.revoked_since.get(author) {
+            Some(Some(since)) => *since <= date,
+            Some(None) => true,
+            None => false,
+        }
+    }
+
+    /// Find a known Id by its string form
+    ///
+    /// Unlike CrevIDs, OpenPGP Ids can't be created from their string form
+    /// (fingerprint), so they have to be found among the Ids seen in proofs.
+    pub fn find_id(&self, id_str: &str) -> Option<Id> {
+        self.all_known_ids()
+            .into_iter()
+            .find(|id| id.to_string().eq_ignore_ascii_case(id_str))
+    }
+
     pub fn all_known_ids(&self) -> BTreeSet<Id> {
         self.url_by_id_self_reported
             .keys()
@@ -903,6 +1148,11 @@ impl ProofDB {
             .collect()
     }
 
+    /// Whether `id` is an author of unsigned reviews, like an advisory database
+    pub fn is_unsigned_author(&self, id: &Id) -> bool {
+        self.unsigned_authors_trust.contains_key(id)
+    }
+
     /// Get all Ids that authored a proof (with total count)
     pub fn all_author_ids(&self) -> BTreeMap<Id, usize> {
         let mut res = BTreeMap::new();
@@ -959,6 +1209,10 @@ impl ProofDB {
         from: &crev_data::PublicId,
         fetched_from: &FetchSource,
     ) {
+        // authors of unsigned advisories don't have proof repos
+        if let FetchSource::AdvisoryDb(_) = fetched_from {
+            return;
+        }
         if let Some(url) = &from.url {
             let tu = TimestampedUrl {
                 value: url.clone(),
@@ -986,34 +1240,142 @@ impl ProofDB {
             .verify()
             .expect("All proofs were supposed to be valid here");
         match proof.kind() {
-            proof::CodeReview::KIND => self.add_code_review(&proof.parse_content()?, fetched_from),
+            proof::CodeReview::KIND => {
+                self.add_code_review(&proof.parse_content()?, proof.signature(), fetched_from)
+            }
             proof::PackageReview::KIND => {
                 self.add_package_review(&proof.parse_content()?, proof.signature(), fetched_from)
             }
             proof::Trust::KIND => self.add_trust(&proof.parse_content()?, fetched_from),
+            proof::Revocation::KIND => self.add_revocation(&proof.parse_content()?, fetched_from),
+            proof::KeyLink::KIND => self.add_key_link(&proof.parse_content()?, fetched_from),
+            proof::Team::KIND => self.add_team(&proof.parse_content()?, fetched_from),
             other => Err(Error::UnknownProofType(other.into()))?,
         }
 
         Ok(())
     }
 
+    /// Import proofs
+    ///
+    /// Proofs made after their author's Id got revoked are ignored, as they
+    /// could otherwise replace older, legitimate proofs of the same author.
     pub fn import_from_iter(&mut self, i: impl Iterator<Item = (proof::Proof, FetchSource)>) {
-        for (proof, fetch_source) in i {
+        // revocations go first, so the proofs they invalidate are never imported
+        let (revocations, others): (Vec<_>, Vec<_>) =
+            i.partition(|(proof, _)| proof.kind() == proof::Revocation::KIND);
+        for (proof, fetch_source) in revocations.into_iter().chain(others) {
+            let is_revocation = proof.kind() == proof::Revocation::KIND;
+            let author = &proof.from().id;
+            if !is_revocation && self.is_proof_revoked(author, proof.date_utc()) {
+                debug!("Ignoring proof made after the revocation of {}", author);
+                continue;
+            }
             // ignore errors
-            if let Err(e) = self.add_proof(&proof, fetch_source) {
+            if let Err(e) = self.add_proof(&proof, fetch_source.clone()) {
                 debug!("Ignoring proof: {}", e);
+                continue;
+            }
+            if !is_revocation {
+                self.last_proof_date_by_author
+                    .entry(author.clone())
+                    .and_modify(|date| *date = std::cmp::max(*date, proof.date_utc()))
+                    .or_insert_with(|| proof.date_utc());
+            }
+            self.proofs.push((proof, fetch_source));
+        }
+
+        // proofs imported before are already indexed, and could have replaced
+        // proofs made before the revocation
+        if self
+            .last_proof_date_by_author
+            .iter()
+            .any(|(author, date)| self.is_proof_revoked(author, *date))
+        {
+            self.reimport();
+        }
+    }
+
+    /// Import all the proofs again into an empty db, now without the revoked ones
+    fn reimport(&mut self) {
+        let proofs = std::mem::take(&mut self.proofs);
+        let unsigned_package_reviews = std::mem::take(&mut self.unsigned_package_reviews);
+        let unsigned_authors_trust = std::mem::take(&mut self.unsigned_authors_trust);
+        *self = ProofDB {
+            unsigned_authors_trust,
+            ..default()
+        };
+
+        self.import_from_iter(proofs.into_iter());
+        for (review, signature, fetched_from) in unsigned_package_reviews {
+            self.add_unsigned_package_review(review, signature, fetched_from);
+        }
+    }
+
+    /// Import package reviews that come without signatures, like advisories
+    /// converted from an advisory database
+    ///
+    /// Authors of these reviews are trusted directly by `trusted_by` (usually
+    /// the local user, who fetched them), with `trust_level`, and only in its
+    /// own trust set.
+    pub fn import_unsigned_package_reviews(
+        &mut self,
+        i: impl Iterator<Item = review::Package>,
+        fetched_from: FetchSource,
+        trusted_by: Option<&Id>,
+        trust_level: TrustLevel,
+    ) {
+        for review in i {
+            if let Err(e) = review.validate_data() {
+                debug!("Ignoring unsigned review: {}", e);
+                continue;
             }
+            self.unsigned_authors_trust.insert(
+                review.from().id.clone(),
+                trusted_by.map(|trusted_by| (trusted_by.clone(), trust_level)),
+            );
+            // there's no signature, but reviews are still stored by one
+            let signature = format!(
+                "unsigned-{}",
+                crev_common::base64_encode(&crev_common::blake2b256sum(
+                    review.to_string().as_bytes()
+                ))
+            );
+            self.add_unsigned_package_review(review, signature, fetched_from.clone());
         }
     }
 
-    fn get_trust_list_of_id(&self, id: &Id) -> impl Iterator<Item = (TrustLevel, &Id)> {
-        if let Some(map) = self.trust_id_to_id.get(id) {
-            Some(map.iter().map(|(id, trust)| (trust.value, id)))
-        } else {
-            None
+    fn add_unsigned_package_review(
+        &mut self,
+        review: review::Package,
+        signature: Signature,
+        fetched_from: FetchSource,
+    ) {
+        self.add_package_review(&review, &signature, fetched_from.clone());
+        self.unsigned_package_reviews
+            .push((review, signature, fetched_from));
+    }
+
+    /// Ids trusted by `id`, skipping trust that has expired by `now`, or was revoked
+    ///
+    /// Trust in an Id extends to the Ids it was rotated to.
+    fn get_trust_list_of_id(
+        &self,
+        id: &Id,
+        params: &TrustDistanceParams,
+        now: DateTime<Utc>,
+    ) -> Vec<(TrustLevel, &Id)> {
+        let mut list = vec![];
+        for (to, trust) in self.trust_id_to_id.get(id).into_iter().flatten() {
+            if params.is_trust_expired(trust, now) || self.is_proof_revoked(id, trust.date) {
+                continue;
+            }
+            list.push((trust.value.level, to));
+            for linked_id in self.get_linked_ids(to) {
+                list.push((trust.value.level, linked_id));
+            }
         }
-        .into_iter()
-        .flatten()
+        list
     }
 
     pub fn calculate_trust_set(&self, for_id: &Id, params: &TrustDistanceParams) -> TrustSet {
@@ -1046,6 +1408,7 @@ impl ProofDB {
             id: Id,
         }
 
+        let now = crev_common::now().with_timezone(&Utc);
         let mut pending = BTreeSet::new();
         let mut visited = TrustSet::default();
         visited.distrusted = distrusted;
@@ -1060,7 +1423,8 @@ impl ProofDB {
             debug!("Traversing id: {:?}", current);
             pending.remove(&current);
 
-            for (direct_trust, candidate_id) in self.get_trust_list_of_id(&&current.id) {
+            for (direct_trust, candidate_id) in self.get_trust_list_of_id(&current.id, params, now)
+            {
                 debug!(
                     "{} trusts {} - level: {}",
                     current.id, candidate_id, direct_trust
@@ -1071,6 +1435,13 @@ impl ProofDB {
                     continue;
                 }
 
+                // a fully revoked Id can't be trusted anymore;
+                // one revoked since some date is handled per-proof
+                if self.get_revoked_since(candidate_id) == Some(None) {
+                    debug!("{} is revoked", candidate_id);
+                    continue;
+                }
+
                 if direct_trust == TrustLevel::Distrust {
                     debug!("Adding {} to distrusted list", candidate_id);
                     visited
@@ -1140,6 +1511,24 @@ impl ProofDB {
             }
         }
 
+        // authors of unsigned reviews don't trust anyone, so there's no need to visit them;
+        // they are added last, once all the Ids distrusting them have been visited
+        for (id, trust) in &self.unsigned_authors_trust {
+            let trust_level = match trust {
+                Some((trusted_by, trust_level)) if trusted_by == for_id => *trust_level,
+                _ => continue,
+            };
+            if visited.distrusted.contains_key(id) {
+                continue;
+            }
+            if let Some(distance) = params
+                .distance_by_level(trust_level)
+                .filter(|&distance| distance <= params.max_distance)
+            {
+                visited.record_trusted_id(id.clone(), for_id.clone(), distance, trust_level);
+            }
+        }
+
         visited
     }
 
@@ -1204,13 +1593,32 @@ impl<'a> UrlOfId<'a> {
     }
 }
 
+/// Trust an Id gets through one of the Ids trusting it
+#[derive(Debug, Clone, Copy)]
+struct RefererDetails {
+    // effective trust, when reached through this referer
+    effective_trust: TrustLevel,
+    // distance from the root, when reached through this referer
+    distance: u64,
+}
+
 /// Details of a one Id that is
 #[derive(Debug, Clone)]
 struct TrustedIdDetails {
     distance: u64,
     // effective, global trust from the root of the WoT
     effective_trust: TrustLevel,
-    referers: HashMap<Id, TrustLevel>,
+    referers: HashMap<Id, RefererDetails>,
+}
+
+/// One step of a path in the WoT, from the root to some trusted Id
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrustPathHop {
+    pub id: Id,
+    /// Effective trust of `id`, when reached through the previous hop
+    pub effective_trust: TrustLevel,
+    /// Distance of `id` from the root, when reached through the previous hop
+    pub distance: u64,
 }
 
 #[derive(Default, Debug, Clone)]
@@ -1253,7 +1661,13 @@ impl TrustSet {
         match self.trusted.entry(subject) {
             Entry::Vacant(entry) => {
                 let mut referers = HashMap::default();
-                referers.insert(referer, effective_trust);
+                referers.insert(
+                    referer,
+                    RefererDetails {
+                        effective_trust,
+                        distance,
+                    },
+                );
                 entry.insert(TrustedIdDetails {
                     distance,
                     effective_trust,
@@ -1274,13 +1688,20 @@ impl TrustSet {
                 }
                 match details.referers.entry(referer) {
                     Entry::Vacant(entry) => {
-                        entry.insert(effective_trust);
+                        entry.insert(RefererDetails {
+                            effective_trust,
+                            distance,
+                        });
                         changed = true;
                     }
                     Entry::Occupied(mut entry) => {
-                        let level = entry.get_mut();
-                        if *level < effective_trust {
-                            *level = effective_trust;
+                        let referer_details = entry.get_mut();
+                        if referer_details.effective_trust < effective_trust {
+                            referer_details.effective_trust = effective_trust;
+                            changed = true;
+                        }
+                        if referer_details.distance > distance {
+                            referer_details.distance = distance;
                             changed = true;
                         }
                     }
@@ -1298,6 +1719,95 @@ impl TrustSet {
     pub fn get_effective_trust_level_opt(&self, id: &Id) -> Option<TrustLevel> {
         self.trusted.get(id).map(|details| details.effective_trust)
     }
+
+    /// Distance of a trusted Id from the root of the WoT
+    pub fn get_distance(&self, id: &Id) -> Option<u64> {
+        self.trusted.get(id).map(|details| details.distance)
+    }
+
+    /// Ids directly trusting a trusted Id, with the effective trust and distance through each
+    pub fn get_referers<'s>(
+        &'s self,
+        id: &Id,
+    ) -> impl Iterator<Item = (&'s Id, TrustLevel, u64)> + 's {
+        let id = id.clone();
+        self.trusted
+            .get(&id)
+            .into_iter()
+            .flat_map(|details| details.referers.iter())
+            .filter(move |(referer, _)| **referer != id)
+            .map(|(referer, details)| (referer, details.effective_trust, details.distance))
+    }
+
+    /// Ids that distrusted a given Id
+    pub fn get_distrusted_by(&self, id: &Id) -> Option<&HashSet<Id>> {
+        self.distrusted.get(id)
+    }
+
+    /// Path with the lowest distance from the root of the WoT to a trusted Id
+    ///
+    /// Starts with the root, and ends with `id`.
+    pub fn get_shortest_trust_path(&self, id: &Id) -> Option<Vec<TrustPathHop>> {
+        self.get_trust_path_by(id, |a, b| {
+            a.distance
+                .cmp(&b.distance)
+                .then(b.effective_trust.cmp(&a.effective_trust))
+        })
+    }
+
+    /// Path with the highest effective trust from the root of the WoT to a trusted Id
+    ///
+    /// Starts with the root, and ends with `id`.
+    pub fn get_strongest_trust_path(&self, id: &Id) -> Option<Vec<TrustPathHop>> {
+        self.get_trust_path_by(id, |a, b| {
+            b.effective_trust
+                .cmp(&a.effective_trust)
+                .then(a.distance.cmp(&b.distance))
+        })
+    }
+
+    /// Walk back from `id` to the root, at each step picking
+    /// the referer that compares as the lowest by `cmp`
+    fn get_trust_path_by(
+        &self,
+        id: &Id,
+        cmp: impl Fn(&RefererDetails, &RefererDetails) -> std::cmp::Ordering,
+    ) -> Option<Vec<TrustPathHop>> {
+        let mut path = vec![];
+        let mut visited = HashSet::new();
+        let mut current = id.clone();
+
+        loop {
+            let details = self.trusted.get(&current)?;
+
+            // the root is the only Id that refers to itself
+            if details.referers.contains_key(&current) {
+                path.push(TrustPathHop {
+                    id: current,
+                    effective_trust: details.effective_trust,
+                    distance: details.distance,
+                });
+                break;
+            }
+
+            visited.insert(current.clone());
+            let (referer, through_referer) = details
+                .referers
+                .iter()
+                .filter(|(referer, _)| !visited.contains(*referer))
+                .min_by(|a, b| cmp(a.1, b.1))?;
+
+            path.push(TrustPathHop {
+                id: current,
+                effective_trust: through_referer.effective_trust,
+                distance: through_referer.distance,
+            });
+            current = referer.clone();
+        }
+
+        path.reverse();
+        Some(path)
+    }
 }
 
 pub struct TrustDistanceParams {
@@ -1305,6 +1815,8 @@ pub struct TrustDistanceParams {
     pub high_trust_distance: u64,
     pub medium_trust_distance: u64,
     pub low_trust_distance: u64,
+    /// Trust proofs older than this are ignored, unless re-signed
+    pub trust_max_age: Option<chrono::Duration>,
 }
 
 impl TrustDistanceParams {
@@ -1314,9 +1826,24 @@ impl TrustDistanceParams {
             high_trust_distance: 1,
             medium_trust_distance: 1,
             low_trust_distance: 1,
+            trust_max_age: None,
         }
     }
 
+    /// Trust expires when its `valid-until` passes, or it gets older than `trust_max_age`
+    ///
+    /// Distrust never expires.
+    fn is_trust_expired(&self, trust: &TimestampedTrustEdge, now: DateTime<Utc>) -> bool {
+        trust.value.level != TrustLevel::Distrust
+            && (trust
+                .value
+                .valid_until
+                .map_or(false, |valid_until| valid_until < now)
+                || self
+                    .trust_max_age
+                    .map_or(false, |max_age| trust.date + max_age < now))
+    }
+
     fn distance_by_level(&self, level: TrustLevel) -> Option<u64> {
         use crev_data::proof::trust::TrustLevel::*;
         Some(match level {
@@ -1336,6 +1863,7 @@ impl Default for TrustDistanceParams {
             high_trust_distance: 0,
             medium_trust_distance: 1,
             low_trust_distance: 5,
+            trust_max_age: None,
         }
     }
 }